@@ -1,10 +1,17 @@
 use add_ed::{Ed, Result};
 use add_ed::error::UIError;
 use add_ed::ui::{UI, UILock};
+use add_ed::io::{IO, RestrictedIO};
+use add_ed::macros::{Macro, MacroGetter};
+use add_ed::options::OptionValue;
+use std::collections::HashMap;
+use std::io::{Write, IsTerminal};
+use std::path::PathBuf;
 /// Error type for a [`ClassicUI`]
 #[derive(Debug)]
 enum ClassicUIError {
   TerminalError,
+  EndOfInput,
   #[cfg(feature = "initial_input_data")]
   InitialData,
 }
@@ -13,6 +20,7 @@ impl std::fmt::Display for ClassicUIError {
     use ClassicUIError::*;
     match self {
       TerminalError => write!(f, "Failed to read from terminal. This is bad, save if you can."),
+      EndOfInput => write!(f, "Reached end of input."),
       #[cfg(feature = "initial_input_data")]
       InitialData => write!(f, "UI received initial data when taking input, this isn't supported."),
     }
@@ -22,7 +30,12 @@ impl std::error::Error for ClassicUIError {}
 impl add_ed::error::UIErrorTrait for ClassicUIError {}
 
 /// A simple UI based on the original ED editor
-struct ClassicUI{}
+struct ClassicUI{
+  /// Printed in place of the command prefix, if set
+  prompt: Option<String>,
+  /// Set when reading from stdin reaches end of file
+  eof: bool,
+}
 impl UI for ClassicUI {
   fn print_message(
     &mut self,
@@ -37,11 +50,24 @@ impl UI for ClassicUI {
   fn get_command(
     &mut self,
     _ed: &Ed,
-    _prefix: Option<char>,
+    prefix: Option<char>,
   ) -> Result<String> {
+    // Ed only gives the prefix while the prompt is shown, as toggled by `P`
+    if let Some(prefix) = prefix {
+      match &self.prompt {
+        Some(prompt) => print!("{}", prompt),
+        None => print!("{}", prefix),
+      }
+      std::io::stdout().flush()
+        .map_err(|_| -> UIError { ClassicUIError::TerminalError.into() })?;
+    }
     let mut input = String::new();
-    std::io::stdin().read_line(&mut input)
+    let read = std::io::stdin().read_line(&mut input)
       .map_err(|_| -> UIError { ClassicUIError::TerminalError.into() })?;
+    if read == 0 {
+      self.eof = true;
+      return Err(Into::<UIError>::into(ClassicUIError::EndOfInput).into());
+    }
     Ok(input)
   }
   fn get_input(
@@ -62,8 +88,13 @@ impl UI for ClassicUI {
     loop {
       let mut buf = String::new();
       let res = stdin.read_line(&mut buf);
-      if res.is_err() {
-        return Err(Into::<UIError>::into(ClassicUIError::TerminalError).into());
+      match res {
+        Err(_) => return Err(
+          Into::<UIError>::into(ClassicUIError::TerminalError).into()
+        ),
+        // End of file ends input, as the terminator would
+        Ok(0) => { self.eof = true; return Ok(input); },
+        Ok(_) => (),
       }
       if buf == terminator { return Ok(input); }
       else { input.push(buf); }
@@ -104,31 +135,230 @@ impl UI for ClassicUI {
   fn unlock_ui(&mut self) {}
 }
 
+/// Configuration, read from a JSON file
+///
+/// All fields are optional, those not given keep the `Ed` defaults.
+#[derive(Default, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Config {
+  n: Option<bool>,
+  l: Option<bool>,
+  print_errors: Option<bool>,
+  quiet: Option<bool>,
+  shell_quote: Option<bool>,
+  /// A single character, or "none" to not print a prefix
+  cmd_prefix: Option<String>,
+  /// Wether to show the prompt from start, as toggled by `P`
+  show_cmd_prefix: Option<bool>,
+  /// A prompt to print instead of `cmd_prefix`, which may be longer
+  prompt: Option<String>,
+  recursion_limit: Option<usize>,
+  /// Macros by name, in the serde representation of `Macro`
+  macros: ConfigMacros,
+}
+/// The macros defined in the config
+#[derive(Default, serde::Deserialize)]
+#[serde(transparent)]
+struct ConfigMacros(HashMap<String, Macro>);
+impl MacroGetter for ConfigMacros {
+  fn get_macro(&self, name: &str) -> Result<Option<&Macro>> {
+    Ok(self.0.get(name))
+  }
+}
+impl Config {
+  // The XDG config paths to look for the config file in, most important first
+  fn default_paths() -> Vec<PathBuf> {
+    let home_config = std::env::var_os("XDG_CONFIG_HOME")
+      .map(PathBuf::from)
+      .filter(|p| p.is_absolute())
+      .or_else(|| std::env::var_os("HOME")
+        .map(|home| PathBuf::from(home).join(".config"))
+      )
+    ;
+    let config_dirs = std::env::var("XDG_CONFIG_DIRS")
+      .ok()
+      .filter(|x| !x.is_empty())
+      .unwrap_or_else(|| "/etc/xdg".to_owned())
+    ;
+    home_config.into_iter()
+      .chain(config_dirs.split(':').map(PathBuf::from).filter(|p| p.is_absolute()))
+      .map(|dir| dir.join("classic-ed").join("config.json"))
+      .collect()
+  }
+  /// Load the given config file, or else the first found in the XDG paths
+  ///
+  /// Gives the default config if no path is given and no file is found.
+  fn load(path: Option<PathBuf>) -> std::result::Result<Self, String> {
+    let path = match path {
+      Some(p) => p,
+      None => match Self::default_paths().into_iter().find(|p| p.is_file()) {
+        Some(p) => p,
+        None => return Ok(Self::default()),
+      },
+    };
+    let data = std::fs::read_to_string(&path)
+      .map_err(|e| format!("Failed to read config `{}`: {}", path.display(), e))?;
+    serde_json::from_str(&data)
+      .map_err(|e| format!("Invalid config `{}`: {}", path.display(), e))
+  }
+  /// Set the configured defaults on the editor
+  fn apply(&self, ed: &mut Ed) -> Result<()> {
+    if let Some(n) = self.n { ed.set_option("n", OptionValue::Bool(n))?; }
+    if let Some(l) = self.l { ed.set_option("l", OptionValue::Bool(l))?; }
+    if let Some(x) = self.print_errors {
+      ed.set_option("print_errors", OptionValue::Bool(x))?;
+    }
+    if let Some(x) = self.quiet { ed.set_option("quiet", OptionValue::Bool(x))?; }
+    if let Some(x) = self.shell_quote {
+      ed.set_option("shell_quote", OptionValue::Bool(x))?;
+    }
+    if let Some(x) = &self.cmd_prefix { ed.set_option_text("cmd_prefix", x)?; }
+    if let Some(x) = self.show_cmd_prefix {
+      ed.set_option("show_cmd_prefix", OptionValue::Bool(x))?;
+    }
+    if let Some(x) = self.recursion_limit {
+      ed.set_option("recursion_limit", OptionValue::Number(x))?;
+    }
+    Ok(())
+  }
+}
+
 use clap::Parser;
+/// An editor in the style of GNU Ed, accepting its command line arguments
 #[derive(Parser)]
 #[command(version)]
 struct CliArgs {
   /// Path to file to open or ! followed by command to read output from
   #[arg(default_value_t)] // Default to empty string
   file: String,
+  /// Path to config file, instead of classic-ed/config.json in the XDG config
+  /// directories
+  #[arg(long)]
+  config: Option<PathBuf>,
+  /// Print the given prompt before reading each command. Toggle it with `P`
+  #[arg(short, long)]
+  prompt: Option<String>,
+  /// Don't print byte counts and the `!` after shell commands, and quit at end
+  /// of input even with unsaved changes. For use in scripts
+  #[arg(short = 's', long, visible_alias = "silent")]
+  quiet: bool,
+  /// Forbid shell commands and editing files outside the current directory
+  #[arg(short, long)]
+  restricted: bool,
+  /// Print error messages instead of `?` (as toggled by `H`)
+  #[arg(short, long)]
+  verbose: bool,
+  /// Use extended regular expressions. Always the case, accepted for
+  /// compatibility
+  #[arg(short = 'E', long)]
+  extended_regexp: bool,
+  /// Exit with status 0 even if commands failed
+  #[arg(short, long)]
+  loose_exit_status: bool,
 }
+
+// Exit statuses are those of GNU Ed: 1 if anything failed, including opening
+// the file given (unless --loose-exit-status), 2 if a script ends with unsaved
+// changes.
 fn main() {
-  let cli = CliArgs::parse();
+  let cli = match CliArgs::try_parse() {
+    Ok(c) => c,
+    Err(e) => {
+      // Print help or error, exiting with error only for invalid arguments
+      let status = if e.use_stderr() { 1 } else { 0 };
+      let _ = e.print();
+      std::process::exit(status);
+    },
+  };
+  // If stdin isn't a terminal (a file, pipe or here-document) we are running a
+  // script, which errors abort (as GNU Ed does)
+  let script = !std::io::stdin().is_terminal();
+  let error_status = if cli.loose_exit_status { 0 } else { 1 };
   // Construct state components
-  let mut ui = ClassicUI{};
-  let mut io = add_ed::io::LocalIO::new();
-  let macro_store = std::collections::HashMap::new();
+  let mut ui = ClassicUI{ prompt: None, eof: false };
+  let mut local_io = add_ed::io::LocalIO::new();
+  let mut restricted_io;
+  let io: &mut dyn IO = if cli.restricted {
+    let cwd = match std::env::current_dir() {
+      Ok(dir) => dir,
+      Err(e) => {
+        ui.print_message(&format!("Failed to get current directory: {}", e))
+          .expect("Failed to print error after failing to get current directory");
+        std::process::exit(1);
+      },
+    };
+    restricted_io = RestrictedIO::new(&mut local_io, cwd).allow_dir(".");
+    &mut restricted_io
+  } else {
+    &mut local_io
+  };
+  let mut config = match Config::load(cli.config) {
+    Ok(c) => c,
+    Err(e) => {
+      ui.print_message(&e).expect("Failed to print error after failing to load config");
+      std::process::exit(1);
+    },
+  };
+  let macro_store = std::mem::take(&mut config.macros);
   // Construct Ed
-  let mut ed = Ed::new(&mut io, &macro_store);
-  // Apply any configurations
+  let mut ed = Ed::new(io, &macro_store);
+  // Print `?` on error and hide the `*` prompt by default, as GNU Ed
+  ed.print_errors = false;
+  ed.cmd_prefix = Some('*');
+  ed.show_cmd_prefix = false;
+  // Shell quote substituted file names and commands, unless configured not to
+  ed.shell_quote = true;
+  // Apply any configurations, then the command line arguments
+  if let Err(e) = config.apply(&mut ed) {
+    ui.print_message(&format!("Invalid config: {}", e)).expect("Failed to print error after failing to apply config");
+    std::process::exit(1);
+  }
+  // A prompt given as argument is shown from start, as in GNU Ed
+  if cli.prompt.is_some() { ed.show_cmd_prefix = true; }
+  ui.prompt = cli.prompt.or(config.prompt);
+  if cli.verbose { ed.print_errors = true; }
+  if cli.quiet { ed.quiet = true; }
+  let print_error = |ui: &mut ClassicUI, ed: &Ed, e: add_ed::EdError| {
+    if ed.print_errors { ui.print_message(&e.to_string()) }
+    else { ui.print_message("?") }
+      .expect("Failed to print error.")
+  };
+  let mut status = 0;
   // Load in from path if given
   if ! cli.file.is_empty() {
     if let Err(e) = ed.run_command(&mut ui, &format!("e{}", cli.file)) {
-      // On failure to open file we print error and quit
-      ui.print_message(&e.to_string()).expect("Failed to print error after failing to open file");
-      return;
+      // On failure to open file we print error and continue with an empty
+      // buffer, unless running a script
+      print_error(&mut ui, &ed, e);
+      if script { std::process::exit(error_status); }
+      status = error_status;
+    }
+  }
+  // Run until quit or end of input
+  let mut warned = false;
+  loop {
+    match ed.get_and_run_command(&mut ui) {
+      Ok(true) => break,
+      Ok(false) => (),
+      // At end of input quit, but warn once about unsaved changes unless quiet
+      Err(_) if ui.eof => {
+        let quit = if cli.quiet || warned { "Q\n" } else { "q\n" };
+        match ed.run_command(&mut ui, quit) {
+          Ok(_) => break,
+          Err(e) => {
+            print_error(&mut ui, &ed, e);
+            if script { std::process::exit(2); }
+            warned = true;
+            ui.eof = false;
+          },
+        }
+      },
+      Err(e) => {
+        print_error(&mut ui, &ed, e);
+        status = error_status;
+        if script { break; }
+      },
     }
   }
-  // Run
-  ed.run(&mut ui).expect("Failed to print during execution.");
+  std::process::exit(status);
 }
@@ -11,6 +11,8 @@ use crate::{EdError, Result};
 ///
 /// Needed due to orphan rules.
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature="serde", serde(transparent))]
 pub struct Clipboard {
   inner: Vec<PubLine>,
 }
@@ -67,6 +69,19 @@ impl<'a> TryFrom<&'a [&str]> for Clipboard {
   }
 }
 
+impl<'a> From<&'a Buffer> for Clipboard {
+  fn from(b: &'a Buffer) -> Self {
+    b.iter().collect()
+  }
+}
+impl<'a> FromIterator<&'a Line> for Clipboard {
+  fn from_iter<I: IntoIterator<Item = &'a Line>>(iter: I) -> Self {
+    Self{
+      inner: iter.into_iter().map(|line| line.into()).collect(),
+    }
+  }
+}
+
 impl Into<Vec<Line>> for &Clipboard {
   fn into(self) -> Vec<Line> {
     let mut tmp = Vec::new();
@@ -77,12 +92,19 @@ impl Into<Vec<Line>> for &Clipboard {
   }
 }
 
-/// Declare a type over Vec<Line>, to be able to add some utility methods
+/// The editing buffer, a list of [`Line`]s with some utility methods
+///
+/// Internally the lines are stored in reference counted chunks, so that
+/// snapshots (see [`History`](crate::History)) can share all chunks that
+/// aren't modified. Snapshotting and editing cost one pointer copy per chunk
+/// of 512 lines rather than per line, which keeps them cheap even for very
+/// large files. Since the lines aren't stored contiguously the API is modeled
+/// after [`std::collections::VecDeque`] rather than `Vec`, with `.range()` in
+/// place of slicing.
 ///
-/// Though `Deref<Target = Vec<Line>>` gives free access to the Buffer internals
-/// the restrictions upon Line makes it a bit difficult to make use of this. The
-/// intended method is to convert to and from [`Clipboard`] or [`PubLine`] as
-/// shown below.
+/// The restrictions upon Line make it a bit difficult to construct lines
+/// directly. The intended method is to convert to and from [`Clipboard`] or
+/// [`PubLine`] as shown below.
 ///
 /// Examples of how to construct Line instances to insert into the Buffer:
 /// ```
@@ -131,7 +153,9 @@ impl Into<Vec<Line>> for &Clipboard {
 /// buffer.append(&mut (&pub_lines).into());
 /// // Getting data out in clipboard format is quite easy (and generally the
 /// // way to go, unless you are just moving Lines around).
-/// let fetched_data: Clipboard = (&buffer[..]).into();
+/// let fetched_data: Clipboard = (&buffer).into();
+/// // Or only some of the lines, via `.range()`
+/// let fetched_data: Clipboard = buffer.range(1..3).collect();
 /// // If you want you can also use the iterators on Buffer
 /// let fetched_data: Vec<String> = buffer.get_lines((1,buffer.len()))
 ///   .expect("Invalid selection")
@@ -139,35 +163,157 @@ impl Into<Vec<Line>> for &Clipboard {
 ///   .collect()
 /// ;
 /// ```
-#[derive(Debug, PartialEq)]
 pub struct Buffer {
-  pub inner: Vec<Line>,
+  inner: ChunkedVec<Line>,
 }
-impl std::ops::Deref for Buffer {
-  type Target = Vec<Line>;
-  fn deref(&self) -> &Self::Target {
-    &self.inner
+impl std::fmt::Debug for Buffer {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    f.debug_list().entries(self.iter()).finish()
   }
 }
-impl std::ops::DerefMut for Buffer {
-  fn deref_mut(&mut self) -> &mut Self::Target {
-    &mut self.inner
+impl PartialEq for Buffer {
+  fn eq(&self, other: &Self) -> bool {
+    // Snapshots of an unmodified buffer share all their chunks, which lets us
+    // skip comparing them line by line
+    self.len() == other.len() && (
+      self.inner.shares_chunks(&other.inner) ||
+      self.iter().eq(other.iter())
+    )
   }
 }
 // Manually implement a special clone for History
 impl Snapshot for Buffer {
   fn create_snapshot(&self) -> Self {
-    let mut new_inner = Vec::new();
-    for line in self.inner.iter() {
-      new_inner.push(line.create_snapshot());
-    }
-    Self{ inner: new_inner }
+    Self{ inner: self.inner.create_snapshot() }
+  }
+  /// Counts the chunks of lines not shared with base, and the texts in those
+  /// that aren't in the chunks of base they replace.
+  fn estimate_unshared_memory(&self, base: &Self) -> usize {
+    let replaced: std::collections::HashSet<*const String> = base.inner
+      .unshared_chunks(&self.inner)
+      .flat_map(|chunk| chunk.iter().map(|line| line.text.id()))
+      .collect()
+    ;
+    std::mem::size_of::<Self>() + self.inner.unshared_chunks(&base.inner)
+      .map(|chunk| {
+        std::mem::size_of::<Vec<Line>>() + chunk.iter().map(|line| {
+          std::mem::size_of::<Line>() +
+            if replaced.contains(&line.text.id()) { 0 } else { line.text.len() }
+        }).sum::<usize>()
+      })
+      .sum::<usize>()
+  }
+  /// Compares only the texts of the lines, ignoring tags. Snapshots mostly
+  /// share their chunks and texts, so pointer equality is checked first.
+  fn content_eq(&self, other: &Self) -> bool {
+    self.len() == other.len() && (
+      self.inner.shares_chunks(&other.inner) ||
+      self.iter().zip(other.iter()).all(|(a, b)| {
+        a.text.id() == b.text.id() || a.text == b.text
+      })
+    )
   }
 }
 impl Default for Buffer {
-  fn default() -> Self{ Self{ inner: Vec::new() } }
+  fn default() -> Self{ Self{ inner: ChunkedVec::new() } }
+}
+impl std::ops::Index<usize> for Buffer {
+  type Output = Line;
+  fn index(&self, index: usize) -> &Self::Output {
+    match self.inner.get(index) {
+      Some(line) => line,
+      None => panic!(
+        "index out of bounds: the len is {} but the index is {}",
+        self.len(),
+        index,
+      ),
+    }
+  }
+}
+impl From<&Clipboard> for Buffer {
+  fn from(c: &Clipboard) -> Self {
+    c.iter().map(|line| line.into()).collect()
+  }
+}
+impl From<Vec<Line>> for Buffer {
+  fn from(v: Vec<Line>) -> Self {
+    v.into_iter().collect()
+  }
+}
+impl FromIterator<Line> for Buffer {
+  fn from_iter<I: IntoIterator<Item = Line>>(iter: I) -> Self {
+    let mut tmp = Self::default();
+    tmp.extend(iter);
+    tmp
+  }
+}
+impl Extend<Line> for Buffer {
+  fn extend<I: IntoIterator<Item = Line>>(&mut self, iter: I) {
+    for line in iter {
+      self.inner.push(line);
+    }
+  }
+}
+impl<'a> IntoIterator for &'a Buffer {
+  type Item = &'a Line;
+  type IntoIter = BufferIter<'a>;
+  fn into_iter(self) -> Self::IntoIter {
+    self.iter()
+  }
 }
 impl Buffer {
+  /// Number of lines in the buffer
+  pub fn len(&self) -> usize {
+    self.inner.len()
+  }
+  /// Returns true if there are no lines in the buffer
+  pub fn is_empty(&self) -> bool {
+    self.inner.is_empty()
+  }
+  /// Get the line at the given 0-indexed position, if any
+  pub fn get(&self, index: usize) -> Option<&Line> {
+    self.inner.get(index)
+  }
+  /// Iterate over all the lines in the buffer
+  pub fn iter(&self) -> BufferIter<'_> {
+    self.inner.iter().into()
+  }
+  /// Iterate over the lines in the given 0-indexed range
+  ///
+  /// Replaces slicing, as the lines aren't stored contiguously. Panics if the
+  /// range is out of bounds, just like slicing would.
+  pub fn range(&self, range: std::ops::Range<usize>) -> BufferIter<'_> {
+    self.inner.range(range).into()
+  }
+  /// Add a line to the end of the buffer
+  pub fn push(&mut self, line: Line) {
+    self.inner.push(line)
+  }
+  /// Move all lines from other to the end of this buffer, leaving other empty
+  pub fn append(&mut self, other: &mut Self) {
+    self.inner.append(&mut other.inner)
+  }
+  /// Split off the lines from the given 0-indexed position onwards
+  ///
+  /// Returns the split off lines as a new Buffer. Panics if `at > len`, just
+  /// like `Vec::split_off`.
+  pub fn split_off(&mut self, at: usize) -> Self {
+    Self{ inner: self.inner.split_off(at) }
+  }
+  /// Remove all lines from the buffer
+  pub fn clear(&mut self) {
+    self.inner.clear()
+  }
+  // Chunk level access, so serialization can preserve sharing between
+  // snapshots.
+  #[cfg(feature = "serde")]
+  pub(crate) fn chunks(&self) -> &[crate::sync::Shared<Vec<Line>>] {
+    self.inner.chunks()
+  }
+  #[cfg(feature = "serde")]
+  pub(crate) fn from_chunks(chunks: Vec<crate::sync::Shared<Vec<Line>>>) -> Self {
+    Self{ inner: ChunkedVec::from_chunks(chunks) }
+  }
   /// Verify that an index is valid to operate on
   ///
   /// Doesn't mean that there exists a line at the index.
@@ -217,8 +363,7 @@ impl Buffer {
     selection: (usize, usize),
   ) -> Result<LinesIter> {
     self.verify_selection(selection)?;
-    Ok(self[selection.0 - 1 .. selection.1]
-      .iter()
+    Ok(self.range(selection.0 - 1 .. selection.1)
       .map(get_lines_helper as fn(&Line) -> &str)
       .into()
     )
@@ -231,8 +376,7 @@ impl Buffer {
     selection: (usize, usize),
   ) -> Result<TaggedLinesIter> {
     self.verify_selection(selection)?;
-    Ok(self[selection.0 - 1 .. selection.1]
-      .iter()
+    Ok(self.range(selection.0 - 1 .. selection.1)
       .map(get_tagged_lines_helper as fn(&Line) -> (char, &str))
       .into()
     )
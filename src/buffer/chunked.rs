@@ -0,0 +1,380 @@
+//! The chunked storage backing [`Buffer`]
+//!
+//! Lines are stored in reference counted chunks, so that a snapshot only needs
+//! to clone one `Rc` per chunk instead of every line. When a chunk that is
+//! shared with a snapshot needs to be modified it is copied (using
+//! [`Snapshot::create_snapshot`] on its entries) before modification, which
+//! means that edits only ever copy the chunks they touch.
+//!
+//! Costs, with `n` lines and chunks of up to `CHUNK_SIZE` lines:
+//! - snapshot: O(n / CHUNK_SIZE)
+//! - index: O(log(n / CHUNK_SIZE))
+//! - split_off, append: O(CHUNK_SIZE + chunks moved)
+//! - push: amortized O(1), unless the last chunk is shared
+//!
+//! An edit splits off the lines after it and appends them back, which moves
+//! every chunk after the edit. So snapshotting and editing are both
+//! O(n / CHUNK_SIZE), not the O(log n) of a balanced tree. That is a pointer
+//! copy per `CHUNK_SIZE` lines, which is cheap even for millions of lines.
+
+use crate::sync::Shared;
+
+use super::*;
+
+/// Max number of entries in a single chunk
+///
+/// Large enough that the per-chunk overhead is negligible, small enough that
+/// copying a shared chunk upon modification is cheap.
+pub(crate) const CHUNK_SIZE: usize = 512;
+
+/// A vector-like container split into reference counted chunks
+///
+/// Invariants:
+/// - No chunk is empty.
+/// - No chunk holds more than `CHUNK_SIZE` entries.
+/// - `starts[i]` is the index of the first entry in `chunks[i]`.
+/// - `len` is the sum of all chunk lengths.
+pub(crate) struct ChunkedVec<T> {
+  chunks: Vec<Shared<Vec<T>>>,
+  starts: Vec<usize>,
+  len: usize,
+}
+
+// Takes ownership of the contents of a chunk, copying them if the chunk is
+// shared with another ChunkedVec.
+fn take_chunk<T: Snapshot>(chunk: Shared<Vec<T>>) -> Vec<T> {
+  Shared::try_unwrap(chunk).unwrap_or_else(|shared|
+    shared.iter().map(Snapshot::create_snapshot).collect()
+  )
+}
+
+impl<T: Snapshot> ChunkedVec<T> {
+  pub fn new() -> Self {
+    Self{ chunks: Vec::new(), starts: Vec::new(), len: 0 }
+  }
+  pub fn len(&self) -> usize {
+    self.len
+  }
+  pub fn is_empty(&self) -> bool {
+    self.len == 0
+  }
+
+  // Rebuilds starts and len from the chunks, when constructing from chunks
+  fn recalculate(&mut self) {
+    self.starts.clear();
+    let mut start = 0;
+    for chunk in &self.chunks {
+      self.starts.push(start);
+      start += chunk.len();
+    }
+    self.len = start;
+  }
+  // Get the chunk index and offset within chunk for the given entry index
+  //
+  // Only valid for index < self.len
+  fn locate(&self, index: usize) -> (usize, usize) {
+    let chunk_i = self.starts.partition_point(|s| *s <= index) - 1;
+    (chunk_i, index - self.starts[chunk_i])
+  }
+
+  pub fn get(&self, index: usize) -> Option<&T> {
+    if index >= self.len { return None; }
+    let (chunk_i, offset) = self.locate(index);
+    Some(&self.chunks[chunk_i][offset])
+  }
+
+  pub fn iter(&self) -> Iter<'_, T> {
+    self.range(0 .. self.len)
+  }
+  /// Panics if the range is out of bounds, same as slicing a Vec
+  pub fn range(&self, range: std::ops::Range<usize>) -> Iter<'_, T> {
+    assert!(
+      range.start <= range.end && range.end <= self.len,
+      "range {:?} out of bounds for ChunkedVec of length {}", range, self.len,
+    );
+    if range.start == range.end {
+      return Iter{ chunks: [].iter(), current: [].iter(), remaining: 0 };
+    }
+    let (chunk_i, offset) = self.locate(range.start);
+    Iter{
+      chunks: self.chunks[chunk_i + 1 ..].iter(),
+      current: self.chunks[chunk_i][offset ..].iter(),
+      remaining: range.end - range.start,
+    }
+  }
+
+  pub fn push(&mut self, value: T) {
+    match self.chunks.last_mut() {
+      Some(last) if last.len() < CHUNK_SIZE => {
+        // If the chunk is shared we need to copy it before modifying it
+        if Shared::get_mut(last).is_none() {
+          let copy = last.iter().map(Snapshot::create_snapshot).collect();
+          *last = Shared::new(copy);
+        }
+        // Cannot fail, as we made sure the Shared is unique above
+        Shared::get_mut(last).unwrap().push(value);
+      },
+      _ => {
+        self.starts.push(self.len);
+        self.chunks.push(Shared::new(vec![value]));
+      },
+    }
+    self.len += 1;
+  }
+
+  pub fn clear(&mut self) {
+    self.chunks.clear();
+    self.starts.clear();
+    self.len = 0;
+  }
+
+  /// Panics if at > len, same as Vec::split_off
+  pub fn split_off(&mut self, at: usize) -> Self {
+    assert!(
+      at <= self.len,
+      "split_off index {} out of bounds for ChunkedVec of length {}", at, self.len,
+    );
+    if at == self.len { return Self::new(); }
+    let (chunk_i, offset) = self.locate(at);
+    let tail_chunks = if offset == 0 {
+      self.chunks.split_off(chunk_i)
+    } else {
+      let mut tail_chunks = self.chunks.split_off(chunk_i + 1);
+      // Safe to unwrap, since locate returned a valid chunk index
+      let mut head = take_chunk(self.chunks.pop().unwrap());
+      let tail = head.split_off(offset);
+      self.chunks.push(Shared::new(head));
+      tail_chunks.insert(0, Shared::new(tail));
+      tail_chunks
+    };
+    // The chunks kept start where they did, so only the moved ones need new
+    // starts
+    self.starts.truncate(self.chunks.len());
+    self.len = at;
+    let mut tail = Self{ chunks: tail_chunks, starts: Vec::new(), len: 0 };
+    tail.recalculate();
+    tail
+  }
+
+  /// Moves all entries from other into self, leaving other empty
+  pub fn append(&mut self, other: &mut Self) {
+    let other_chunks = std::mem::take(&mut other.chunks);
+    let other_len = other.len;
+    other.clear();
+    let mut other_chunks = other_chunks.into_iter().peekable();
+    // Start of the next chunk added, as the chunks in self keep their starts
+    let mut start = self.len;
+    self.len += other_len;
+    // To prevent repeated split_off and append from fragmenting the chunks we
+    // merge the chunks on either side of the seam if they fit in one chunk.
+    let merge = match (self.chunks.last(), other_chunks.peek()) {
+      (Some(a), Some(b)) => a.len() + b.len() <= CHUNK_SIZE,
+      _ => false,
+    };
+    if merge {
+      // Safe to unwrap, both were verified to exist above
+      let mut head = take_chunk(self.chunks.pop().unwrap());
+      let mut first = take_chunk(other_chunks.next().unwrap());
+      start += first.len();
+      head.append(&mut first);
+      self.chunks.push(Shared::new(head));
+    }
+    for chunk in other_chunks {
+      self.starts.push(start);
+      start += chunk.len();
+      self.chunks.push(chunk);
+    }
+  }
+
+  /// Access to the chunks themselves, for serializing with shared chunks
+  #[cfg(feature = "serde")]
+  pub fn chunks(&self) -> &[Shared<Vec<T>>] {
+    &self.chunks
+  }
+  /// Construct from chunks, for deserializing with shared chunks
+  ///
+  /// Empty chunks are dropped and oversized chunks are split up, to uphold
+  /// the invariants even if given chunks from another version of add-ed.
+  #[cfg(feature = "serde")]
+  pub fn from_chunks(chunks: Vec<Shared<Vec<T>>>) -> Self {
+    let mut tmp = Self::new();
+    for chunk in chunks {
+      if chunk.is_empty() { continue; }
+      if chunk.len() <= CHUNK_SIZE {
+        tmp.chunks.push(chunk);
+      } else {
+        let mut data = take_chunk(chunk);
+        while !data.is_empty() {
+          let rest = data.split_off(data.len().min(CHUNK_SIZE));
+          tmp.chunks.push(Shared::new(data));
+          data = rest;
+        }
+      }
+    }
+    tmp.recalculate();
+    tmp
+  }
+
+  /// Iterate over the chunks in self that aren't shared with base
+  pub fn unshared_chunks<'a>(&'a self, base: &Self)
+    -> impl Iterator<Item = &'a [T]>
+  {
+    let shared: std::collections::HashSet<*const Vec<T>> = base.chunks.iter()
+      .map(Shared::as_ptr)
+      .collect()
+    ;
+    self.chunks.iter()
+      .filter(move |c| !shared.contains(&Shared::as_ptr(c)))
+      .map(|c| &c[..])
+  }
+
+  /// Returns true if both share all their chunks
+  ///
+  /// Cheap way to find that two instances are identical without comparing
+  /// the entries themselves. False doesn't mean they differ.
+  pub fn shares_chunks(&self, other: &Self) -> bool {
+    self.chunks.len() == other.chunks.len() &&
+      self.chunks.iter().zip(other.chunks.iter())
+        .all(|(a, b)| Shared::ptr_eq(a, b))
+  }
+}
+
+impl<T: Snapshot> Snapshot for ChunkedVec<T> {
+  fn create_snapshot(&self) -> Self {
+    Self{
+      chunks: self.chunks.clone(),
+      starts: self.starts.clone(),
+      len: self.len,
+    }
+  }
+}
+
+/// Iterator over references to the entries in a [`ChunkedVec`]
+pub(crate) struct Iter<'a, T> {
+  chunks: std::slice::Iter<'a, Shared<Vec<T>>>,
+  current: std::slice::Iter<'a, T>,
+  remaining: usize,
+}
+impl<'a, T> Iterator for Iter<'a, T> {
+  type Item = &'a T;
+  fn next(&mut self) -> Option<Self::Item> {
+    if self.remaining == 0 { return None; }
+    loop {
+      if let Some(x) = self.current.next() {
+        self.remaining -= 1;
+        return Some(x);
+      }
+      self.current = self.chunks.next()?.iter();
+    }
+  }
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    (self.remaining, Some(self.remaining))
+  }
+}
+impl<T> ExactSizeIterator for Iter<'_, T> {}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  // Minimal Snapshot implementor, to test the container in isolation
+  #[derive(Debug, PartialEq)]
+  struct Entry(usize);
+  impl Snapshot for Entry {
+    fn create_snapshot(&self) -> Self { Entry(self.0) }
+  }
+
+  fn filled(len: usize) -> ChunkedVec<Entry> {
+    let mut v = ChunkedVec::new();
+    for i in 0 .. len { v.push(Entry(i)); }
+    v
+  }
+  fn contents(v: &ChunkedVec<Entry>) -> Vec<usize> {
+    v.iter().map(|e| e.0).collect()
+  }
+  // Panics if the incrementally updated starts and len are wrong
+  fn assert_starts(v: &ChunkedVec<Entry>) {
+    let mut expected = ChunkedVec{
+      chunks: v.chunks.clone(),
+      starts: Vec::new(),
+      len: 0,
+    };
+    expected.recalculate();
+    assert_eq!(v.starts, expected.starts, "Chunk starts weren't updated.");
+    assert_eq!(v.len, expected.len, "Length wasn't updated.");
+  }
+
+  #[test]
+  fn indexing_across_chunks() {
+    let v = filled(CHUNK_SIZE * 3 + 7);
+    assert_eq!(v.len(), CHUNK_SIZE * 3 + 7);
+    for i in 0 .. v.len() {
+      assert_eq!(v.get(i), Some(&Entry(i)), "Wrong entry at index {}.", i);
+    }
+    assert_eq!(v.get(v.len()), None);
+    assert_eq!(
+      v.range(CHUNK_SIZE - 2 .. CHUNK_SIZE + 2).map(|e| e.0).collect::<Vec<_>>(),
+      vec![CHUNK_SIZE - 2, CHUNK_SIZE - 1, CHUNK_SIZE, CHUNK_SIZE + 1],
+      "Range iteration over a chunk border returned the wrong entries."
+    );
+  }
+
+  #[test]
+  fn split_and_append_roundtrip() {
+    let mut v = filled(CHUNK_SIZE * 2 + 3);
+    for at in [0, 1, CHUNK_SIZE, CHUNK_SIZE + 1, v.len()] {
+      let mut tail = v.split_off(at);
+      assert_eq!(v.len(), at);
+      assert_starts(&v);
+      assert_starts(&tail);
+      v.append(&mut tail);
+      assert_starts(&v);
+      assert!(tail.is_empty(), "Append didn't empty the appended instance.");
+      assert_eq!(contents(&v), (0 .. CHUNK_SIZE * 2 + 3).collect::<Vec<_>>());
+    }
+  }
+
+  #[test]
+  fn snapshot_is_unaffected_by_edits() {
+    let mut v = filled(CHUNK_SIZE + 10);
+    let snapshot = v.create_snapshot();
+    assert!(v.shares_chunks(&snapshot));
+    let _ = v.split_off(5);
+    v.push(Entry(1000));
+    assert_eq!(contents(&v), vec![0, 1, 2, 3, 4, 1000]);
+    assert_eq!(contents(&snapshot), (0 .. CHUNK_SIZE + 10).collect::<Vec<_>>());
+    assert!(!v.shares_chunks(&snapshot));
+  }
+
+  #[test]
+  fn unshared_chunks_after_edit() {
+    let mut v = filled(CHUNK_SIZE * 3);
+    let snapshot = v.create_snapshot();
+    assert_eq!(v.unshared_chunks(&snapshot).count(), 0);
+    // Delete one entry in the middle chunk, which should copy only that chunk
+    let mut tail = v.split_off(CHUNK_SIZE + 2);
+    let _deleted = v.split_off(CHUNK_SIZE + 1);
+    v.append(&mut tail);
+    let unshared: Vec<&[Entry]> = v.unshared_chunks(&snapshot).collect();
+    assert_eq!(unshared.len(), 1);
+    assert_eq!(unshared[0].len(), CHUNK_SIZE - 1);
+  }
+
+  #[test]
+  fn repeated_edits_dont_fragment() {
+    let mut v = filled(CHUNK_SIZE * 4);
+    // Simulate many single entry deletions, same as the `d` command does them
+    for i in 0 .. CHUNK_SIZE * 2 {
+      let at = (i * 7) % v.len();
+      let mut tail = v.split_off(at + 1);
+      let _deleted = v.split_off(at);
+      v.append(&mut tail);
+      assert_starts(&v);
+    }
+    assert_eq!(v.len(), CHUNK_SIZE * 2);
+    assert!(
+      v.chunks.len() <= 2 * v.len() / CHUNK_SIZE + 2,
+      "Storage fragmented into {} chunks.", v.chunks.len()
+    );
+  }
+}
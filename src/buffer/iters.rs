@@ -2,16 +2,40 @@
 //! through the API.
 
 use crate::Line;
+use super::chunked;
 
 // Type shorthand for the current implementation specific iterator, to reduce
 // how many times I have to write out this monstrosity of a type
 type Inner<'b> = std::iter::Map<
-  std::slice::Iter<'b, Line>, for<'a> fn(&'a Line) -> &'a str
+  BufferIter<'b>, for<'a> fn(&'a Line) -> &'a str
 >;
 type TaggedInner<'b> = std::iter::Map<
-  std::slice::Iter<'b, Line>, for<'a> fn(&'a Line) -> (char, &'a str)
+  BufferIter<'b>, for<'a> fn(&'a Line) -> (char, &'a str)
 >;
 
+/// The iterator returned by [`Buffer::iter`](crate::Buffer::iter) and
+/// [`Buffer::range`](crate::Buffer::range)
+pub struct BufferIter<'a> {
+  inner: chunked::Iter<'a, Line>,
+}
+impl<'a> Iterator for BufferIter<'a> {
+  type Item = &'a Line;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    self.inner.next()
+  }
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    self.inner.size_hint()
+  }
+}
+impl ExactSizeIterator for BufferIter<'_> {}
+
+impl<'a> From<chunked::Iter<'a, Line>> for BufferIter<'a> {
+  fn from(i: chunked::Iter<'a, Line>) -> Self {
+    Self{ inner: i }
+  }
+}
+
 /// The iterator returned by [`Ed::get_selection`]
 ///
 /// To simplify [`IO`] testing it implements
@@ -1,7 +1,6 @@
 //! The text storage structures
 
-use std::cell::{Cell, RefCell};
-use std::rc::Rc;
+use crate::sync::{Shared, CharCell, Lock};
 
 use super::*;
 
@@ -24,7 +23,7 @@ impl std::error::Error for LineTextError {}
 /// when cloning (as this will be done very often within `add-ed`'s logic).
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct LineText {
-  inner: Rc<String>,
+  inner: Shared<String>,
 }
 impl LineText {
   /// Create new LineText instance
@@ -39,16 +38,42 @@ impl LineText {
     if !text.ends_with('\n') || text[..text.len()-1].contains('\n') {
       Err(LineTextError{text})
     } else {
-      Ok(Self{ inner: Rc::new(text) })
+      Ok(Self{ inner: Shared::new(text) })
     }
   }
 }
+impl LineText {
+  // Identifies the shared text data, so it can be stored once when serializing
+  // and counted once when estimating memory use
+  pub(crate) fn id(&self) -> *const String {
+    Shared::as_ptr(&self.inner)
+  }
+}
 impl std::ops::Deref for LineText {
   type Target = String;
   fn deref(&self) -> &Self::Target {
     &(*self.inner)
   }
 }
+/// Serializes as the text, including the terminating newline
+#[cfg(feature = "serde")]
+impl serde::Serialize for LineText {
+  fn serialize<S: serde::Serializer>(&self, serializer: S)
+    -> Result<S::Ok, S::Error>
+  {
+    serializer.serialize_str(&self.inner)
+  }
+}
+/// Validates the text like [`LineText::new`]
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for LineText {
+  fn deserialize<D: serde::Deserializer<'de>>(deserializer: D)
+    -> Result<Self, D::Error>
+  {
+    Self::new(String::deserialize(deserializer)?)
+      .map_err(serde::de::Error::custom)
+  }
+}
 impl TryFrom<&str> for LineText {
   type Error = LineTextError;
   fn try_from(t: &str) -> Result<Self, Self::Error> {
@@ -91,13 +116,13 @@ pub struct Line {
   // Also note that this will be empty on newly created lines, but get_matching
   // handles this by defaulting to false and mark_matching explicitly resizes to
   // the size it needs.
-  pub(crate) matched: Rc<RefCell<Vec<bool>>>,
+  pub(crate) matched: Shared<Lock<Vec<bool>>>,
   // The tag set on the given line
   //
-  // Rc<Cell> makes it so we can have the same tag throughout all snapshots of
-  // the same line, but also requires us to hide the variable (so library users
-  // can't clone the Rc and cause strange behaviour.
-  tag: Rc<Cell<char>>,
+  // Sharing the cell makes it so we can have the same tag throughout all
+  // snapshots of the same line, but also requires us to hide the variable (so
+  // library users can't clone the Shared and cause strange behaviour).
+  tag: Shared<CharCell>,
   /// The text data for a given line
   ///
   /// [`LineText`] ensures that the text data is valid for a single line and
@@ -117,8 +142,8 @@ impl Line {
     text: T,
   ) -> Result<Self, LineTextError> {
     Ok(Self{
-      matched: Rc::new(RefCell::new(Vec::new())),
-      tag: Rc::new(Cell::new('\0')),
+      matched: Shared::new(Lock::new(Vec::new())),
+      tag: Shared::new(CharCell::new('\0')),
       text: LineText::new(text)?,
     })
   }
@@ -132,6 +157,12 @@ impl Line {
   pub fn set_tag(&self, new: char) {
     self.tag.set(new)
   }
+  // Identifies the line throughout history, since all historical instances of
+  // a line share the same tag
+  #[cfg(feature = "serde")]
+  pub(crate) fn id(&self) -> *const CharCell {
+    Shared::as_ptr(&self.tag)
+  }
 }
 // Our internal-only Clone implementation, to enable snapshotting without
 // misleading library users that they can Clone Lines.
@@ -148,8 +179,8 @@ impl From<&PubLine> for Line {
   fn from(l: &PubLine) -> Self {
     Self{
       text: l.text.clone(),
-      tag: Rc::new(Cell::new(l.tag)),
-      matched: Rc::new(RefCell::new(Vec::new())),
+      tag: Shared::new(CharCell::new(l.tag)),
+      matched: Shared::new(Lock::new(Vec::new())),
     }
   }
 }
@@ -169,6 +200,8 @@ impl TryFrom<&str> for Line {
 /// [`Line`]. Some TryFrom implementations that may be useful also exist.
 ///
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature="serde", serde(deny_unknown_fields))]
 pub struct PubLine {
   /// The tag set on the line
   ///
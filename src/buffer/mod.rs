@@ -7,6 +7,10 @@ use crate::history::Snapshot;
 pub mod iters;
 use iters::*;
 
+// The storage behind Buffer is internal, we only expose it through Buffer
+mod chunked;
+use chunked::ChunkedVec;
+
 // The other modules we keep private and export their contents directly
 
 mod line;
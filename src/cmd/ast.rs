@@ -0,0 +1,605 @@
+// Public representation of commands, parsed without executing anything
+//
+// Only the parsing which doesn't need the editor state happens here, so
+// interpreting selections, compiling regexes and running macros or shell
+// commands is left to the command implementations when the command is run.
+
+use super::*;
+use std::fmt;
+
+/// A parsed command, see [`parse_command`]
+///
+/// Holds what can be known of a command without the editor state, selections
+/// are for example only interpreted into line numbers when the command is run.
+///
+/// Displaying a command gives its canonical text, which parses back into the
+/// same command. [`Ed::execute`] labels the undo snapshot with that text.
+/// Hand built commands should hold what parsing would give, so no newlines,
+/// no `/` in [`Ind::Pattern`], etc.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+  /// No command, selects and prints the given selection. Without selection it
+  /// scrolls down by the length of the current selection.
+  Blank{ selection: Option<Sel> },
+  /// `q`, quits. `Q` if forced, which doesn't check for unsaved changes.
+  Quit{ force: bool },
+  /// `h`, prints the last error
+  PrintError,
+  /// `help`, prints the list of commands
+  Help,
+  /// `H`, toggles printing errors after they occur
+  ToggleErrorPrinting,
+  /// `Help`, prints the command documentation
+  Documentation,
+  /// `=`, prints the selection
+  PrintSelection{ selection: Option<Sel> },
+  /// `#`, selects and ignores the rest of the line
+  Comment{ selection: Option<Sel>, text: String },
+  /// `P`, toggles printing with line numbers resp. literal by default
+  TogglePrintDefaults{ n: bool, l: bool },
+  /// `b`, lists open buffers
+  ListBuffers,
+  /// `b <name>`, switches to the named buffer, opening it if needed
+  SwitchBuffer{ name: String },
+  /// `bq`, closes the named buffer (default the active one). `bQ` if forced,
+  /// which doesn't check for unsaved changes.
+  CloseBuffer{ name: Option<String>, force: bool },
+  /// `o` (or `set`), sets the named option if a value is given, otherwise
+  /// prints it. Lists all options if no name is given.
+  Options{ name: Option<String>, value: Option<String> },
+  /// `f`, sets the default file if a path is given, otherwise prints it
+  Filename{ path: Option<String> },
+  /// `!`, runs the shell command
+  Shell{ command: String },
+  /// `|`, pipes the selection through the shell command, replacing it
+  Pipe{ selection: Option<Sel>, command: String },
+  /// `e`, replaces the buffer with the file (default the default file). `E`
+  /// if forced, which doesn't check for unsaved changes.
+  ///
+  /// A path starting with `!` is a shell command to read the output of.
+  Edit{ path: Option<String>, force: bool },
+  /// `r`, appends the file (default the default file) after the selection
+  ///
+  /// A path starting with `!` is a shell command to read the output of.
+  Read{ selection: Option<Sel>, path: Option<String> },
+  /// `w`, writes the selection (default the whole buffer) to the file (default
+  /// the default file). `W` if appending to the file, `wq` if quitting after
+  /// (in which case the path isn't used).
+  ///
+  /// A path starting with `!` is a shell command to write to.
+  Write{
+    selection: Option<Sel>,
+    path: Option<String>,
+    append: bool,
+    quit: bool,
+  },
+  /// `p`, `n` or `l`, prints the selection
+  ///
+  /// The flags set decide the command, `p` is used if none is set.
+  Print{ selection: Option<Sel>, flags: PrintingFlags },
+  /// `z`, scrolls down the given number of lines (default 3). `Z` if
+  /// scrolling up.
+  Scroll{
+    selection: Option<Sel>,
+    backwards: bool,
+    lines: Option<usize>,
+    flags: PrintingFlags,
+  },
+  /// `i`, inserts input before the selection. `a` if appending after the
+  /// selection, `I` and `A` if joining the first resp. last input line with
+  /// the selected line.
+  Input{
+    selection: Option<Sel>,
+    append: bool,
+    inline: bool,
+    flags: PrintingFlags,
+  },
+  /// `c`, replaces the selection with input. `C` if the input should start
+  /// out as the selection (needs the `initial_input_data` feature).
+  Change{ selection: Option<Sel>, initial_data: bool, flags: PrintingFlags },
+  /// `d`, cuts the selection into the clipboard
+  Cut{ selection: Option<Sel>, flags: PrintingFlags },
+  /// `y`, copies the selection into the clipboard
+  Copy{ selection: Option<Sel>, flags: PrintingFlags },
+  /// `x`, pastes the clipboard after the selection. `X` if pasting before.
+  Paste{ selection: Option<Sel>, before: bool, flags: PrintingFlags },
+  /// `u`, undoes or redoes as described by the arguments
+  ///
+  /// The arguments are parsed when the command is run, as they may refer to
+  /// undo history.
+  Undo{ selection: Option<Sel>, arguments: String },
+  /// `U`, prints or manages undo history as described by the arguments
+  ///
+  /// The arguments are parsed when the command is run, as they may refer to
+  /// undo history.
+  UndoHistory{ selection: Option<Sel>, arguments: String },
+  /// `k`, tags the first line in selection with the tag (or clears its tag).
+  /// `K` if tagging the last line.
+  Tag{ selection: Option<Sel>, last: bool, tag: Option<char> },
+  /// `m`, moves the selection to after the index. `t` if copying instead.
+  ///
+  /// If a buffer is named the lines are transferred into it, and the index is
+  /// interpreted there.
+  Transfer{
+    selection: Option<Sel>,
+    copy: bool,
+    buffer: Option<String>,
+    index: Option<Ind>,
+    flags: PrintingFlags,
+  },
+  /// `j`, joins the selected lines into one
+  Join{ selection: Option<Sel>, flags: PrintingFlags },
+  /// `s`, substitutes matches in the selection. Without arguments the last
+  /// substitution is repeated.
+  Substitute{ selection: Option<Sel>, arguments: Option<Substitution> },
+  /// `g`, runs the commands on every line matching the pattern. `v` if
+  /// running on the lines not matching.
+  ///
+  /// If the last command isn't empty more commands are taken as input, until
+  /// a line with only the separator.
+  Global{
+    selection: Option<Sel>,
+    inverse: bool,
+    separator: char,
+    pattern: String,
+    commands: Vec<String>,
+  },
+  /// `G`, takes commands as input for every line matching the pattern. `V` if
+  /// doing so for the lines not matching.
+  ///
+  /// Input for each line ends at a line with only the separator.
+  InteractiveGlobal{
+    selection: Option<Sel>,
+    inverse: bool,
+    separator: char,
+    pattern: String,
+  },
+  /// `:`, runs the named macro with the arguments
+  Macro{ selection: Option<Sel>, name: String, arguments: Vec<String> },
+  /// `@`, runs the named plugin with the arguments
+  Plugin{ selection: Option<Sel>, name: String, arguments: String },
+  /// Any command add-ed doesn't define, which will be given to the
+  /// [`crate::plugins::CommandExtender`] (if any) when run
+  Other{ selection: Option<Sel>, command: char, tail: String },
+}
+
+// Parses the arguments to `o` (and `set`)
+fn parse_options(tail: &str) -> Result<Command> {
+  Ok(match tail.chars().next() {
+    None => Command::Options{ name: None, value: None },
+    Some(' ') => match tail[1..].split_once('=') {
+      Some((name, value)) => Command::Options{
+        name: Some(name.trim().to_owned()),
+        value: Some(value.trim().to_owned()),
+      },
+      None => Command::Options{
+        name: Some(tail[1..].trim().to_owned()),
+        value: None,
+      },
+    },
+    Some(_) => {
+      parse_flags(tail, "")?;
+      ed_unreachable!()?
+    },
+  })
+}
+
+/// Parse a command, without executing anything
+///
+/// A trailing newline is accepted. Returns the errors that can be found
+/// without the editor state, so selections that don't exist in the buffer,
+/// invalid regexes, unknown macros, etc. are only found when the command is
+/// run.
+pub fn parse_command(command: &str) -> Result<Command> {
+  let command = command.trim_end_matches('\n');
+  let (cmd_i, selection) = parse_selection(command)?;
+  // Trim end to get None instead of ' ' if there is no command
+  let ch = match command[cmd_i..].trim_end().chars().next() {
+    None => return Ok(Command::Blank{ selection }),
+    Some(ch) => ch,
+  };
+  let tail = &command[cmd_i + ch.len_utf8() ..];
+  Ok(match ch {
+    'q' | 'Q' => {
+      if selection.is_some() { return Err(EdError::SelectionForbidden); }
+      parse_flags(tail, "")?;
+      Command::Quit{ force: ch == 'Q' }
+    },
+    'h' | 'H' => {
+      if selection.is_some() { return Err(EdError::SelectionForbidden); }
+      match (ch, tail) {
+        ('h', "elp") => Command::Help,
+        ('H', "elp") => Command::Documentation,
+        _ => {
+          parse_flags(tail, "")?;
+          if ch == 'h' { Command::PrintError } else { Command::ToggleErrorPrinting }
+        },
+      }
+    },
+    '=' => {
+      parse_flags(tail, "")?;
+      Command::PrintSelection{ selection }
+    },
+    '#' => Command::Comment{ selection, text: tail.to_owned() },
+    'P' => {
+      if selection.is_some() { return Err(EdError::SelectionForbidden); }
+      let flags = parse_print_flags(tail, "nl")?;
+      Command::TogglePrintDefaults{ n: flags.n, l: flags.l }
+    },
+    'b' => {
+      if selection.is_some() { return Err(EdError::SelectionForbidden); }
+      match tail.chars().next() {
+        None => Command::ListBuffers,
+        Some(' ') => Command::SwitchBuffer{ name: tail[1..].trim().to_owned() },
+        Some(c @ ('q' | 'Q')) => Command::CloseBuffer{
+          name: Some(tail[1..].trim()).filter(|x| !x.is_empty()).map(str::to_owned),
+          force: c == 'Q',
+        },
+        Some(_) => {
+          parse_flags(tail, "")?;
+          ed_unreachable!()?
+        },
+      }
+    },
+    'o' => {
+      if selection.is_some() { return Err(EdError::SelectionForbidden); }
+      parse_options(tail)?
+    },
+    // `set` is an alias for `o`. Only without selection, as `s` with `e` as
+    // separator is the more likely meaning after a selection.
+    's' if selection.is_none() &&
+      (tail == "et" || tail.starts_with("et "))
+    => parse_options(&tail[2..])?,
+    'f' => {
+      if selection.is_some() { return Err(EdError::SelectionForbidden); }
+      if let Some(Path::Command(_)) = parse_path(tail) {
+        return Err(EdError::CommandEscapeForbidden(tail.to_owned()));
+      }
+      Command::Filename{ path: owned_path(tail) }
+    },
+    '!' => {
+      if selection.is_some() { return Err(EdError::SelectionForbidden); }
+      Command::Shell{ command: tail.to_owned() }
+    },
+    '|' => Command::Pipe{ selection, command: tail.to_owned() },
+    'e' | 'E' => {
+      if selection.is_some() { return Err(EdError::SelectionForbidden); }
+      Command::Edit{ path: owned_path(tail), force: ch == 'E' }
+    },
+    'r' => Command::Read{ selection, path: owned_path(tail) },
+    'w' | 'W' => {
+      let quit = tail == "q";
+      let path = if quit { None } else { owned_path(tail) };
+      if ch == 'W' {
+        if let Some(Path::Command(_)) = parse_path(tail) {
+          return Err(EdError::CommandEscapeForbidden(tail.to_owned()));
+        }
+      }
+      Command::Write{ selection, path, append: ch == 'W', quit }
+    },
+    'p' | 'n' | 'l' => Command::Print{
+      selection,
+      flags: parse_print_flags(&command[cmd_i..], "pnl")?,
+    },
+    'z' | 'Z' => {
+      let nr_end = tail.find( | c: char | !c.is_numeric() ).unwrap_or(tail.len());
+      let lines = if nr_end == 0 { None } else {
+        let nr = tail[.. nr_end].parse::<usize>()
+          .map_err(|_|EdError::ScrollNotInt(tail[..nr_end].to_owned()))
+        ?;
+        if nr == 0 { return Err(EdError::NoOp); }
+        Some(nr)
+      };
+      Command::Scroll{
+        selection,
+        backwards: ch == 'Z',
+        lines,
+        flags: parse_print_flags(&tail[nr_end ..], "pnl")?,
+      }
+    },
+    'a' | 'i' | 'A' | 'I' => Command::Input{
+      selection,
+      append: ch == 'a' || ch == 'A',
+      inline: ch == 'A' || ch == 'I',
+      flags: parse_print_flags(tail, "pnl")?,
+    },
+    'c' | 'C' => {
+      #[cfg(not(feature = "initial_input_data"))]
+      if ch == 'C' { return Err(EdError::CommandUndefined(ch)); }
+      Command::Change{
+        selection,
+        initial_data: ch == 'C',
+        flags: parse_print_flags(tail, "pnl")?,
+      }
+    },
+    'd' => Command::Cut{ selection, flags: parse_print_flags(tail, "pnl")? },
+    'y' => Command::Copy{ selection, flags: parse_print_flags(tail, "pnl")? },
+    'x' | 'X' => Command::Paste{
+      selection,
+      before: ch == 'X',
+      flags: parse_print_flags(tail, "pnl")?,
+    },
+    'u' => Command::Undo{ selection, arguments: tail.to_owned() },
+    'U' => Command::UndoHistory{ selection, arguments: tail.to_owned() },
+    'k' | 'K' => {
+      if tail.chars().count() > 1 {
+        return Err(EdError::TagInvalid(tail.to_owned()));
+      }
+      Command::Tag{ selection, last: ch == 'K', tag: tail.chars().next() }
+    },
+    'm' | 't' => {
+      let (buffer, tail) = match tail.strip_prefix('@') {
+        None => (None, tail),
+        Some(target) => {
+          let (name, tail) = target.split_once(':')
+            .ok_or_else(|| EdError::BufferNameInvalid(target.to_owned()))
+          ?;
+          (Some(name.to_owned()), tail)
+        },
+      };
+      let (ind_end, index) = parse_index(tail)?;
+      Command::Transfer{
+        selection,
+        copy: ch == 't',
+        buffer,
+        index,
+        flags: parse_print_flags(&tail[ind_end..], "pnl")?,
+      }
+    },
+    'j' => Command::Join{ selection, flags: parse_print_flags(tail, "pnl")? },
+    's' => {
+      let arguments = if tail.is_empty() { None } else {
+        let expressions = parse_expressions(tail)?;
+        if expressions.len() != 3 {
+          return Err(EdError::ArgumentsWrongNr{
+            expected: "none or 3".into(),
+            received: expressions.len(),
+          });
+        }
+        let mut flags = parse_flags(&expressions[2], "gpnl")?;
+        Some(Substitution{
+          pattern: expressions[0].to_owned(),
+          substitute: expressions[1].to_owned(),
+          global: flags.remove(&'g').unwrap(),
+          p: flags.remove(&'p').unwrap(),
+          n: flags.remove(&'n').unwrap(),
+          l: flags.remove(&'l').unwrap(),
+        })
+      };
+      Command::Substitute{ selection, arguments }
+    },
+    'g' | 'v' => {
+      let mut expressions = parse_expressions(tail)?;
+      if expressions.len() < 2 {
+        return Err(EdError::ArgumentsWrongNr{
+          expected: "2 or more".into(),
+          received: expressions.len(),
+        });
+      }
+      let commands = expressions.split_off(1);
+      Command::Global{
+        selection,
+        inverse: ch == 'v',
+        // expressions.len() would be 0 if no separator, so safe to unwrap
+        separator: tail.chars().next().unwrap(),
+        pattern: expressions.remove(0),
+        commands,
+      }
+    },
+    'G' | 'V' => {
+      let mut expressions = parse_expressions(tail)?;
+      if expressions.len() != 2 {
+        return Err(EdError::ArgumentsWrongNr{
+          expected: "2".into(),
+          received: expressions.len(),
+        });
+      }
+      if let Some(c) = expressions[1].chars().next() {
+        return Err(EdError::FlagUndefined(c));
+      }
+      Command::InteractiveGlobal{
+        selection,
+        inverse: ch == 'V',
+        separator: tail.chars().next().unwrap(),
+        pattern: expressions.remove(0),
+      }
+    },
+    ':' => {
+      let mut args = tail.split(' ');
+      let name = args.next().unwrap_or("").to_owned();
+      Command::Macro{
+        selection,
+        name,
+        arguments: args.map(str::to_owned).collect(),
+      }
+    },
+    '@' => {
+      let (name, arguments) = tail.split_once(' ').unwrap_or((tail, ""));
+      Command::Plugin{
+        selection,
+        name: name.to_owned(),
+        arguments: arguments.to_owned(),
+      }
+    },
+    _ => Command::Other{ selection, command: ch, tail: tail.to_owned() },
+  })
+}
+
+// Parse flags into printing flags, only allowing the given ones
+fn parse_print_flags(input: &str, flag_list: &str) -> Result<PrintingFlags> {
+  let flags = parse_flags(input, flag_list)?;
+  let get = |c| flags.get(&c).copied().unwrap_or(false);
+  Ok(PrintingFlags{ p: get('p'), n: get('n'), l: get('l') })
+}
+
+// Trimmed path argument, None if none given
+fn owned_path(input: &str) -> Option<String> {
+  match input.trim_start() {
+    "" => None,
+    x => Some(x.to_owned()),
+  }
+}
+
+impl fmt::Display for PrintingFlags {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    if self.p { write!(f, "p")?; }
+    if self.n { write!(f, "n")?; }
+    if self.l { write!(f, "l")?; }
+    Ok(())
+  }
+}
+
+// Writes each expression after the separator, escaping the separator within
+// them so parse_expressions gives them back
+fn write_expressions(
+  f: &mut fmt::Formatter,
+  separator: char,
+  expressions: &[&str],
+) -> fmt::Result {
+  let escaped = format!("\\{}", separator);
+  for expression in expressions {
+    write!(f, "{}{}", separator, expression.replace(separator, &escaped))?;
+  }
+  Ok(())
+}
+
+// Writes a space and the path, if any
+fn write_path(f: &mut fmt::Formatter, path: &Option<String>) -> fmt::Result {
+  match path {
+    Some(p) => write!(f, " {}", p),
+    None => Ok(()),
+  }
+}
+
+impl fmt::Display for Command {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    use Command::*;
+    // Write out the selection first, for the commands that take one
+    match self {
+      Blank{selection} | PrintSelection{selection} | Comment{selection, ..} |
+      Pipe{selection, ..} | Read{selection, ..} | Write{selection, ..} |
+      Print{selection, ..} | Scroll{selection, ..} | Input{selection, ..} |
+      Change{selection, ..} | Cut{selection, ..} | Copy{selection, ..} |
+      Paste{selection, ..} | Undo{selection, ..} | UndoHistory{selection, ..} |
+      Tag{selection, ..} | Transfer{selection, ..} | Join{selection, ..} |
+      Substitute{selection, ..} | Global{selection, ..} |
+      InteractiveGlobal{selection, ..} | Macro{selection, ..} |
+      Plugin{selection, ..} | Other{selection, ..}
+      => if let Some(s) = selection { write!(f, "{}", s)?; },
+      Quit{..} | PrintError | Help | ToggleErrorPrinting | Documentation |
+      TogglePrintDefaults{..} | ListBuffers | SwitchBuffer{..} |
+      CloseBuffer{..} | Options{..} | Filename{..} | Shell{..} | Edit{..}
+      => (),
+    }
+    match self {
+      Blank{..} => Ok(()),
+      Quit{force} => write!(f, "{}", if *force { 'Q' } else { 'q' }),
+      PrintError => write!(f, "h"),
+      Help => write!(f, "help"),
+      ToggleErrorPrinting => write!(f, "H"),
+      Documentation => write!(f, "Help"),
+      PrintSelection{..} => write!(f, "="),
+      Comment{text, ..} => write!(f, "#{}", text),
+      TogglePrintDefaults{n, l} => write!(f, "P{}",
+        PrintingFlags{ p: false, n: *n, l: *l },
+      ),
+      ListBuffers => write!(f, "b"),
+      SwitchBuffer{name} => write!(f, "b {}", name),
+      CloseBuffer{name, force} => {
+        write!(f, "b{}", if *force { 'Q' } else { 'q' })?;
+        write_path(f, name)
+      },
+      Options{name, value} => {
+        write!(f, "o")?;
+        if let Some(name) = name { write!(f, " {}", name)?; }
+        if let Some(value) = value { write!(f, "={}", value)?; }
+        Ok(())
+      },
+      Filename{path} => { write!(f, "f")?; write_path(f, path) },
+      Shell{command} => write!(f, "!{}", command),
+      Pipe{command, ..} => write!(f, "|{}", command),
+      Edit{path, force} => {
+        write!(f, "{}", if *force { 'E' } else { 'e' })?;
+        write_path(f, path)
+      },
+      Read{path, ..} => { write!(f, "r")?; write_path(f, path) },
+      Write{path, append, quit, ..} => {
+        write!(f, "{}", if *append { 'W' } else { 'w' })?;
+        if *quit { write!(f, "q") } else { write_path(f, path) }
+      },
+      Print{flags, ..} => {
+        if flags.p || flags.n || flags.l { write!(f, "{}", flags) }
+        else { write!(f, "p") }
+      },
+      Scroll{backwards, lines, flags, ..} => {
+        write!(f, "{}", if *backwards { 'Z' } else { 'z' })?;
+        if let Some(nr) = lines { write!(f, "{}", nr)?; }
+        write!(f, "{}", flags)
+      },
+      Input{append, inline, flags, ..} => write!(f, "{}{}",
+        match (append, inline) {
+          (false, false) => 'i',
+          (true, false) => 'a',
+          (false, true) => 'I',
+          (true, true) => 'A',
+        },
+        flags,
+      ),
+      Change{initial_data, flags, ..} => write!(f, "{}{}",
+        if *initial_data { 'C' } else { 'c' },
+        flags,
+      ),
+      Cut{flags, ..} => write!(f, "d{}", flags),
+      Copy{flags, ..} => write!(f, "y{}", flags),
+      Paste{before, flags, ..} => write!(f, "{}{}",
+        if *before { 'X' } else { 'x' },
+        flags,
+      ),
+      Undo{arguments, ..} => write!(f, "u{}", arguments),
+      UndoHistory{arguments, ..} => write!(f, "U{}", arguments),
+      Tag{last, tag, ..} => {
+        write!(f, "{}", if *last { 'K' } else { 'k' })?;
+        if let Some(t) = tag { write!(f, "{}", t)?; }
+        Ok(())
+      },
+      Transfer{copy, buffer, index, flags, ..} => {
+        write!(f, "{}", if *copy { 't' } else { 'm' })?;
+        if let Some(b) = buffer { write!(f, "@{}:", b)?; }
+        if let Some(i) = index { write!(f, "{}", i)?; }
+        write!(f, "{}", flags)
+      },
+      Join{flags, ..} => write!(f, "j{}", flags),
+      Substitute{arguments, ..} => {
+        write!(f, "s")?;
+        if let Some(s) = arguments {
+          write_expressions(f, '/', &[&s.pattern, &s.substitute])?;
+          write!(f, "/{}{}", if s.global { "g" } else { "" },
+            PrintingFlags{ p: s.p, n: s.n, l: s.l },
+          )?;
+        }
+        Ok(())
+      },
+      Global{inverse, separator, pattern, commands, ..} => {
+        write!(f, "{}", if *inverse { 'v' } else { 'g' })?;
+        let mut expressions = vec![&pattern[..]];
+        expressions.extend(commands.iter().map(|c| &c[..]));
+        write_expressions(f, *separator, &expressions)
+      },
+      InteractiveGlobal{inverse, separator, pattern, ..} => {
+        write!(f, "{}", if *inverse { 'V' } else { 'G' })?;
+        write_expressions(f, *separator, &[pattern, ""])
+      },
+      Macro{name, arguments, ..} => {
+        write!(f, ":{}", name)?;
+        for arg in arguments { write!(f, " {}", arg)?; }
+        Ok(())
+      },
+      Plugin{name, arguments, ..} => {
+        write!(f, "@{}", name)?;
+        if !arguments.is_empty() { write!(f, " {}", arguments)?; }
+        Ok(())
+      },
+      Other{command, tail, ..} => write!(f, "{}{}", command, tail),
+    }
+  }
+}
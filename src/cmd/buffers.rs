@@ -0,0 +1,48 @@
+use super::*;
+
+// List open buffers, marking the active one
+pub fn list_buffers(
+  state: &mut Ed<'_>,
+  ui: &mut dyn UI,
+) -> Result<()> {
+  let mut tmp = String::new();
+  let active = std::iter::once((&state.buffer_name, &state.file, &state.history));
+  let others = state.buffers.iter().map(|(n, b)| (n, &b.file, &b.history));
+  let mut all: Vec<_> = active.chain(others).collect();
+  all.sort_by(|a, b| a.0.cmp(b.0));
+  for (name, file, history) in all {
+    if !tmp.is_empty() { tmp.push('\n'); }
+    tmp.push_str(&format!(
+      "{} {}\t{}{}",
+      if name == &state.buffer_name { '>' } else { ' ' },
+      name,
+      if file.is_empty() { "-" } else { file },
+      if history.saved() { "" } else { " (unsaved)" },
+    ));
+  }
+  ui.print_message(&tmp)
+}
+
+// Switch to the named buffer, opening a new one if none has the name
+pub fn switch_buffer(
+  state: &mut Ed<'_>,
+  ui: &mut dyn UI,
+  name: &str,
+) -> Result<()> {
+  state.open_buffer(name)?;
+  ui.buffer_switched(state)
+}
+
+// Close the named buffer, or the active one if none named
+pub fn close_buffer(
+  state: &mut Ed<'_>,
+  ui: &mut dyn UI,
+  name: Option<&str>,
+  force: bool,
+) -> Result<()> {
+  let name = name.unwrap_or(&state.buffer_name).to_owned();
+  let was_active = name == state.buffer_name;
+  state.close_buffer(&name, force)?;
+  if was_active { ui.buffer_switched(state)?; }
+  Ok(())
+}
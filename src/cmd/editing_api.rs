@@ -0,0 +1,185 @@
+// Typed editing methods on Ed
+//
+// These share the inner functions of the matching commands, so the buffer,
+// selection and clipboard end up as after running the command. Snapshots are
+// labeled with the text of the matching command.
+
+use super::*;
+use crate::PubLine;
+
+/// The outcome of an edit made through the typed editing API on [`Ed`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EditSummary {
+  /// The selection after the edit, which is also set as `Ed.selection`
+  pub selection: (usize, usize),
+  /// Nr of lines removed or replaced, which are put in the clipboard
+  pub removed: usize,
+  /// Nr of lines added
+  pub added: usize,
+}
+
+// The selection as it would be written in a command
+fn literal(selection: (usize, usize)) -> Option<Sel> {
+  Some(Sel::Pair(Ind::Literal(selection.0), Ind::Literal(selection.1)))
+}
+
+impl <'a> Ed <'a> {
+  /// Insert lines after the given index (0 to insert at the start)
+  ///
+  /// Like `a`, selects the inserted lines (the selection is unchanged if no
+  /// lines are given).
+  pub fn insert_lines(
+    &mut self,
+    index: usize,
+    lines: &[PubLine],
+  ) -> Result<EditSummary> {
+    self.history.current().verify_index(index)?;
+    let label = Command::Input{
+      selection: Some(Sel::Lone(Ind::Literal(index))),
+      append: true,
+      inline: false,
+      flags: PrintingFlags::default(),
+    }.to_string();
+    if !lines.is_empty() {
+      inner_input(self, &label, lines.iter().map(|l| l.into()).collect(), index)?;
+      self.selection = (index + 1, index + lines.len());
+    }
+    Ok(EditSummary{ selection: self.selection, removed: 0, added: lines.len() })
+  }
+
+  /// Delete the selected lines into the clipboard
+  ///
+  /// Like `d`, selects the line after the deleted lines if any, else the line
+  /// before.
+  pub fn delete(
+    &mut self,
+    selection: (usize, usize),
+  ) -> Result<EditSummary> {
+    self.history.current().verify_selection(selection)?;
+    let label = Command::Cut{
+      selection: literal(selection),
+      flags: PrintingFlags::default(),
+    }.to_string();
+    inner_cut(self, &label, selection)?;
+    Ok(EditSummary{
+      selection: self.selection,
+      removed: selection.1 - selection.0 + 1,
+      added: 0,
+    })
+  }
+
+  /// Replace the selected lines with the given lines
+  ///
+  /// Like `c`, puts the replaced lines in the clipboard and selects the new
+  /// lines (or as after [`Ed::delete`] if no lines are given).
+  pub fn replace(
+    &mut self,
+    selection: (usize, usize),
+    lines: &[PubLine],
+  ) -> Result<EditSummary> {
+    self.history.current().verify_selection(selection)?;
+    let label = Command::Change{
+      selection: literal(selection),
+      initial_data: false,
+      flags: PrintingFlags::default(),
+    }.to_string();
+    inner_change(self, &label, lines.iter().map(|l| l.into()).collect(), selection)?;
+    Ok(EditSummary{
+      selection: self.selection,
+      removed: selection.1 - selection.0 + 1,
+      added: lines.len(),
+    })
+  }
+
+  /// Replace matches of the regex pattern in the selected lines
+  ///
+  /// Like `s`, the selected lines are joined before matching (so patterns may
+  /// match across lines) and the substitute may use `\n` and capture groups.
+  /// The replaced lines are put in the clipboard and the resulting lines are
+  /// selected. Replaces only the first match unless `global`.
+  ///
+  /// Returns [`EdError::RegexNoMatch`] without modifying anything if nothing
+  /// matched. On success the arguments are kept for a following lone `s`.
+  pub fn substitute(
+    &mut self,
+    selection: (usize, usize),
+    pattern: &str,
+    substitute: &str,
+    global: bool,
+  ) -> Result<EditSummary> {
+    let s = Substitution{
+      pattern: pattern.to_owned(),
+      substitute: substitute.to_owned(),
+      global,
+      p: false,
+      n: false,
+      l: false,
+    };
+    let label = Command::Substitute{
+      selection: literal(selection),
+      arguments: Some(s.clone()),
+    }.to_string();
+    let end = inner_substitute(
+      &mut self.history,
+      &mut self.clipboard,
+      &label,
+      selection,
+      pattern,
+      substitute,
+      global,
+    )?;
+    // Same as after 's', see the command for details
+    self.selection = (selection.0.min(end).max(1), end);
+    self.prev_s = Some(s);
+    Ok(EditSummary{
+      selection: self.selection,
+      removed: selection.1 - selection.0 + 1,
+      added: end + 1 - selection.0,
+    })
+  }
+
+  /// Move the selected lines to after the given index (0 to move to the start)
+  ///
+  /// Like `m`, selects the moved lines. Errors if the index is within the
+  /// selection.
+  pub fn move_lines(
+    &mut self,
+    selection: (usize, usize),
+    index: usize,
+  ) -> Result<EditSummary> {
+    self.transfer_lines(selection, index, TransferType::Move)
+  }
+
+  /// Copy the selected lines to after the given index (0 to copy to the start)
+  ///
+  /// Like `t`, selects the copies.
+  pub fn copy_lines(
+    &mut self,
+    selection: (usize, usize),
+    index: usize,
+  ) -> Result<EditSummary> {
+    self.transfer_lines(selection, index, TransferType::Copy)
+  }
+
+  fn transfer_lines(
+    &mut self,
+    selection: (usize, usize),
+    index: usize,
+    mode: TransferType,
+  ) -> Result<EditSummary> {
+    let copy = matches!(mode, TransferType::Copy);
+    let label = Command::Transfer{
+      selection: literal(selection),
+      copy,
+      buffer: None,
+      index: Some(Ind::Literal(index)),
+      flags: PrintingFlags::default(),
+    }.to_string();
+    self.selection = inner_transfer(self, &label, selection, index, mode)?;
+    Ok(EditSummary{
+      selection: self.selection,
+      removed: 0,
+      added: if copy { selection.1 - selection.0 + 1 } else { 0 },
+    })
+  }
+}
@@ -1,20 +1,40 @@
 use super::*;
 
-fn inner_change(
+// Replaces the verified selection with the input and selects it
+pub(crate) fn inner_change(
   state: &mut Ed<'_>,
   full_command: &str,
-  mut input: Vec<String>,
-  selection: (usize, usize),
+  input: Vec<Line>,
+  sel: (usize, usize),
 ) -> Result<()> {
+  let inputlen = input.len();
   let buffer = state.history.current_mut(full_command.into());
-  let mut tail = buffer.split_off(selection.1);
-  state.clipboard = buffer.split_off(selection.0 - 1)[..].into();
-  // Note that drain gives full Strings and Line::new will use them as-is,
-  // without re-allocating them (but risking leaving them over allocated).
-  for line in input.drain(..) {
-    buffer.push(Line::new(line).map_err(InternalError::InvalidLineText)?);
-  }
+  let mut tail = buffer.split_off(sel.1);
+  state.clipboard = (&buffer.split_off(sel.0 - 1)).into();
+  buffer.extend(input);
   buffer.append(&mut tail);
+  state.selection = {
+    // For change behaviour select:
+    // - sel.0 to sel.0 + inputlen
+
+    // For deletion behaviour try to select:
+    // - nearest following line
+    // - if no following line, take the last line in buffer
+    // - If buffer empty, fallback to (1,0)
+
+    // The minimum for start is 1, max will switch over to 1 if it would be less
+    (1.max(
+      // Try to select sel.0
+      // (if delete is line after selection, if change is first line of changed)
+      // But limit to buffer.len via a min in case we deleted the whole buffer
+      sel.0.min(buffer.len())
+    ),
+      // Try to select sel.0 + inputlen.saturating_sub(1)
+      // (if delete is same as sel.0 above, if change is last line of changed
+      // But limit to buffer.len via a min in case we deleted the whole buffer
+      (sel.0 + inputlen.saturating_sub(1)).min(buffer.len())
+    )
+  };
   Ok(())
 }
 pub fn change(
@@ -22,29 +42,26 @@ pub fn change(
   ui: &mut dyn UI,
   pflags: &mut PrintingFlags,
   full_command: &str,
-  selection: Option<Sel<'_>>,
-  command: char,
-  flags: &str,
+  selection: Option<Sel>,
+  initial_data: bool,
+  flags: PrintingFlags,
 ) -> Result<()> {
   let sel = interpret_selection(&state, selection, state.selection)?;
   let buffer = state.history.current();
   buffer.verify_selection(sel)?;
-  let mut flags = parse_flags(flags, "pnl")?;
-  pflags.p = flags.remove(&'p').unwrap();
-  pflags.n = flags.remove(&'n').unwrap();
-  pflags.l = flags.remove(&'l').unwrap();
+  *pflags = flags;
   #[allow(unused_variables)]
-  let initial_input_data: Option<Vec<String>> = if command == 'C' {
+  let initial_input_data: Option<Vec<String>> = if initial_data {
     #[cfg(feature = "initial_input_data")]
     {
-      Some(buffer[sel.0 - 1 .. sel.1].iter()
+      Some(buffer.range(sel.0 - 1 .. sel.1)
         .map(|s| (&s.text[..]).to_owned())
         .collect()
       )
     }
     #[cfg(not(feature = "initial_input_data"))]
     {
-      return Err(EdError::CommandUndefined(command));
+      return Err(EdError::CommandUndefined('C'));
     }
   } else {
     None
@@ -63,30 +80,12 @@ pub fn change(
       return Err(EdError::PrintAfterWipe);
     }
   }
-  inner_change(state, full_command, input, sel)?;
-  // Re-declare to allow the old one to drop for mutable call above and still use one after
-  let buffer = state.history.current();
-  state.selection = {
-    // For change behaviour select:
-    // - sel.0 to sel.0 + inputlen
-
-    // For deletion behaviour try to select:
-    // - nearest following line
-    // - if no following line, take the last line in buffer
-    // - If buffer empty, fallback to (1,0)
-
-    // The minimum for start is 1, max will switch over to 1 if it would be less
-    (1.max(
-      // Try to select sel.0
-      // (if delete is line after selection, if change is first line of changed)
-      // But limit to buffer.len via a min in case we deleted the whole buffer
-      sel.0.min(buffer.len())
-    ),
-      // Try to select sel.0 + inputlen.saturating_sub(1)
-      // (if delete is same as sel.0 above, if change is last line of changed
-      // But limit to buffer.len via a min in case we deleted the whole buffer
-      (sel.0 + inputlen.saturating_sub(1)).min(buffer.len())
-    )
-  };
-  Ok(())
+  // Note that into_iter gives full Strings and Line::new will use them as-is,
+  // without re-allocating them (but risking leaving them over allocated).
+  let input = input.into_iter()
+    .map(Line::new)
+    .collect::<std::result::Result<Vec<_>, _>>()
+    .map_err(InternalError::InvalidLineText)
+  ?;
+  inner_change(state, full_command, input, sel)
 }
@@ -5,21 +5,18 @@ fn inner_copy(
   selection: (usize, usize),
 ) -> Result<()> {
   let buffer = state.history.current();
-  state.clipboard = buffer[selection.0 - 1 .. selection.1].into();
+  state.clipboard = buffer.range(selection.0 - 1 .. selection.1).collect();
   Ok(())
 }
 pub fn copy(
   state: &mut Ed<'_>,
   pflags: &mut PrintingFlags,
-  selection: Option<Sel<'_>>,
-  tail: &str,
+  selection: Option<Sel>,
+  flags: PrintingFlags,
 ) -> Result<()> {
   let sel = interpret_selection(&state, selection, state.selection)?;
   state.history.current().verify_selection(sel)?;
-  let mut flags = parse_flags(tail, "pnl")?;
-  pflags.p = flags.remove(&'p').unwrap();
-  pflags.n = flags.remove(&'n').unwrap();
-  pflags.l = flags.remove(&'l').unwrap();
+  *pflags = flags;
   inner_copy(state, sel)?;
   state.selection = sel;
   Ok(())
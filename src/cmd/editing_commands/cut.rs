@@ -1,34 +1,16 @@
 use super::*;
 
-pub fn cut(
+// Cuts the verified selection into the clipboard and selects after it
+pub(crate) fn inner_cut(
   state: &mut Ed<'_>,
-  pflags: &mut PrintingFlags,
   full_command: &str,
-  selection: Option<Sel<'_>>,
-  tail: &str,
+  sel: (usize, usize),
 ) -> Result<()> {
-  let sel = interpret_selection(&state, selection, state.selection)?;
-  let buffer = state.history.current();
-  buffer.verify_selection(sel)?;
-  // Since selection after execution can be 0 it isn't allowed to auto print after
-  // Get the flags
-  let mut flags = parse_flags(tail, "pnl")?;
-  // Set the global print pflags (safe to unwrap since parse_flags never removes a key)
-  pflags.p = flags.remove(&'p').unwrap();
-  pflags.n = flags.remove(&'n').unwrap();
-  pflags.l = flags.remove(&'l').unwrap();
-  // If we are about to delete whole buffer
-  if sel.0 == 1 && sel.1 == buffer.len() {
-    // And we are to print after execution, error
-    if pflags.p || pflags.n || pflags.l {
-      return Err(EdError::PrintAfterWipe);
-    }
-  }
   let buffer = state.history.current_mut(full_command.into());
   let mut tail = buffer.split_off(sel.1);
   let data = buffer.split_off(sel.0 - 1);
   buffer.append(&mut tail);
-  state.clipboard = data[..].into();
+  state.clipboard = (&data).into();
   // Try to figure out a selection after the deletion
   state.selection = {
     // For deletion behaviour try to select:
@@ -49,3 +31,24 @@ pub fn cut(
   };
   Ok(())
 }
+pub fn cut(
+  state: &mut Ed<'_>,
+  pflags: &mut PrintingFlags,
+  full_command: &str,
+  selection: Option<Sel>,
+  flags: PrintingFlags,
+) -> Result<()> {
+  let sel = interpret_selection(&state, selection, state.selection)?;
+  let buffer = state.history.current();
+  buffer.verify_selection(sel)?;
+  // Since selection after execution can be 0 it isn't allowed to auto print after
+  *pflags = flags;
+  // If we are about to delete whole buffer
+  if sel.0 == 1 && sel.1 == buffer.len() {
+    // And we are to print after execution, error
+    if pflags.p || pflags.n || pflags.l {
+      return Err(EdError::PrintAfterWipe);
+    }
+  }
+  inner_cut(state, full_command, sel)
+}
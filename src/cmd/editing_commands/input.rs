@@ -1,16 +1,15 @@
 use super::*;
 
-fn inner_input(
+// Inserts the input after the verified index
+pub(crate) fn inner_input(
   state: &mut Ed<'_>,
   full_command: &str,
-  mut input: Vec<String>,
+  input: Vec<Line>,
   index: usize,
 ) -> Result<()> {
   let buffer = state.history.current_mut(full_command.into());
   let mut tail = buffer.split_off(index);
-  for line in input.drain(..) {
-    buffer.push(Line::new(line).map_err(InternalError::InvalidLineText)?);
-  }
+  buffer.extend(input);
   buffer.append(&mut tail);
   Ok(())
 }
@@ -65,42 +64,38 @@ fn inner_inline_input(
     },
   }
   buffer.append(&mut tail);
-  state.clipboard = (&*indexed_line).into();
+  state.clipboard = (&indexed_line).into();
   Ok(())
 }
+#[allow(clippy::too_many_arguments)]
 pub fn input(
   state: &mut Ed<'_>,
   ui: &mut dyn UI,
   pflags: &mut PrintingFlags,
   full_command: &str,
-  selection: Option<Sel<'_>>,
-  command: char,
-  flags: &str,
+  selection: Option<Sel>,
+  append: bool,
+  inline: bool,
+  flags: PrintingFlags,
 ) -> Result<()> {
-  let mut flags = parse_flags(flags, "pnl")?;
-  pflags.p = flags.remove(&'p').unwrap();
-  pflags.n = flags.remove(&'n').unwrap();
-  pflags.l = flags.remove(&'l').unwrap();
+  *pflags = flags;
 
   let buffer = state.history.current();
-  let index = match command {
-    'a' | 'A' => {
-      let i = interpret_index_from_selection(&state, selection, state.selection, true)?;
-      if command == 'a' { buffer.verify_index(i)? } else { buffer.verify_line(i)? }
-      i
-    },
-    // Note that saturating_sub really is needed, since inserting at index 0
-    // should be valid and equivalent to inserting at index 1.
-    'i' | 'I' => {
-      let mut i = interpret_index_from_selection(&state, selection, state.selection, false)?;
-      if command == 'i' {
-        i = i.saturating_sub(1);
-        buffer.verify_index(i)?;
-      }
-      else { buffer.verify_line(i)? }
-      i
-    },
-    _ => ed_unreachable!()?,
+  let index = if append {
+    let i = interpret_index_from_selection(&state, selection, state.selection, true)?;
+    if inline { buffer.verify_line(i)? } else { buffer.verify_index(i)? }
+    i
+  }
+  // Note that saturating_sub really is needed, since inserting at index 0
+  // should be valid and equivalent to inserting at index 1.
+  else {
+    let mut i = interpret_index_from_selection(&state, selection, state.selection, false)?;
+    if inline { buffer.verify_line(i)? }
+    else {
+      i = i.saturating_sub(1);
+      buffer.verify_index(i)?;
+    }
+    i
   };
   // Now that we have checked that the command is valid, get input
   // This is done so we don't drop text input, which would be annoying
@@ -118,21 +113,25 @@ pub fn input(
     let end = start + input.len() - 1; // Subtract for inclusive select
     // In the case of 'a', 'i' that is all
     // 'A' and 'I' need a join
-    match command {
-      'A' => {
+    match (append, inline) {
+      (true, true) => {
         inner_inline_input(state, full_command, input, index, InlineSide::After)?;
         // This offsets start and end of sel by -1
         (start - 1, end - 1)
       },
-      'I' => {
+      (false, true) => {
         inner_inline_input(state, full_command, input, index, InlineSide::Before)?;
         (start - 1,end - 1)
       },
-      'a' | 'i' => {
+      (_, false) => {
+        let input = input.into_iter()
+          .map(Line::new)
+          .collect::<std::result::Result<Vec<_>, _>>()
+          .map_err(InternalError::InvalidLineText)
+        ?;
         inner_input(state, full_command, input, index)?;
         (start, end)
       },
-      _ => ed_unreachable!()?,
     }
   }
   // If no input is given, keep old selection
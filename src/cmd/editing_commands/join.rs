@@ -21,22 +21,19 @@ fn inner_join(
   buffer.push(Line::new(text).map_err(InternalError::InvalidLineText)?);
   // Add back tail data and save old data into clipboard
   buffer.append(&mut tail);
-  state.clipboard = data[..].into();
+  state.clipboard = (&data).into();
   Ok(())
 }
 pub fn join(
   state: &mut Ed<'_>,
   pflags: &mut PrintingFlags,
   full_command: &str,
-  selection: Option<Sel<'_>>,
-  tail: &str,
+  selection: Option<Sel>,
+  flags: PrintingFlags,
 ) -> Result<()> {
   let selection = interpret_selection(&state, selection, state.selection)?;
   state.history.current().verify_selection(selection)?; // Verify without creating snapshot
-  let mut flags = parse_flags(tail, "pnl")?;
-  pflags.p = flags.remove(&'p').unwrap();
-  pflags.n = flags.remove(&'n').unwrap();
-  pflags.l = flags.remove(&'l').unwrap();
+  *pflags = flags;
   inner_join(state, full_command, selection)?;
   state.selection = (selection.0, selection.0);
   Ok(())
@@ -16,17 +16,14 @@ pub fn paste(
   state: &mut Ed<'_>,
   pflags: &mut PrintingFlags,
   full_command: &str,
-  selection: Option<Sel<'_>>,
-  command: char,
-  tail: &str,
+  selection: Option<Sel>,
+  before: bool,
+  flags: PrintingFlags,
 ) -> Result<()> {
-  let mut flags = parse_flags(tail, "pnl")?;
-  pflags.p = flags.remove(&'p').unwrap();
-  pflags.n = flags.remove(&'n').unwrap();
-  pflags.l = flags.remove(&'l').unwrap();
+  *pflags = flags;
   // Append or prepend based on command
-  let mut index = interpret_index_from_selection(&state, selection, state.selection, command == 'x')?;
-  if command == 'X' { index = index.saturating_sub(1); }
+  let mut index = interpret_index_from_selection(&state, selection, state.selection, !before)?;
+  if before { index = index.saturating_sub(1); }
   let length = inner_paste(state, full_command, index)?;
   if length != 0 {
     state.selection = (index + 1, index + length);
@@ -3,39 +3,29 @@ use super::*;
 pub fn scroll(
   state: &mut Ed<'_>,
   pflags: &mut PrintingFlags,
-  selection: Option<Sel<'_>>,
-  command: char,
-  tail: &str,
+  selection: Option<Sel>,
+  backwards: bool,
+  lines: Option<usize>,
+  flags: PrintingFlags,
   default_scroll_length: usize,
 ) -> Result<()> {
   // Depending on forward or backward we use start or end of selection as starting point
   let sel = interpret_selection(&state, selection, state.selection)?;
-  let index = if command == 'z' {
-    sel.1
-  } else {
+  let index = if backwards {
     sel.0
+  } else {
+    sel.1
   };
   let buffer = state.history.current(); // As we only need an immutable buffer we use the same one
   buffer.verify_index(index)?;
-  // Parse the arguments to see how many lines to scroll
-  let nr_end = tail.find( | c: char | !c.is_numeric() ).unwrap_or(tail.len());
-  let nr = if nr_end == 0 {
-    default_scroll_length
-  } else {
-    let nr = tail[.. nr_end].parse::<usize>()
-      .map_err(|_|EdError::ScrollNotInt(tail[..nr_end].to_owned()))
-    ?;
-    // Scrolling 0 lines is invalid, return error
-    if nr == 0 { return Err(EdError::NoOp); }
-    nr
-  };
-  // Check what isn't numeric for flags
-  let mut flags = parse_flags(&tail[nr_end ..], "pnl")?;
+  // Scrolling 0 lines is invalid, return error
+  if lines == Some(0) { return Err(EdError::NoOp); }
+  let nr = lines.unwrap_or(default_scroll_length);
   pflags.p = true; // This command should print, so p always true
-  pflags.n = flags.remove(&'n').unwrap();
-  pflags.l = flags.remove(&'l').unwrap();
+  pflags.n = flags.n;
+  pflags.l = flags.l;
   // The real purpose is to update the selection, do that
-  let new_sel = if command == 'z' {
+  let new_sel = if !backwards {
     // Gracefully handle overrunning bufferlen
     let start = buffer.len().min(index + 1);
     let end = buffer.len().min(index + nr);
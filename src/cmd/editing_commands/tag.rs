@@ -2,18 +2,14 @@ use super::*;
 
 pub fn tag(
   state: &mut Ed<'_>,
-  selection: Option<Sel<'_>>,
-  command: char,
-  tail: &str,
+  selection: Option<Sel>,
+  last: bool,
+  tag: Option<char>,
 ) -> Result<()> {
   let selection = interpret_selection(&state, selection, state.selection)?;
-  let index = if command == 'k' { selection.0 } else { selection.1 };
+  let index = if last { selection.1 } else { selection.0 };
   let buffer = state.history.current();
   buffer.verify_line(index)?;
-  // we only expect the tag, no flags
-  if tail.chars().count() > 1 {
-    return Err(EdError::TagInvalid(tail.to_owned()));
-  }
-  buffer[index - 1].set_tag(tail.chars().next().unwrap_or('\0').into());
+  buffer[index - 1].set_tag(tag.unwrap_or('\0').into());
   Ok(())
 }
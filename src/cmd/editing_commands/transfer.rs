@@ -3,11 +3,11 @@ use super::*;
 // Note that we must be careful to break the Rc connection between the tag and
 // matched fields on the lines. (Otherwise those lines will be considered the
 // same for tagging and 'g' matching)
-enum TransferType {
+pub(crate) enum TransferType {
   Copy,
   Move,
 }
-fn inner_transfer(
+pub(crate) fn inner_transfer(
   state: &mut Ed<'_>,
   full_command: &str,
   selection: (usize, usize),
@@ -30,7 +30,7 @@ fn inner_transfer(
     TransferType::Copy => {
       // We make sure to not duplicate the tag or matched Rc:s when copying by
       // using a temporary clipboard, which breaks those references.
-      let tmp: Clipboard = buffer[selection.0 - 1 .. selection.1].into();
+      let tmp: Clipboard = buffer.range(selection.0 - 1 .. selection.1).collect();
       let mut tail = buffer.split_off(index);
       let start_ind = buffer.len() + 1; // +1 excludes current last line
       buffer.append(&mut (&tmp).into());
@@ -72,43 +72,88 @@ fn inner_transfer(
     },
   }
 }
+// Copy or move the selection to after the index in another open buffer
+//
+// The index is interpreted in the other buffer, which gets its selection set
+// to the transferred lines. The active buffer stays active.
+fn transfer_to_buffer(
+  state: &mut Ed<'_>,
+  full_command: &str,
+  selection: (usize, usize),
+  name: &str,
+  ind: Option<Ind>,
+  flags: PrintingFlags,
+  mode: TransferType,
+) -> Result<()> {
+  state.history.current().verify_selection(selection)?;
+  // Printing would show the active buffer, not the transferred lines
+  if let Some(flag) = flags.to_string().chars().next() {
+    return Err(EdError::FlagUndefined(flag));
+  }
+  if !state.buffers.contains_key(name) {
+    return Err(EdError::BufferUnknown(name.to_owned()));
+  }
+  // Via a clipboard to not share tag and matched Rc:s between buffers
+  let data: Clipboard = state.history.current()
+    .range(selection.0 - 1 .. selection.1)
+    .collect()
+  ;
+  let origin = state.buffer_name.clone();
+  state.switch_buffer(name)?;
+  let res = interpret_index(
+    state,
+    ind.unwrap_or(Ind::BufferLen),
+    state.selection.1,
+  ).and_then(|index| {
+    state.history.current().verify_index(index)?;
+    let buffer = state.history.current_mut(full_command.into());
+    let mut tail = buffer.split_off(index);
+    buffer.append(&mut (&data).into());
+    buffer.append(&mut tail);
+    state.selection = (index + 1, index + data.len());
+    Ok(())
+  });
+  state.switch_buffer(&origin)?;
+  res?;
+  if let TransferType::Move = mode {
+    let buffer = state.history.current_mut(full_command.into());
+    let mut tail = buffer.split_off(selection.1);
+    let _ = buffer.split_off(selection.0 - 1);
+    buffer.append(&mut tail);
+    // Select like 'd', the nearest following line if any
+    let end = selection.0.min(buffer.len());
+    state.selection = (1.max(end), end);
+  }
+  Ok(())
+}
+#[allow(clippy::too_many_arguments)]
 pub fn transfer(
   state: &mut Ed<'_>,
   pflags: &mut PrintingFlags,
   full_command: &str,
-  selection: Option<Sel<'_>>,
-  command: char,
-  tail: &str,
+  selection: Option<Sel>,
+  copy: bool,
+  buffer: Option<&str>,
+  ind: Option<Ind>,
+  flags: PrintingFlags,
 ) -> Result<()> {
   let selection = interpret_selection(&state, selection, state.selection)?;
-  // Parse the target index, then the flags if any
-  let (ind_end, ind) = parse_index(tail)?;
-  let index = if command == 'm' || command == 't' {
-    interpret_index(
-      &state,
-      ind.unwrap_or_else(|| Ind::BufferLen),
-      state.selection.1,
-    )?
+  let mode = if copy { TransferType::Copy } else { TransferType::Move };
+  // Transfer to another buffer if its name is given as `@<name>:` before index
+  if let Some(name) = buffer {
+    if name != state.buffer_name {
+      return transfer_to_buffer(state, full_command, selection, name, ind,
+        flags, mode,
+      );
+    }
   }
-  // We still keep the handling for 'T' and 'M', we may re-enable it later
-  else {
-    interpret_index(
-      &state,
-      ind.unwrap_or_else(|| Ind::Literal(1)),
-      state.selection.0,
-    )?
-  };
-  let mut flags = parse_flags(&tail[ind_end..], "pnl")?;
-  pflags.p = flags.remove(&'p').unwrap();
-  pflags.n = flags.remove(&'n').unwrap();
-  pflags.l = flags.remove(&'l').unwrap();
+  let index = interpret_index(
+    &state,
+    ind.unwrap_or(Ind::BufferLen),
+    state.selection.1,
+  )?;
+  *pflags = flags;
   // Run the command, returning the selection after
-  state.selection = match command {
-    'm' => inner_transfer(state, full_command, selection, index, TransferType::Move)?,
-    'M' => inner_transfer(state, full_command, selection, index.saturating_sub(1), TransferType::Move)?,
-    't' => inner_transfer(state, full_command, selection, index, TransferType::Copy)?,
-    'T' => inner_transfer(state, full_command, selection, index.saturating_sub(1), TransferType::Copy)?,
-    _ => return ed_unreachable!(),
-  };
+  state.selection = inner_transfer(state, full_command, selection, index, mode)?;
   Ok(())
 }
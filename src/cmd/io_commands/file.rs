@@ -55,9 +55,10 @@ pub fn read_from_file(
   state: &mut Ed<'_>,
   ui: &mut dyn UI,
   full_command: &str,
-  selection: Option<Sel<'_>>,
+  selection: Option<Sel>,
   command: char,
   path: &str,
+  recursion_depth: usize,
 ) -> Result<()> {
   let index =
     if command == 'r' {
@@ -83,13 +84,20 @@ pub fn read_from_file(
           cmd,
           &state.file,
           &state.prev_shell_command,
+          state.shell_quote,
         )?;
         if changed {
           ui.print_message( &substituted )?;
         }
+        let context = shell_context(
+          state,
+          index.map(|i| (i, i)).unwrap_or(state.selection),
+          recursion_depth,
+        );
         let data = state.io.run_read_command(
           &mut ui.lock_ui(),
           substituted.clone(),
+          &context,
         )?;
         state.prev_shell_command = substituted;
         data
@@ -110,24 +118,37 @@ pub fn read_from_file(
       // Considering saved after command is odd, and commands cannot be saved
       // into state.file, only aftereffect is state.prev_shell_command
       Path::Command(_cmd) => {
-        ui.print_message(&format!(
+        if !state.quiet { ui.print_message(&format!(
           "Read {} bytes from command `{}`",
           unformated_data.len(),
           &state.prev_shell_command,
-        ))?;
+        ))?; }
       },
       Path::File(file) => {
-        ui.print_message(&format!(
+        if !state.quiet { ui.print_message(&format!(
           "Read {} bytes from path `{}`",
           unformated_data.len(),
           file,
-        ))?;
+        ))?; }
         // Should only occur if we cleared buffer or it was empty before read.
         // Rule of least surprise means 'r' shouldn't do this even then, since
         // it normally won't.
         if state.history.current().len() == datalen && command != 'r' {
           state.file = file.to_owned();
           state.history.set_saved();
+          // Failing to load history shouldn't fail reading the file
+          #[cfg(feature = "history_file")]
+          if state.persistent_history {
+            match state.load_history() {
+              Ok(true) => if !state.quiet {
+                ui.print_message("Loaded undo history for file")?
+              },
+              Ok(false) => (),
+              Err(e) => ui.print_message(&format!(
+                "Failed to load undo history: {}", e,
+              ))?,
+            }
+          }
         }
       },
     }
@@ -138,9 +159,11 @@ pub fn read_from_file(
 pub fn write_to_file(
   state: &mut Ed<'_>,
   ui: &mut dyn UI,
-  selection: Option<Sel<'_>>,
-  command: char,
-  in_path: &str,
+  selection: Option<Sel>,
+  in_path: Option<&str>,
+  append: bool,
+  quit: bool,
+  recursion_depth: usize,
 ) -> Result<bool> {
   // Since 'w' and 'W' should default to the whole buffer rather than previous selection
   // they get some custom code here
@@ -160,27 +183,21 @@ pub fn write_to_file(
   };
 
   // If not wq, parse path
-  let (q, path, overwrite) = if in_path != "q" {
-    match parse_path(in_path) {
-      Some(p) => (false, p, false),
-      None => (false, Path::File(&state.file), true),
-    }
-  }
-  // If wq, use current file path
-  else {
-    (true, Path::File(&state.file), true)
+  let in_path = in_path.unwrap_or("");
+  let (path, overwrite) = match parse_path(in_path).filter(|_| !quit) {
+    Some(p) => (p, false),
+    // If wq or no path, use current file path
+    None => (Path::File(&state.file), true),
   };
   // If the 'q' flag is set the whole buffer must be selected
-  if q && sel.is_some() { return Err(EdError::UnsavedChanges); }
+  if quit && sel.is_some() { return Err(EdError::UnsavedChanges); }
   // Read out data from buffer (Also verifies selection, to the extent needed)
-  let data = state.history.current().get_lines(
-    sel.unwrap_or((1, state.history.current().len()))
-  )?
-  ;
+  let written_sel = sel.unwrap_or((1, state.history.current().len()));
+  let data = state.history.current().get_lines(written_sel)?;
   // Write into command or file, print nr of bytes written
   match path {
     Path::File(file) => {
-      let wtype = if command == 'W' {
+      let wtype = if append {
         WriteType::Append
       } else {
         if overwrite {
@@ -194,42 +211,54 @@ pub fn write_to_file(
         wtype,
         data,
       )?;
-      ui.print_message(&format!(
+      if !state.quiet { ui.print_message(&format!(
         "Wrote {} bytes to path `{}`",
         written,
         file,
-      ))?;
+      ))?; }
       // Since path isn't allowed to be a command, do check in here
       // If path now contains only whole buffer, set saved and update state.file.
       // Rule of least surprise means 'W' shouldn't do so even then, since it
       // normally won't
-      if sel.is_none() && command != 'W' {
+      if sel.is_none() && !append {
         state.file = file.to_string();
         state.history.set_saved();
+        // Failing to store history shouldn't fail writing the file
+        #[cfg(feature = "history_file")]
+        if state.persistent_history {
+          if let Err(e) = state.save_history() {
+            ui.print_message(&format!(
+              "Failed to store undo history: {}", e,
+            ))?;
+          }
+        }
       }
     },
     Path::Command(cmd) => {
       // 'W' with a command is probably a misstake, error instead
-      if command == 'W' {
+      if append {
         return Err(EdError::CommandEscapeForbidden(in_path.to_owned()));
       }
       let (changed, substituted) = command_substitutions(
         cmd,
         &state.file,
         &state.prev_shell_command,
+        state.shell_quote,
       )?;
+      let context = shell_context(state, written_sel, recursion_depth);
       state.prev_shell_command = substituted.clone();
       if changed {ui.print_message( &substituted )?;}
       let written = state.io.run_write_command(
         &mut ui.lock_ui(),
         substituted,
+        &context,
         data,
       )?;
-      ui.print_message(&format!(
+      if !state.quiet { ui.print_message(&format!(
         "Wrote {} bytes to command `{}`",
         written,
         &state.prev_shell_command,
-      ))?;
+      ))?; }
     },
   }
   // If selection was given, save that selection
@@ -239,5 +268,5 @@ pub fn write_to_file(
       state.selection = (s.0, s.1);
     },
   }
-  Ok(q)
+  Ok(quit)
 }
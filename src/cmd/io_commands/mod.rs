@@ -1,5 +1,21 @@
 use super::*;
 
+// The context for a shell command operating on the given selection
+//
+// Must be created before prev_shell_command is replaced by the command.
+fn shell_context(
+  state: &Ed<'_>,
+  selection: (usize, usize),
+  recursion_depth: usize,
+) -> ShellContext {
+  ShellContext{
+    file: state.file.clone(),
+    selection,
+    prev_shell_command: state.prev_shell_command.clone(),
+    script: recursion_depth > 0,
+  }
+}
+
 mod file;
 pub use file::*;
 mod shell;
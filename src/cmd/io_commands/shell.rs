@@ -10,7 +10,7 @@ fn replace_selection(
   // Selection already verified by get_selection call before calling this fn
   let buffer = state.history.current_mut(full_command.into());
   let mut tail = buffer.split_off(selection.1);
-  state.clipboard = buffer.split_off(selection.0 - 1)[..].into();
+  state.clipboard = (&buffer.split_off(selection.0 - 1)).into();
   for line in input.drain(..) {
     buffer.push(Line::new(line).map_err(InternalError::InvalidLineText)?);
   }
@@ -21,9 +21,10 @@ pub fn run_command(
   state: &mut Ed<'_>,
   ui: &mut dyn UI,
   full_command: &str,
-  selection: Option<Sel<'_>>,
+  selection: Option<Sel>,
   ch: char,
   command: &str,
+  recursion_depth: usize,
 ) -> Result<()> {
   // '!' doesn't allow a selection
   let sel = if ch == '!' {
@@ -40,7 +41,9 @@ pub fn run_command(
     command,
     &state.file,
     &state.prev_shell_command,
+    state.shell_quote,
   )?;
+  let context = shell_context(state, sel.unwrap_or(state.selection), recursion_depth);
   state.prev_shell_command = substituted.clone();
   if changed {ui.print_message( &substituted )?;}
   // Depending on selection or not we use run_transform_command or run_command
@@ -50,9 +53,10 @@ pub fn run_command(
       let res = state.io.run_command(
         &mut ui.lock_ui(),
         substituted,
+        &context,
       );
       // Signify end of command output before reacting to potential error
-      ui.print_message(&ch.to_string())?;
+      if !state.quiet { ui.print_message(&ch.to_string())?; }
       res?;
     },
     // When there is a selection we pipe that selection through the command and
@@ -62,6 +66,7 @@ pub fn run_command(
       let mut transformed = state.io.run_transform_command(
         &mut ui.lock_ui(),
         substituted,
+        &context,
         data,
       )?;
       if !transformed.ends_with('\n') { transformed.push('\n'); }
@@ -75,11 +80,11 @@ pub fn run_command(
       else {
         (1.max(s.0 - 1), s.0 - 1)
       };
-      ui.print_message(&format!(
+      if !state.quiet { ui.print_message(&format!(
         "Transformation returned {} bytes through command `{}`",
         transformed.len(),
         &state.prev_shell_command,
-      ))?;
+      ))?; }
     },
   }
   Ok(())
@@ -1,4 +1,5 @@
 use crate::{Ed, Substitution, Line, Clipboard, Buffer, WriteType};
+use crate::io::ShellContext;
 use crate::ui::{UI, ScriptedUI};
 use crate::error::*;
 use crate::messages::*;
@@ -7,36 +8,95 @@ use crate::messages::*;
 // Parsing helpers
 mod parsing;
 use parsing::*;
+// Exported for CommandExtender
+pub use parsing::{Sel, Ind};
+pub(crate) use parsing::interpret_selection;
+
+// Public command representation, parsed without execution
+mod ast;
+pub use ast::{Command, parse_command};
+// Typed editing methods on Ed, sharing the commands' inner functions
+mod editing_api;
+pub use editing_api::EditSummary;
 
 // Command logic in separate loosely grouped files, to manage file size
 //
-// The commands are usually split into a pub(super) parsing and state managing
-// wrapper around an inner function that performs the text editing operation.
+// The commands are usually split into a pub(super) state managing wrapper,
+// taking the parsed arguments, around an inner function that performs the text
+// editing operation.
 mod io_commands;
 use io_commands::*;
 mod editing_commands;
 use editing_commands::*;
 mod regex_commands;
 use regex_commands::*;
+// For Ed::step to verify 'g' before taking input, and drive 'G' and 'V' line
+// by line
+pub(crate) use regex_commands::{
+  get_marked,
+  global_interactive_mark,
+  global_interactive_line,
+};
 
 mod undo;
 use undo::*;
+mod buffers;
+use buffers::*;
+mod options;
+use options::*;
 
 // Helps to hand in globally relevant flags as one &mut struct to the command
 // implementations
 // (pub because rusts pub fn is a bit clunky and complains otherwise)
-#[derive(Default)]
+/// Flags to print the selection after a command, see
+/// [`crate::plugins::CommandExtender`] and [`Command`]
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
 pub struct PrintingFlags {
   pub p: bool,
   pub n: bool,
   pub l: bool,
 }
 
-// The horrifying piece that is command parsing and execution.
+// Runs f with snapshotting disabled, so all its modifications are squashed into
+// one snapshot labelled with the command. Used by 'g', macros and plugins.
+//
+// Snapshotting is re-enabled in the buffer it was disabled in, even if f
+// switches to another buffer.
+fn squash_snapshots<T>(
+  state: &mut Ed<'_>,
+  command: &str,
+  f: impl FnOnce(&mut Ed<'_>) -> Result<T>,
+) -> Result<T> {
+  // Before disabling snapshotting, create one for this command
+  state.history.snapshot(command.to_owned());
+  let buffer_name = state.buffer_name.clone();
+  let orig_dont_snapshot = state.history.dont_snapshot;
+  state.history.dont_snapshot = true;
+  let res = f(state);
+  if let Some(history) = state.buffer_history_mut(&buffer_name) {
+    history.dont_snapshot = orig_dont_snapshot;
+    // If snapshotting was originally enabled we should handle if no
+    // mutation of the buffer occured during the dont_snapshot.
+    if !orig_dont_snapshot { history.dedup_present(); }
+  }
+  res
+}
+
+// Parses and executes a command
+pub(crate) fn run(
+  state: &mut Ed<'_>,
+  ui: &mut dyn UI,
+  command: &str,
+  recursion_depth: usize,
+) -> Result<bool> {
+  let parsed = parse_command(command)?;
+  // The full command without the newline, to give as label to `history.current_mut()`
+  execute(state, ui, parsed, command.trim_end_matches('\n'), recursion_depth)
+}
+
+// The horrifying piece that is command execution.
 //
-// I tried to break it up, but since all commands require different subsequent
-// parsing it is a lost cause.
-// If someone manages to do it, a PR is more than welcome.
+// Parsing is done by parse_command, so this only handles what needs the state.
 //
 // Important things to remember if modifying this or underlying functions are:
 // * If taking input, verify everything you have first. Nothing is more
@@ -44,10 +104,11 @@ pub struct PrintingFlags {
 //   index doesn't exist...
 // * Forbid input you don't handle. This should prevent accidentally force
 //   exiting with ',Q file.txt' because you pressed 'Q' instead of 'W'.
-pub(crate) fn run(
+pub(crate) fn execute(
   state: &mut Ed<'_>,
   ui: &mut dyn UI,
-  command: &str,
+  command: Command,
+  label: &str,
   recursion_depth: usize,
 ) -> Result<bool> {
   // Check current recursion depth against state.recursion limit, to prevent
@@ -59,15 +120,9 @@ pub(crate) fn run(
   // Declare flags for printing after the command has been executed.
   let mut pflags = PrintingFlags::default();
 
-  // Parse out the command index and the selection
-  let (cmd_i, selection) = parse_selection(command)?;
-
-  // Use the cmd_i to get a clean selection  
-  // Match the command and act upon it
-   // (Trim end to get None instead of '\n' or ' ' if there is no command)
-   let ret = match command[cmd_i..].trim_end().chars().next() {
+  let ret = match command {
     // No command is valid. It updates selection and prints
-    None => {
+    Command::Blank{selection} => {
       if selection.is_some() {
         // Get and update the selection.
         let sel = interpret_selection(&state, selection, state.selection)?;
@@ -77,224 +132,231 @@ pub(crate) fn run(
       } else {
         // Since state.selection may be invalid
         state.history.current().verify_selection(state.selection)?;
-        scroll(state, &mut pflags, selection, 'z', "",
+        scroll(state, &mut pflags, selection, false, None,
+          PrintingFlags::default(),
           state.selection.1 - state.selection.0 + 1,
         )?;
       }
-      Ok(false)
+      false
     },
-    Some(ch) => {
-      let tail = {
-        let mut x = 1;
-        while ! command.is_char_boundary(cmd_i + x) { x += 1; }
-        &command[cmd_i + x ..]
-      };
-      // Don't trim spaces, to allow using them as separator in expressions
-      let clean = tail.trim_end_matches('\n');
-      // The full command without the newline, to give as label to `history.current_mut()`
-      let clean_command = command.trim_end_matches('\n');
-      match ch {
-        // Quit commands
-        'q' | 'Q' => {
-          if selection.is_some() { return Err(EdError::SelectionForbidden); }
-          parse_flags(clean, "")?;
-          if state.history.saved() || ch == 'Q' {
-            Ok(true)
-          }
-          else {
-            Err(EdError::UnsavedChanges)
-          }
-        }
-        // Help commands
-        'h' => {
-          if selection.is_some() { return Err(EdError::SelectionForbidden); }
-          // If 'help' was entered, print help
-          if clean == "elp" { ui.print_commands()?; }
-          // Else no flags accepted and print last error
-          else {
-            parse_flags(clean, "")?;
-            match &state.error {
-              Some(e) => {
-                let msg = e.to_string();
-                ui.print_message(&msg)?;
-              },
-              None => ui.print_message(NO_ERROR)?,
-            }
-          }
-          Ok(false)
-        },
-        'H' => {
-          if selection.is_some() { return Err(EdError::SelectionForbidden); }
-          if clean == "elp" { ui.print_command_documentation()?; }
-          else {
-            parse_flags(clean, "")?;
-            state.print_errors = !state.print_errors; // Toggle the setting
-          }
-          Ok(false)
-        }
-        // Non-editing commands
-        '=' | '#' => {
-          let sel = interpret_selection(&state, selection, state.selection)?;
-          state.history.current().verify_selection(sel)?;
-          if ch== '=' { parse_flags(clean, "")?; }
-          state.selection = sel;
-          if ch == '=' { ui.print_message(&format!("({},{})", sel.0, sel.1) )?; }
-          Ok(false)
-        },
-        // Toggles printing with/without numbering/literal by default
-        'P' => {
-          if selection.is_some() { return Err(EdError::SelectionForbidden); }
-          let mut flags = parse_flags(clean, "nl")?;
-          // Toggle default state of the flags defined
-          if flags.remove(&'l').unwrap() {
-            state.l = !state.l;
-          }
-          if flags.remove(&'n').unwrap() {
-            state.n = !state.n;
-          }
-          Ok(false)
-        },
-        // File/shell commands
-        'f' => { // Set or print filename
-          if selection.is_some() { return Err(EdError::SelectionForbidden); }
-          // Print or update filename
-          filename(state, ui, clean)?;
-          Ok(false)
-        },
-        '!' | '|' => {
-          run_command(state, ui, clean_command, selection, ch, clean)?;
-          Ok(false)
-        },
-        'e' | 'E' | 'r' => {
-          read_from_file(state, ui, clean_command, selection, ch, clean)?;
-          Ok(false)
-        },
-        'w' | 'W' => {
-          write_to_file(state, ui, selection, ch, clean)
-        },
-        // Print commands
-        'p' | 'n' | 'l' => {
-          let sel = interpret_selection(&state, selection, state.selection)?;
-          state.history.current().verify_selection(sel)?;
-          // Get the flags
-          let mut flags = parse_flags(&command[cmd_i..], "pnl")?;
-          // Set the global print pflags (safe to unwrap since parse_flags never removes a key)
-          pflags.p = flags.remove(&'p').unwrap();
-          pflags.n = flags.remove(&'n').unwrap();
-          pflags.l = flags.remove(&'l').unwrap();
-          state.selection = sel;
-          Ok(false)
-        },
-        'z' | 'Z' => {
-          scroll(state, &mut pflags, selection, ch, clean, 3)?;
-          Ok(false)
-        },
-        // Basic editing commands
-        'a' | 'i' | 'A' | 'I' => {
-          input(state, ui, &mut pflags, clean_command, selection, ch, clean)?;
-          Ok(false)
-        },
-        'c' | 'C' => {
-          change(state, ui, &mut pflags, clean_command, selection, ch, clean)?;
-          Ok(false)
-        },
-        'd' => { // Cut
-          cut(state, &mut pflags, clean_command, selection, clean)?;
-          Ok(false)
-        },
-        'y' => { // Copy to clipboard
-          copy(state, &mut pflags, selection, clean)?;
-          Ok(false)
-        },
-        'x' | 'X' => { // Append/prepend (respectively) clipboard contents to selection
-          paste(state, &mut pflags, clean_command, selection, ch, clean)?;
-          Ok(false)
-        },
-        'U' => {
-          manage_history(state, ui, selection, clean)?;
-          Ok(false)
-        },
-        'u' => { // Undo/redo (undoing a negative number of steps redoes)
-           undo(state, ui, selection, clean)?;
-           Ok(false)
-        },
-        // Advanced editing commands
-        'k' | 'K' => { // Tag first (k) or last (K) line in selection
-          tag(state, selection, ch, clean)?;
-          Ok(false)
-        },
-        'm' | 't' => {
-          transfer(state, &mut pflags, clean_command, selection, ch, clean)?;
-          Ok(false)
-        }
-        'j' => {
-          join(state, &mut pflags, clean_command, selection, clean)?;
-          Ok(false)
-        },
-        // Pattern commands
-        's' => {
-          substitute(state, &mut pflags, clean_command, selection, tail)?;
-          Ok(false)
-        },
-        'g' | 'v' | 'G' | 'V' => {
-          // Before disabling snapshotting, create one for this command
-          state.history.snapshot(clean_command.to_string());
-          // Disable snapshotting during execution, reset it after
-          let orig_dont_snapshot = state.history.dont_snapshot;
-          state.history.dont_snapshot = true;
-          let res = if ch == 'g' || ch == 'v' {
-            global(state, ui, selection, ch, clean, recursion_depth)
-          } else {
-            global_interactive(state, ui, selection, ch, clean, recursion_depth)
-          };
-          state.history.dont_snapshot = orig_dont_snapshot;
-          // If snapshotting was originally enabled we should handle if no
-          // mutation of the buffer occured during the dont_snapshot.
-          if !orig_dont_snapshot { state.history.dedup_present(); }
-          res?;
-          Ok(false)
+    // Quit commands
+    Command::Quit{force} => {
+      // Unsaved changes in any open buffer prevent quitting
+      let saved = state.history.saved() &&
+        state.buffers.values().all(|b| b.history.saved())
+      ;
+      if !saved && !force { return Err(EdError::UnsavedChanges); }
+      true
+    },
+    // Help commands
+    Command::Help => { ui.print_commands()?; false },
+    Command::PrintError => {
+      match &state.error {
+        Some(e) => {
+          let msg = e.to_string();
+          ui.print_message(&msg)?;
         },
-        ':' => {
-          let given_selection = if selection.is_some() {
-            let s = interpret_selection(&state, selection, state.selection)?;
-            state.history.current().verify_selection(s)?;
-            Some(s)
+        None => ui.print_message(NO_ERROR)?,
+      }
+      false
+    },
+    Command::Documentation => { ui.print_command_documentation()?; false },
+    Command::ToggleErrorPrinting => {
+      state.print_errors = !state.print_errors; // Toggle the setting
+      false
+    },
+    // Non-editing commands
+    Command::PrintSelection{selection} => {
+      let sel = interpret_selection(&state, selection, state.selection)?;
+      state.history.current().verify_selection(sel)?;
+      state.selection = sel;
+      ui.print_message(&format!("({},{})", sel.0, sel.1) )?;
+      false
+    },
+    Command::Comment{selection, ..} => {
+      let sel = interpret_selection(&state, selection, state.selection)?;
+      state.history.current().verify_selection(sel)?;
+      state.selection = sel;
+      false
+    },
+    // Toggles printing with/without numbering/literal by default, or
+    // showing the command prefix if no flags are given
+    Command::TogglePrintDefaults{n, l} => {
+      if !n && !l { state.show_cmd_prefix = !state.show_cmd_prefix; }
+      // Toggle default state of the flags defined
+      if l { state.l = !state.l; }
+      if n { state.n = !state.n; }
+      false
+    },
+    // File/shell commands
+    Command::ListBuffers => { list_buffers(state, ui)?; false },
+    Command::SwitchBuffer{name} => { switch_buffer(state, ui, &name)?; false },
+    Command::CloseBuffer{name, force} => {
+      close_buffer(state, ui, name.as_deref(), force)?;
+      false
+    },
+    Command::Options{name, value} => {
+      options(state, ui, name.as_deref(), value.as_deref())?;
+      false
+    },
+    Command::Filename{path} => { // Set or print filename
+      filename(state, ui, path.as_deref().unwrap_or(""))?;
+      false
+    },
+    Command::Shell{command} => {
+      run_command(state, ui, label, None, '!', &command, recursion_depth)?;
+      false
+    },
+    Command::Pipe{selection, command} => {
+      run_command(state, ui, label, selection, '|', &command, recursion_depth)?;
+      false
+    },
+    Command::Edit{path, force} => {
+      read_from_file(state, ui, label, None, if force { 'E' } else { 'e' },
+        path.as_deref().unwrap_or(""), recursion_depth,
+      )?;
+      false
+    },
+    Command::Read{selection, path} => {
+      read_from_file(state, ui, label, selection, 'r',
+        path.as_deref().unwrap_or(""), recursion_depth,
+      )?;
+      false
+    },
+    Command::Write{selection, path, append, quit} => {
+      write_to_file(state, ui, selection, path.as_deref(), append, quit,
+        recursion_depth,
+      )?
+    },
+    // Print commands
+    Command::Print{selection, flags} => {
+      let sel = interpret_selection(&state, selection, state.selection)?;
+      state.history.current().verify_selection(sel)?;
+      pflags = flags;
+      // Default to 'p' if no flag is set
+      if !(flags.p || flags.n || flags.l) { pflags.p = true; }
+      state.selection = sel;
+      false
+    },
+    Command::Scroll{selection, backwards, lines, flags} => {
+      scroll(state, &mut pflags, selection, backwards, lines, flags, 3)?;
+      false
+    },
+    // Basic editing commands
+    Command::Input{selection, append, inline, flags} => {
+      input(state, ui, &mut pflags, label, selection, append, inline, flags)?;
+      false
+    },
+    Command::Change{selection, initial_data, flags} => {
+      change(state, ui, &mut pflags, label, selection, initial_data, flags)?;
+      false
+    },
+    Command::Cut{selection, flags} => {
+      cut(state, &mut pflags, label, selection, flags)?;
+      false
+    },
+    Command::Copy{selection, flags} => { // Copy to clipboard
+      copy(state, &mut pflags, selection, flags)?;
+      false
+    },
+    Command::Paste{selection, before, flags} => {
+      paste(state, &mut pflags, label, selection, before, flags)?;
+      false
+    },
+    Command::UndoHistory{selection, arguments} => {
+      manage_history(state, ui, selection, &arguments)?;
+      false
+    },
+    // Undo/redo (undoing a negative number of steps redoes)
+    Command::Undo{selection, arguments} => {
+      undo(state, ui, selection, &arguments)?;
+      false
+    },
+    // Advanced editing commands
+    Command::Tag{selection, last, tag: t} => {
+      tag(state, selection, last, t)?;
+      false
+    },
+    Command::Transfer{selection, copy, buffer, index, flags} => {
+      transfer(state, &mut pflags, label, selection, copy, buffer.as_deref(),
+        index, flags,
+      )?;
+      false
+    },
+    Command::Join{selection, flags} => {
+      join(state, &mut pflags, label, selection, flags)?;
+      false
+    },
+    // Pattern commands
+    Command::Substitute{selection, arguments} => {
+      substitute(state, &mut pflags, label, selection, arguments)?;
+      false
+    },
+    Command::Global{selection, inverse, separator, pattern, commands} => {
+      squash_snapshots(state, label, |state| global(state, ui, selection,
+        inverse, separator, &pattern, &commands, recursion_depth,
+      ))?;
+      false
+    },
+    Command::InteractiveGlobal{selection, inverse, separator, pattern} => {
+      squash_snapshots(state, label, |state| global_interactive(state, ui,
+        selection, inverse, separator, &pattern, recursion_depth,
+      ))?;
+      false
+    },
+    Command::Macro{selection, name, arguments} => {
+      let given_selection = if selection.is_some() {
+        let s = interpret_selection(&state, selection, state.selection)?;
+        state.history.current().verify_selection(s)?;
+        Some(s)
+      }
+      else {
+        None
+      };
+      let args: Vec<&str> = arguments.iter().map(|a| &a[..]).collect();
+      match state.macro_getter.get_macro(&name)? {
+        Some(m) => squash_snapshots(state, label, |state| {
+          if let Some(selection) = given_selection {
+            state.selection = selection;
           }
-          else {
-            None
-          };
-          // Sloppy argument parsing into list
-          let mut args = clean.split(' ');
-          let macro_name = args.next().unwrap_or("");
-          let args: Vec<&str> = args.collect();
-          match state.macro_getter.get_macro(macro_name)? {
-            Some(m) => {
-              // Before disabling snapshotting, create one for this command
-              state.history.snapshot(clean_command.into());
-              // Disable undo snapshotting during macro execution
-              let orig_dont_snapshot = state.history.dont_snapshot;
-              state.history.dont_snapshot = true;
-              if let Some(selection) = given_selection {
-                state.selection = selection;
-              }
-              let res = state.private_run_macro(ui, m, &args, recursion_depth+1);
-              // Re-enable snapshotting after
-              state.history.dont_snapshot = orig_dont_snapshot;
-              // If snapshotting was originally enabled we should handle if no
-              // mutation of the buffer occured during the dont_snapshot.
-              if !orig_dont_snapshot { state.history.dedup_present(); }
-              res
-            },
-            None => Err(EdError::MacroUndefined(macro_name.to_owned())),
-          }?;
-          Ok(false)
+          state.private_run_macro(ui, m, &args, recursion_depth+1)
+        }),
+        None => Err(EdError::MacroUndefined(name)),
+      }?;
+      false
+    },
+    Command::Plugin{selection, name, arguments} => {
+      let given_selection = match selection {
+        Some(s) => Some(interpret_selection(state, Some(s), state.selection)?),
+        None => None,
+      };
+      // Take the plugin out while it runs, so it can get the whole state
+      let plugin = state.plugins.remove(&name)
+        .ok_or_else(|| EdError::PluginUndefined(name.clone()))
+      ?;
+      // Like macros, squash all modifications into one snapshot
+      let res = squash_snapshots(state, label, |state| {
+        plugin.run(state, ui, given_selection, &arguments)
+      });
+      state.plugins.insert(name, plugin);
+      res?;
+      false
+    },
+    Command::Other{selection, command, tail} => {
+      // Give the command extender, if any, a chance to claim the command
+      match state.command_extender.take() {
+        None => return Err(EdError::CommandUndefined(command)),
+        Some(extender) => {
+          let res = extender.run_command(
+            state, ui, &mut pflags, label, selection, command, &tail,
+          );
+          state.command_extender = Some(extender);
+          if !res? { return Err(EdError::CommandUndefined(command)); }
+          false
         },
-        _cmd => {
-          Err(EdError::CommandUndefined(ch))
-        }
       }
-    }
-  }?;
+    },
+  };
 
   // If print flags are set, print
   if pflags.p | pflags.n | pflags.l {
@@ -0,0 +1,27 @@
+use super::*;
+
+// List all options with their values if no name is given, otherwise set the
+// named option if given a value, else print it
+pub fn options(
+  state: &mut Ed<'_>,
+  ui: &mut dyn UI,
+  name: Option<&str>,
+  value: Option<&str>,
+) -> Result<()> {
+  match (name, value) {
+    (None, _) => {
+      let mut tmp = String::new();
+      for name in state.option_names() {
+        if !tmp.is_empty() { tmp.push('\n'); }
+        tmp.push_str(&format!("{}={}", name, state.get_option(&name)?));
+      }
+      ui.print_message(&tmp)?;
+    },
+    (Some(name), Some(value)) => state.set_option_text(name, value)?,
+    (Some(name), None) => {
+      let value = state.get_option(name)?;
+      ui.print_message(&format!("{}={}", name, value))?;
+    },
+  }
+  Ok(())
+}
@@ -21,18 +21,35 @@ pub fn parse_path(input: &str)
   }
 }
 
+// Quote the text for POSIX shells, unless it only holds characters which are
+// never special to the shell
+fn shell_quote(text: &str) -> String {
+  let safe = !text.is_empty() && text.chars().all(|c|
+    c.is_ascii_alphanumeric() || "_-./,:+@".contains(c)
+  );
+  if safe { text.to_owned() }
+  else { format!("'{}'", text.replace('\'', "'\\''")) }
+}
+
 pub fn command_substitutions(
   command: &str,
   state_file: &str,
   prev_command: &str,
+  quote: bool,
 ) -> Result<(bool, String)> {
   // In command we replace ! with previous command and % with state.file.
+  // If quote is set they are shell quoted, unless given as %% resp. !! which
+  // then insert them as is. (Without quoting %% inserts the file twice and !!
+  // the previous command twice, as they always have.) A lone ! repeats the
+  // previous command, so it is never quoted.
   // To not clash with other escape processing we only handle \% and \!,
   // for every other case we print the escaping \ and the escaped char.
+  let repeat = command.trim() == "!";
   let mut escaped = false;
   let mut modified = false;
   let mut output = String::new();
-  for ch in command.chars() {
+  let mut chars = command.chars().peekable();
+  while let Some(ch) = chars.next() {
     match ch {
       '\\' => if escaped {
         output.push_str("\\\\");
@@ -46,7 +63,16 @@ pub fn command_substitutions(
       } else {
         modified = true;
         if state_file.is_empty() { return Err(EdError::DefaultFileUnset); }
-        output.push_str(state_file);
+        if !quote {
+          output.push_str(state_file);
+        }
+        else if chars.peek() == Some(&'%') {
+          chars.next();
+          output.push_str(state_file);
+        }
+        else {
+          output.push_str(&shell_quote(state_file));
+        }
       },
       '!' => if escaped {
         output.push(ch);
@@ -56,7 +82,16 @@ pub fn command_substitutions(
         if prev_command.is_empty() {
           return Err(EdError::DefaultShellCommandUnset);
         }
-        output.push_str(prev_command);
+        if !quote || repeat {
+          output.push_str(prev_command);
+        }
+        else if chars.peek() == Some(&'!') {
+          chars.next();
+          output.push_str(prev_command);
+        }
+        else {
+          output.push_str(&shell_quote(prev_command));
+        }
       },
       _ => {
         if escaped {
@@ -79,6 +114,7 @@ mod test {
         "%",
         "state.file",
         "prev_command",
+        false,
       ).unwrap().1,
       "state.file",
       "command_substitutions didn't replace % with data from state_file."
@@ -88,6 +124,7 @@ mod test {
         "\\%",
         "state.file",
         "prev_command",
+        false,
       ).unwrap().1,
       "%",
       "command_substitutions didn't respect escape on %."
@@ -97,6 +134,7 @@ mod test {
         "!",
         "state.file",
         "prev_command",
+        false,
       ).unwrap().1,
       "prev_command",
       "command_substitutions didn't replace ! with data from prev_command."
@@ -106,6 +144,7 @@ mod test {
         "\\!",
         "state.file",
         "prev_command",
+        false,
       ).unwrap().1,
       "!",
       "command_substitutions didn't respect escape on !."
@@ -115,9 +154,44 @@ mod test {
         "\\\\",
         "state.file",
         "prev_command",
+        false,
       ).unwrap().1,
       "\\\\",
       "command_substitution handled escape on \\, it should be passed through."
     );
   }
+  #[test]
+  fn test_command_quoting() {
+    let quoted = |command: &str, file: &str| {
+      command_substitutions(command, file, "prev_command", true).unwrap().1
+    };
+    // Files without special characters are inserted as is
+    assert_eq!(&quoted("wc %", "dir/file-1.txt"), "wc dir/file-1.txt");
+    assert_eq!(&quoted("wc %", "a b"), "wc 'a b'");
+    assert_eq!(&quoted("wc %", "it's"), "wc 'it'\\''s'");
+    assert_eq!(&quoted("wc %", "a b; rm -rf x"), "wc 'a b; rm -rf x'");
+    assert_eq!(&quoted("wc %", "$(x) `y` *"), "wc '$(x) `y` *'");
+    assert_eq!(&quoted("wc %", "\"x\"&|<>"), "wc '\"x\"&|<>'");
+    // %% inserts the file as is, \% a literal %
+    assert_eq!(&quoted("wc %% \\%", "a b"), "wc a b %");
+    // The previous command is quoted, unless given as !! or alone
+    assert_eq!(&quoted("echo !", "a b"), "echo prev_command");
+    assert_eq!(
+      &command_substitutions("echo ! \\!", "a", "ls -l", true).unwrap().1,
+      "echo 'ls -l' !",
+    );
+    assert_eq!(
+      &command_substitutions("!! | wc", "a", "ls -l", true).unwrap().1,
+      "ls -l | wc",
+    );
+    assert_eq!(
+      &command_substitutions("!", "a", "ls -l", true).unwrap().1,
+      "ls -l",
+    );
+    // Without quoting %% and !! are still inserted twice
+    assert_eq!(
+      &command_substitutions("wc %% !!", "a b", "ls -l", false).unwrap().1,
+      "wc a ba b ls -lls -l",
+    );
+  }
 }
@@ -6,21 +6,60 @@ use crate::error::*;
 use crate::Ed;
 
 // A struct to formalise all the kinds of indices
-#[derive(PartialEq, Debug)]
-pub enum Ind <'a> {
+/// A parsed index, interpreted into a line number when a command runs
+#[derive(PartialEq, Debug, Clone)]
+pub enum Ind {
+  /// `.`, the current selection
   Selection,
+  /// `$`, the last line in the buffer
   BufferLen,
+  /// A line number
   Literal(usize),
+  /// `'x`, the first line tagged with the given char
   Tag(char),
-  Pattern(&'a str),
-  RevPattern(&'a str),
-  Add(Box<Ind<'a>>, usize),
-  Sub(Box<Ind<'a>>, usize),
+  /// `/regex/`, the next line matching the regex
+  Pattern(String),
+  /// `?regex?`, the previous line matching the regex
+  RevPattern(String),
+  /// An index plus an offset, such as `.+2`
+  Add(Box<Ind>, usize),
+  /// An index minus an offset, such as `$-2`
+  Sub(Box<Ind>, usize),
 }
 
-pub enum Sel <'a> {
-  Pair(Ind<'a>, Ind<'a>),
-  Lone(Ind<'a>)
+/// A parsed selection, interpreted into line numbers when a command runs
+///
+/// Which lines a lone index selects depends on the command.
+#[derive(PartialEq, Debug, Clone)]
+pub enum Sel {
+  /// A start and end index, such as `1,$`
+  Pair(Ind, Ind),
+  /// A single index, such as `3`
+  Lone(Ind)
+}
+
+// Printed as the index would be written, so it parses back into the same index
+impl std::fmt::Display for Ind {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    match self {
+      Ind::Selection => write!(f, "."),
+      Ind::BufferLen => write!(f, "$"),
+      Ind::Literal(i) => write!(f, "{}", i),
+      Ind::Tag(t) => write!(f, "'{}", t),
+      Ind::Pattern(p) => write!(f, "/{}/", p),
+      Ind::RevPattern(p) => write!(f, "?{}?", p),
+      Ind::Add(inner, offset) => write!(f, "{}+{}", inner, offset),
+      Ind::Sub(inner, offset) => write!(f, "{}-{}", inner, offset),
+    }
+  }
+}
+impl std::fmt::Display for Sel {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    match self {
+      Sel::Pair(a, b) => write!(f, "{},{}", a, b),
+      Sel::Lone(a) => write!(f, "{}", a),
+    }
+  }
 }
 
 enum State {
@@ -33,7 +72,7 @@ enum State {
 
 pub fn parse_index(
   input: &str,
-) -> Result<(usize, Option<Ind<'_>>)> {
+) -> Result<(usize, Option<Ind>)> {
   // Set up state variables for one-pass parse
   let mut i;
   let mut ch;
@@ -186,7 +225,7 @@ pub fn parse_index(
               unrelated_index: input[start-1..input.len().min(i+1)].to_owned(),
             }
           )}
-          current_ind = Some(Ind::Pattern(&input[start .. i]));
+          current_ind = Some(Ind::Pattern(input[start .. i].to_owned()));
           // Moving to state default means that state handles return as needed
           state = State::Default( i + 1 );
         }
@@ -200,7 +239,7 @@ pub fn parse_index(
               unrelated_index: input[start-1..input.len().min(i+1)].to_owned(),
             }
           )}
-          current_ind = Some(Ind::RevPattern(&input[start .. i]));
+          current_ind = Some(Ind::RevPattern(input[start .. i].to_owned()));
           // Moving to state default means that state handles return as needed
           state = State::Default( i + 1 );
         }
@@ -248,7 +287,7 @@ pub fn parse_index(
 
 pub fn parse_selection(
   input: &str,
-) -> Result<(usize, Option<Sel<'_>>)> {
+) -> Result<(usize, Option<Sel>)> {
   // First parse, getting an index and the offset it stopped parsing at
   let (offset, ind) = parse_index(input)?;
   // Match the next char to see what kind of selection this is
@@ -280,7 +319,7 @@ pub fn parse_selection(
 // Should not be able to return a index bigger than history.len().
 pub fn interpret_index(
   state: &Ed<'_>,
-  index: Ind<'_>,
+  index: Ind,
   old_selection: usize,
 ) -> Result<usize> {
   let ind = match index {
@@ -295,14 +334,14 @@ pub fn interpret_index(
     Ind::Pattern(pattern) =>
       super::get_matching(
         state.history.current(),
-        pattern,
+        &pattern,
         old_selection,
         super::Direction::Forwards,
       ),
     Ind::RevPattern(pattern) =>
       super::get_matching(
         state.history.current(),
-        pattern,
+        &pattern,
         old_selection,
         super::Direction::Backwards
       ),
@@ -324,7 +363,7 @@ pub fn interpret_index(
 // This function tries to make every selection inclusive towards its ending index
 pub fn interpret_selection(
   state: &Ed<'_>,
-  input: Option<Sel<'_>>,
+  input: Option<Sel>,
   old_selection: (usize, usize),
 ) -> Result<(usize, usize)> {
   let selection = input.unwrap_or(Sel::Pair( Ind::Selection, Ind::Selection ));
@@ -347,10 +386,10 @@ pub fn interpret_selection(
 // but also handles Lone indices better by giving them the correct default
 pub fn interpret_index_from_selection(
   state: &Ed<'_>,
-  selection: Option<Sel<'_>>,
+  selection: Option<Sel>,
   // When selection is None this is interpreted as a lone selection instead
   // If this isn't given it is defaulted to Ind::Selection
-//  default_index: Option<Ind<'_>>,
+//  default_index: Option<Ind>,
   old_selection: (usize, usize),
   appends: bool,
 ) -> Result<usize> {
@@ -26,7 +26,7 @@ fn mark_matching(
   ?;
   let mut match_found = false;
   for (i, line) in buffer.iter().enumerate() {
-    let mut matched_vec = line.matched.borrow_mut();
+    let mut matched_vec = line.matched.lock();
     // To ensure that there are no outdated values for this recursion depth in the
     // vec we shrink to one less than the size that would hold current depth.
     matched_vec.truncate(recursion_depth);
@@ -50,13 +50,13 @@ fn mark_matching(
 // Helper for all the "do X on matching lines" commands
 //
 // Gets and wipes matched lines at a given recursion depth.
-fn get_marked(
+pub(crate) fn get_marked(
   state: &mut Ed<'_>,
   recursion_depth: usize,
 ) -> Option<usize> {
   let buffer = state.history.current();
   for (index,line) in buffer.iter().enumerate() {
-    let mut matched_vec = line.matched.borrow_mut();
+    let mut matched_vec = line.matched.lock();
     match matched_vec.get(recursion_depth) {
       Some(x) => {
         let tmp = *x;
@@ -71,32 +71,29 @@ fn get_marked(
   None
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn global(
   state: &mut Ed<'_>,
   ui: &mut dyn UI,
-  selection: Option<Sel<'_>>,
-  command: char,
-  tail: &str,
+  selection: Option<Sel>,
+  inverse: bool,
+  separator: char,
+  pattern: &str,
+  commands: &[String],
   recursion_depth: usize,
 ) -> Result<()> {
   let selection = interpret_selection(&state, selection, state.selection)?;
-  // Since this command takes input we need to check inputs early
-  let mut expressions = parse_expressions(tail)?;
-  if expressions.len() < 2 {
-    return Err( EdError::ArgumentsWrongNr{expected: "2 or more".into(), received: expressions.len()} );
-  }
   // We first try to mark all matching lines, to tell if there is any issue
-  mark_matching(state, selection, &expressions[0], command == 'v', recursion_depth + 1)?;
+  mark_matching(state, selection, pattern, inverse, recursion_depth + 1)?;
   // Then we get the script to run against them, if not already given
   // First grab commands given on command line
-  let mut commands: Vec<String> = expressions.split_off(1).iter().map(|s| format!("{}\n", s)).collect();
+  let mut commands: Vec<String> = commands.iter().map(|s| format!("{}\n", s)).collect();
   // If the last command in that list is not empty it means the list was not terminated,
   // so we take more from input
   if commands.last().map(|s| s.trim()) != Some("") {
-    // expressions.len() would be 0 if no command, so safe to unwrap
     let mut input = ui.get_input(
       state,
-      tail.chars().next().unwrap(),
+      separator,
       #[cfg(feature = "initial_input_data")]
       None,
     )?;
@@ -128,48 +125,63 @@ pub fn global(
   Ok(())
 }
 
-pub fn global_interactive(
+// Marks the lines to run on for 'G' and 'V'
+//
+// (Split out, with global_interactive_line, so Ed::step can drive 'G' and 'V')
+pub(crate) fn global_interactive_mark(
   state: &mut Ed<'_>,
-  ui: &mut dyn UI,
-  selection: Option<Sel<'_>>,
-  command: char,
-  tail: &str,
+  selection: Option<Sel>,
+  inverse: bool,
+  pattern: &str,
   recursion_depth: usize,
 ) -> Result<()> {
   let selection = interpret_selection(&state, selection, state.selection)?;
-  // Since this command takes input we need to check inputs early
-  let expressions = parse_expressions(tail)?;
-  if expressions.len() != 2 {
-    return Err( EdError::ArgumentsWrongNr{expected: "2".into(), received: expressions.len()} );
-  }
-  if !expressions[1].is_empty() && expressions[1] != "\n" {
-    return Err(EdError::FlagUndefined(expressions[1].chars().next().unwrap()));
+  // Mark first, to check if the expression is valid
+  mark_matching(state, selection, pattern, inverse, recursion_depth + 1)
+}
+// Runs the commands given as input on a line marked by global_interactive_mark
+pub(crate) fn global_interactive_line(
+  state: &mut Ed<'_>,
+  ui: &mut dyn UI,
+  index: usize,
+  input: Vec<String>,
+  recursion_depth: usize,
+) -> Result<()> {
+  let mut scripted = ScriptedUI{
+    input: input.into(),
+    print_ui: Some(ui),
+  };
+  state.selection = (index, index);
+  loop {
+    if state.private_get_and_run_command(&mut scripted, recursion_depth + 1)? {
+      break;
+    }
   }
+  Ok(())
+}
 
-  // Mark first, to check if the expression is valid
-  mark_matching(state, selection, &expressions[0], command == 'V', recursion_depth + 1)?;
+pub fn global_interactive(
+  state: &mut Ed<'_>,
+  ui: &mut dyn UI,
+  selection: Option<Sel>,
+  inverse: bool,
+  separator: char,
+  pattern: &str,
+  recursion_depth: usize,
+) -> Result<()> {
+  global_interactive_mark(state, selection, inverse, pattern, recursion_depth)?;
   // With all data gathered we fetch and iterate over the lines
   while let Some(index) = get_marked(state, recursion_depth + 1) {
     // Print the line, so the user knows what they are changing
     ui.print_selection(state, (index, index), state.n, state.l)?;
-    // Get input and create dummy-ui with it
-    // expressions.len() == 2 implies that a separator was given
+    // Get input and run it on the line
     let input = ui.get_input(
       state,
-      tail.chars().next().unwrap(),
+      separator,
       #[cfg(feature = "initial_input_data")]
       None,
     )?;
-    let mut scripted = ScriptedUI{
-      input: input.into(),
-      print_ui: Some(ui),
-    };
-    state.selection = (index, index);
-    loop {
-      if state.private_get_and_run_command(&mut scripted, recursion_depth + 1)? {
-        break;
-      }
-    }
+    global_interactive_line(state, ui, index, input, recursion_depth)?;
   }
   Ok(())
 }
@@ -4,7 +4,7 @@ use super::*;
 //
 // Cuts out the selection, performs substitution and returns the index of the
 // selection's end after the substitution (since selection's length may change)
-fn inner_substitute(
+pub(crate) fn inner_substitute(
   history: &mut crate::History<crate::Buffer>,
   clipboard: &mut Clipboard,
   command: &str, // Only because history needs it
@@ -23,7 +23,7 @@ fn inner_substitute(
   let buffer_view = history.current();
   buffer_view.verify_selection(selection)?;
   let mut agg = String::new();
-  for line in &buffer_view[selection.0 - 1 .. selection.1] {
+  for line in buffer_view.range(selection.0 - 1 .. selection.1) {
     agg.push_str(&line.text);
   }
   if !regex.is_match(&agg) {
@@ -38,7 +38,7 @@ fn inner_substitute(
   let mut tail = buffer.split_off(selection.1);
   let before = buffer.split_off(selection.0 - 1);
   // The before state should be saved in clipboard for all editing operations
-  *clipboard = (&*before).into();
+  *clipboard = (&before).into();
 
   // interpret escape sequences, then perform substitution
   // We use data from buffer_view, since it cannot have changed
@@ -70,71 +70,33 @@ pub fn substitute(
   state: &mut Ed<'_>,
   pflags: &mut PrintingFlags,
   command: &str,
-  selection: Option<Sel<'_>>,
-  tail: &str,
+  selection: Option<Sel>,
+  arguments: Option<Substitution>,
 ) -> Result<()> {
   let selection = interpret_selection(&state, selection, state.selection)?;
-  // Clip newline from tail if any
-  let tail = tail.trim_end_matches('\n');
-  // switch based on if tail was given or not
-  if tail.is_empty() {
-    // This means we use the arguments stored in state.s_args
-    match &state.prev_s {
-      None => return Err(EdError::DefaultSArgsUnset),
-      Some(s) => {
-        pflags.p = s.p;
-        pflags.n = s.n;
-        pflags.l = s.l;
-        let end = inner_substitute(
-          &mut state.history,
-          &mut state.clipboard,
-          command,
-          selection,
-          &s.pattern,
-          &s.substitute,
-          s.global,
-        )?;
-        // If we have deleted the whole selection we start sel at end,
-        // in order to select line before the deleted lines. (min(end))
-        // If end is smaller than 1 we have deleted to start of
-        // buffer, then we use (1,0). (max(1))
-        state.selection = (selection.0.min(end).max(1), end);
-      }
-    }
-  }
-  else {
-    let expressions = parse_expressions(tail)?;
-    if expressions.len() != 3 {
-      return Err( EdError::ArgumentsWrongNr{expected: "none or 3".into(), received: expressions.len()} );
-    }
-    let mut flags = parse_flags(&(expressions[2]), "gpnl")?;
-    let g = flags.remove(&'g').unwrap();
-    pflags.p = flags.remove(&'p').unwrap();
-    pflags.n = flags.remove(&'n').unwrap();
-    pflags.l = flags.remove(&'l').unwrap();
-    let end = inner_substitute(
-      &mut state.history,
-      &mut state.clipboard,
-      command,
-      selection,
-      &expressions[0],
-      &expressions[1],
-      g,
-    )?;
-    // If we have deleted the whole selection we start sel at end,
-    // in order to select line before the deleted lines. (min(end))
-    // If end is smaller than 1 we have deleted whole buffer,
-    // then we use (1,0). (max(1))
-    state.selection = (selection.0.min(end).max(1), end);
-    // If that was valid we save all the arguments to support lone 's'
-    state.prev_s = Some(Substitution{
-      pattern: expressions[0].to_string(),
-      substitute: expressions[1].to_string(),
-      global: g,
-      p: pflags.p,
-      n: pflags.n,
-      l: pflags.l,
-    });
-  }
+  // Without arguments we use the ones stored in state.prev_s
+  let s = match arguments.or_else(|| state.prev_s.clone()) {
+    None => return Err(EdError::DefaultSArgsUnset),
+    Some(s) => s,
+  };
+  pflags.p = s.p;
+  pflags.n = s.n;
+  pflags.l = s.l;
+  let end = inner_substitute(
+    &mut state.history,
+    &mut state.clipboard,
+    command,
+    selection,
+    &s.pattern,
+    &s.substitute,
+    s.global,
+  )?;
+  // If we have deleted the whole selection we start sel at end,
+  // in order to select line before the deleted lines. (min(end))
+  // If end is smaller than 1 we have deleted whole buffer,
+  // then we use (1,0). (max(1))
+  state.selection = (selection.0.min(end).max(1), end);
+  // If that was valid we save all the arguments to support lone 's'
+  state.prev_s = Some(s);
   Ok(())
 }
@@ -1,46 +1,187 @@
 use super::*;
 
+use std::time::{Duration, SystemTime};
+
+// Parse nr of steps, defaulting to 1 if none given
+fn parse_steps(text: &str) -> Result<usize> {
+  let steps = if !text.is_empty() {
+    text
+      .parse::<usize>()
+      .map_err(|_| EdError::UndoStepsNotInt(text.to_owned()))
+    ?
+  } else { 1 };
+  if steps == 0 { return Err(EdError::NoOp); }
+  Ok(steps)
+}
+
+// Find the nearest snapshot in the given direction whose label matches pattern
+fn find_snapshot(
+  state: &Ed<'_>,
+  pattern: &str,
+  forwards: bool,
+) -> Result<usize> {
+  let regex = regex::Regex::new(pattern)
+    .map_err(|e| EdError::regex_error(e, pattern))
+  ?;
+  let snapshots = state.history.snapshots();
+  let viewed = state.history.viewed_i();
+  let found = if forwards {
+    (viewed + 1 .. snapshots.len()).find(|i| regex.is_match(&snapshots[*i].0))
+  } else {
+    (0 .. viewed).rev().find(|i| regex.is_match(&snapshots[*i].0))
+  };
+  found.ok_or_else(|| EdError::RegexNoMatch(pattern.to_owned()))
+}
+
+// Interpret the absolute snapshot index after `u*`
+fn parse_absolute(
+  state: &Ed<'_>,
+  text: &str,
+) -> Result<usize> {
+  let viewed = state.history.viewed_i();
+  match text.chars().next() {
+    // Relative to the last snapshot, with an optional negative offset
+    Some('$') => {
+      let last = state.history.len() - 1;
+      let offset = match &text[1..] {
+        "" => 0,
+        x if x.starts_with('-') => parse_steps(&x[1..])?,
+        x => return Err(EdError::UndoStepsNotInt(x.to_owned())),
+      };
+      last.checked_sub(offset).ok_or(EdError::UndoIndexNegative{
+        relative_undo_limit: viewed,
+      })
+    },
+    // The snapshot marked as saved
+    Some('s') if text.len() == 1 => {
+      state.history.saved_i().ok_or(EdError::UndoSavedUnknown)
+    },
+    _ => text
+      .parse::<usize>()
+      .map_err(|_| EdError::UndoStepsNotInt(text.to_owned()))
+    ,
+  }
+}
+
+// Split off the longest prefix of text in the duration syntax, a series of
+// integers each followed by a unit (s, m, h or d). Greedy, so a trailing
+// `d` after a full duration is left over (for the `d` flag to `U`).
+fn split_duration(text: &str) -> (&str, &str) {
+  let mut end = 0;
+  loop {
+    let digits = text[end..].find(|c: char| !c.is_ascii_digit())
+      .unwrap_or(text.len() - end)
+    ;
+    match text[end + digits ..].chars().next() {
+      Some('s' | 'm' | 'h' | 'd') if digits != 0 => end += digits + 1,
+      _ => break,
+    }
+  }
+  text.split_at(end)
+}
+
+// Parse a duration such as `1h30m`, as split off by split_duration
+fn parse_duration(text: &str) -> Result<Duration> {
+  let err = || EdError::UndoDurationInvalid(text.to_owned());
+  if text.is_empty() { return Err(err()); }
+  let mut seconds: u64 = 0;
+  let mut start = 0;
+  for (i, c) in text.char_indices() {
+    let unit = match c {
+      's' => 1,
+      'm' => 60,
+      'h' => 60 * 60,
+      'd' => 24 * 60 * 60,
+      _ => continue,
+    };
+    seconds = text[start..i].parse::<u64>().ok()
+      .and_then(|x| x.checked_mul(unit))
+      .and_then(|x| x.checked_add(seconds))
+      .ok_or_else(err)
+    ?;
+    start = i + 1;
+  }
+  if start != text.len() { return Err(err()); }
+  Ok(Duration::from_secs(seconds))
+}
+
+// Get the index of the snapshot that was newest the given duration ago
+fn index_before(state: &Ed<'_>, duration: Duration) -> Result<usize> {
+  state.history.clock.now().checked_sub(duration)
+    .and_then(|time| state.history.index_at(time))
+    .ok_or(EdError::UndoIndexNegative{
+      relative_undo_limit: state.history.viewed_i(),
+    })
+}
+
+// Format how long ago a time was, in its largest whole unit
+fn format_age(now: SystemTime, time: SystemTime) -> String {
+  let seconds = now.duration_since(time).unwrap_or_default().as_secs();
+  let (amount, unit) = match seconds {
+    x if x < 60 => (x, 's'),
+    x if x < 60 * 60 => (x / 60, 'm'),
+    x if x < 24 * 60 * 60 => (x / (60 * 60), 'h'),
+    x => (x / (24 * 60 * 60), 'd'),
+  };
+  format!("{}{} ago", amount, unit)
+}
+
 pub fn undo(
   state: &mut Ed<'_>,
   ui: &mut dyn UI,
-  selection: Option<Sel<'_>>,
+  selection: Option<Sel>,
   arguments: &str,
 ) -> Result<()> {
   if selection.is_some() {return Err(EdError::SelectionForbidden); }
-  // A undo steps parsing not unlike index parsing would be good later
-  // ie. relative AND shorthand for start and end of history
-  let mut arg_iter = arguments.chars();
-  match arg_iter.next() {
-    // Go to point in history TODO
-    //Some('*') => {},
+  match arguments.chars().next() {
+    // Go to absolute point in history, or search for one by label. All of
+    // these go to the index directly, ignoring branches.
+    Some(c @ ('*' | '/' | '?' | '@' | ':')) => {
+      let new_i = match c {
+        '*' => parse_absolute(state, &arguments[1..])?,
+        ':' => state.history.find_checkpoint(&arguments[1..])
+          .ok_or_else(|| EdError::UndoCheckpointUnknown(arguments[1..].to_owned()))
+        ?,
+        '@' => index_before(state, parse_duration(&arguments[1..])?)?,
+        // Like regex indices the closing separator is optional
+        _ => {
+          let pattern = arguments[1..].strip_suffix(c).unwrap_or(&arguments[1..]);
+          find_snapshot(state, pattern, c == '/')?
+        },
+      };
+      let new_pos = state.history.set_viewed_i(new_i)?;
+      ui.print_message(&format!(
+        "Moved to snapshot {}, right after {}.",
+        new_i,
+        new_pos,
+      ))?;
+    },
     // Undo/redo (negative is redo
     Some('-') => {
-      let steps = if arg_iter.next().is_some() {
-        arguments[1..]
-          .parse::<usize>()
-          .map_err(|_| EdError::UndoStepsNotInt(arguments[1..].to_owned()))
-        ?
-      } else { 1 };
-      if steps == 0 { return Err(EdError::NoOp); }
-      let new_pos = state.history.set_viewed_i(state.history.viewed_i() + steps)?;
+      let steps = parse_steps(&arguments[1..])?;
+      let new_pos = state.history.redo(steps)?;
       ui.print_message(&format!(
         "Redid {} operation(s) to right after {}.",
         steps,
         new_pos,
       ))?;
     },
-    x => {
-      let steps = if x.is_some() {
-        arguments
-          .parse::<usize>()
-          .map_err(|_| EdError::UndoStepsNotInt(arguments.to_owned()))
-        ?
-      } else { 1 };
-      if steps == 0 { return Err(EdError::NoOp); }
-      if state.history.viewed_i() < steps {
-        return Err(EdError::UndoIndexNegative{relative_undo_limit: state.history.viewed_i()});
-      }
-      let new_pos = state.history.set_viewed_i(state.history.viewed_i() - steps)?;
+    // Switch to a newer or older branch of history
+    Some(dir @ ('>' | '<')) => {
+      let steps = parse_steps(&arguments[1..])?;
+      let new_pos = state.history.switch_branch(
+        if dir == '>' { steps as isize } else { -(steps as isize) }
+      )?;
+      ui.print_message(&format!(
+        "Switched {} branch(es) {} to right after {}.",
+        steps,
+        if dir == '>' { "newer" } else { "older" },
+        new_pos,
+      ))?;
+    },
+    _ => {
+      let steps = parse_steps(arguments)?;
+      let new_pos = state.history.undo(steps)?;
       ui.print_message(&format!(
         "Undid {} operation(s) to right after {}.",
         steps,
@@ -51,48 +192,185 @@ pub fn undo(
   Ok(())
 }
 
+// Line level diff from old to new, as lines prefixed by '-' or '+' and the
+// line number in old resp. new buffer.
+//
+// Trims identical start and end before diffing, which is cheap thanks to the
+// texts being shared between snapshots. Changes where the remaining diff would
+// be too costly to calculate are shown as removing and adding all lines.
+fn diff(old: &Buffer, new: &Buffer) -> Vec<String> {
+  let old: Vec<&Line> = old.iter().collect();
+  let new: Vec<&Line> = new.iter().collect();
+  let prefix = old.iter().zip(new.iter())
+    .take_while(|(a, b)| a.text == b.text)
+    .count()
+  ;
+  let suffix = old[prefix..].iter().rev().zip(new[prefix..].iter().rev())
+    .take_while(|(a, b)| a.text == b.text)
+    .count()
+  ;
+  let a = &old[prefix .. old.len() - suffix];
+  let b = &new[prefix .. new.len() - suffix];
+  let format = |sign: char, i: usize, line: &Line| format!(
+    "{}{}\t{}", sign, prefix + i + 1, line.text.trim_end_matches('\n'),
+  );
+  let mut out = Vec::new();
+  // Longest common subsequence, table of lcs length for a[i..] and b[j..]
+  const MAX_TABLE_SIZE: usize = 1 << 22;
+  if (a.len() + 1).saturating_mul(b.len() + 1) > MAX_TABLE_SIZE {
+    out.extend(a.iter().enumerate().map(|(i, l)| format('-', i, l)));
+    out.extend(b.iter().enumerate().map(|(j, l)| format('+', j, l)));
+    return out;
+  }
+  let width = b.len() + 1;
+  let mut table = vec![0usize; (a.len() + 1) * width];
+  for i in (0 .. a.len()).rev() {
+    for j in (0 .. b.len()).rev() {
+      table[i * width + j] = if a[i].text == b[j].text {
+        table[(i + 1) * width + j + 1] + 1
+      } else {
+        table[(i + 1) * width + j].max(table[i * width + j + 1])
+      };
+    }
+  }
+  let (mut i, mut j) = (0, 0);
+  while i < a.len() || j < b.len() {
+    if i < a.len() && j < b.len() && a[i].text == b[j].text {
+      i += 1;
+      j += 1;
+    } else if j == b.len() ||
+      (i < a.len() && table[(i + 1) * width + j] >= table[i * width + j + 1])
+    {
+      out.push(format('-', i, a[i]));
+      i += 1;
+    } else {
+      out.push(format('+', j, b[j]));
+      j += 1;
+    }
+  }
+  out
+}
+
 pub fn manage_history(
   state: &mut Ed<'_>,
   ui: &mut dyn UI,
-  selection: Option<Sel<'_>>,
+  selection: Option<Sel>,
   tail: &str,
 ) -> Result<()> {
   if selection.is_some() {return Err(EdError::SelectionForbidden); }
-  // Some custom flags (or maybe arguments) should probably be added later
-  let mut _flags = parse_flags(tail, "")?;
-  // To enable undoing to an absolute "index" from start of editing the
-  // History struct must allow both accessing current index and the labels for
-  // all indices. Due to the data structure this will also require allowing
-  // access to the snapshotted states and effectively expose internal design.
-
-  // Figure out the history index slice for the nearest 10 snapshots
-  let i = state.history.viewed_i();
+  // Name the viewed snapshot, or list checkpoints if no name given
+  if let Some(name) = tail.strip_prefix(':') {
+    if !name.is_empty() {
+      state.history.set_checkpoint(name.to_owned());
+      return Ok(());
+    }
+    let view = state.history.snapshots();
+    let checkpoints = state.history.checkpoints();
+    if checkpoints.is_empty() {
+      ui.print_message(NO_CHECKPOINTS)?;
+      return Ok(());
+    }
+    let tmp = checkpoints.iter()
+      .map(|(i, name)| format!("{}: snapshot {}, {}", name, i, view[*i].0))
+      .collect::<Vec<_>>()
+      .join("\n")
+    ;
+    ui.print_message(&tmp)?;
+    return Ok(());
+  }
+  // Parse optional index to show, same syntax as for `u*` or `u@`, then flags
+  let index_end = match tail.chars().next() {
+    Some('$') => 1 + tail[1..].find(|c: char| c != '-' && !c.is_ascii_digit())
+      .unwrap_or(tail.len() - 1),
+    Some('@') => 1 + split_duration(&tail[1..]).0.len(),
+    _ => tail.find(|c: char| !c.is_ascii_digit()).unwrap_or(tail.len()),
+  };
+  let index = if index_end == 0 { state.history.viewed_i() }
+  else if tail.starts_with('@') {
+    index_before(state, parse_duration(&tail[1..index_end])?)?
+  }
+  else {
+    let index = parse_absolute(state, &tail[..index_end])?;
+    if index >= state.history.len() { return Err(EdError::UndoIndexTooBig{
+      index,
+      history_len: state.history.len(),
+      relative_redo_limit: state.history.len() - state.history.viewed_i() - 1,
+    }); }
+    index
+  };
+  let mut flags = parse_flags(&tail[index_end..], "aAdt")?;
+  let absolute = flags.remove(&'a').unwrap();
+  let all = flags.remove(&'A').unwrap();
+  let times = flags.remove(&'t').unwrap();
   let view = state.history.snapshots();
+
+  // Diff mode prints the changes made by the snapshot, relative its parent
+  if flags.remove(&'d').unwrap() {
+    let empty = Buffer::default();
+    let parent = match state.history.parent(index) {
+      Some(p) => &view[p].1,
+      None => &empty,
+    };
+    let mut tmp = format!("Changes by snapshot {}, {}:", index, view[index].0);
+    for line in diff(parent, &view[index].1) {
+      tmp.push('\n');
+      tmp.push_str(&line);
+    }
+    ui.print_message(&tmp)?;
+    return Ok(());
+  }
+
+  // History is a tree of snapshots, which without branches is a plain list.
+  // List it in depth first order, so branches are shown as indented.
+  let tree = state.history.tree();
+  // Figure out the tree slice for the nearest 10 snapshots
+  // (Safe to unwrap, as all snapshots are in the tree)
+  let i = tree.iter().position(|(x, _)| *x == index).unwrap();
+  let tree_indices = if all {
+    0 .. tree.len()
+  }
   // If in the first five snapshots we want the first 10
-  let history_indices = if i < 10 {
-    // Use .min(view.len()) to limit within valid slicing
-    0 .. 10.min(view.len())
+  else if i < 10 {
+    // Use .min(tree.len()) to limit within valid slicing
+    0 .. 10.min(tree.len())
   }
   // If in the last five snapshots we want the last 10
-  else if view.len().saturating_sub(10) <= i {
+  else if tree.len().saturating_sub(10) <= i {
     // Use saturating sub to avoid underflow
-    view.len().saturating_sub(10) .. view.len()
+    tree.len().saturating_sub(10) .. tree.len()
   }
   // Otherwise we want the 5 preceding, current and 4 following snapshots
   // (Since none of the preceeding were true we can safely slice this)
   else {
-    i - 5 .. i + 4
+    i - 5 .. i + 5
   };
 
   // Print it nicely
+  let viewed = state.history.viewed_i();
   let saved = state.history.saved_i();
+  let index_width = (view.len() - 1).to_string().len();
+  let now = state.history.clock.now();
+  let ages: Vec<String> = tree_indices.clone()
+    .map(|ti| format_age(now, state.history.time(tree[ti].0).unwrap()))
+    .collect()
+  ;
+  let age_width = ages.iter().map(|x| x.len()).max().unwrap_or(0);
   let mut tmp = String::new();
-  for hi in history_indices {
+  for (ti, age) in tree_indices.zip(ages) {
+    let (hi, depth) = tree[ti];
+    if !tmp.is_empty() { tmp.push('\n'); }
     tmp.push_str(&format!(
-      "{} {} {}",
-      if hi == i { '>' } else { ' ' },
+      "{} {}{}{}{}{}{}",
+      if hi == viewed { '>' } else { ' ' },
+      if absolute { format!("{:>1$} ", hi, index_width) } else { String::new() },
+      if times { format!("{:>1$} ", age, age_width) } else { String::new() },
+      "  ".repeat(depth),
       view[hi].0,
-      if Some(hi) == saved { "(saved)" } else { "" },
+      match state.history.checkpoint(hi) {
+        Some(name) => format!(" [{}]", name),
+        None => String::new(),
+      },
+      if Some(hi) == saved { " (saved)" } else { "" },
     ));
   }
   ui.print_message(&tmp)?;
@@ -21,6 +21,12 @@ impl std::fmt::Display for EdError {
       InfiniteRecursion => write!(f,
         "Execution recursion hit recursion limit, no changes made."
       ),
+      ResponseUnexpected => write!(f,
+        "The response given doesn't answer the last request from the editor."
+      ),
+      StepInputUnsupported => write!(f,
+        "Plugins and command extenders can't take input in this editor."
+      ),
 
       IndexTooBig{index, buffer_len} => {
         if *buffer_len == 0 {
@@ -62,6 +68,29 @@ impl std::fmt::Display for EdError {
         index,
         history_len - 1,
       ),
+      UndoBranchNonexistent{older_branches, newer_branches} => write!(f,
+        "Tried to switch to a branch of history that doesn't exist.\nThere are {} older and {} newer branches from the same snapshot.",
+        older_branches,
+        newer_branches,
+      ),
+      BufferUnknown(name) => write!(f,
+        "No open buffer named `{}`.",
+        name,
+      ),
+      BufferNameInvalid(name) => write!(f,
+        "Invalid buffer name `{}`, it may not be empty nor contain whitespace or `:`.",
+        name,
+      ),
+      BufferLastOpen => write!(f,
+        "Cannot close the only open buffer, use q to quit.",
+      ),
+      UndoCheckpointUnknown(name) => write!(f,
+        "No checkpoint named `{}` in history.",
+        name,
+      ),
+      UndoSavedUnknown => write!(f,
+        "Tried to go to the saved snapshot, but no snapshot is known to match the saved file.",
+      ),
       CommandEscapeForbidden(_path) => write!(f,
         "Command doesn't accept shell escapes. Use \\! if path begins with !.",
       ),
@@ -96,6 +125,16 @@ impl std::fmt::Display for EdError {
         "Couldn't read default `s` arguments as they haven't been set. Run `s` with arguments first.",
       ),
 
+      HistoryFileInvalid{path, error} => write!(f,
+        "Couldn't parse undo history from `{}`: {}",
+        path,
+        error,
+      ),
+      SessionInvalid(error) => write!(f,
+        "Couldn't restore session: {}",
+        error,
+      ),
+
       IndexSpecialAfterStart{prior_index, special_index} => write!(f,
         "Special index character `{}` found after index `{}`.",
         special_index,
@@ -123,6 +162,10 @@ impl std::fmt::Display for EdError {
         "Unknown command `{}`.",
         cmd,
       ),
+      CommandNewline(text) => write!(f,
+        "Command may not contain a newline: `{}`.",
+        text.escape_debug(),
+      ),
       ArgumentListEscapedEnd(arguments) => write!(f,
         "Argument list's end was escaped: `{}`.",
         arguments,
@@ -140,6 +183,10 @@ impl std::fmt::Display for EdError {
         "Failed to parse nr of changes to undo/redo `{}` as a number.",
         text,
       ),
+      UndoDurationInvalid(text) => write!(f,
+        "Failed to parse `{}` as a duration, expected for example `1h30m`.",
+        text,
+      ),
       ReflowNotInt{error: e, text: t} => write!(f,
         "Failed to parse nr of columns to reflow within `{}` as a number: {}",
         t,
@@ -149,6 +196,20 @@ impl std::fmt::Display for EdError {
         "Given macro `{}` is not defined.",
         macro_name,
       ),
+      PluginUndefined(plugin_name) => write!(f,
+        "Given plugin `{}` is not defined.",
+        plugin_name,
+      ),
+      OptionUndefined(option_name) => write!(f,
+        "Given option `{}` is not defined.",
+        option_name,
+      ),
+      OptionValueInvalid{name, value, expected} => write!(f,
+        "Option `{}` takes a {} value, `{}` isn't one.",
+        name,
+        expected,
+        value,
+      ),
 
       FlagDuplicate(flag) => write!(f,
         "Flag `{}` was given more than once.",
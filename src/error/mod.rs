@@ -1,6 +1,6 @@
 //! Holds Error type for the crate
 
-use std::rc::Rc;
+use crate::sync::{Shared, MaybeSend};
 use std::borrow::Cow;
 
 pub type Result<T> = std::result::Result<T, EdError>;
@@ -11,14 +11,14 @@ pub use internal::*;
 
 // Define structs and traits for UI and IO errors
 /// A trait to mark fulfilling the requirements put upon UI error types.
-pub trait UIErrorTrait: std::error::Error + as_any::AsAny + 'static {}
+pub trait UIErrorTrait: std::error::Error + as_any::AsAny + MaybeSend + 'static {}
 /// A wrapping struct for any UI's error type
 ///
 /// To use the wrapper implement [`UIErrorTrait`] on the error type to wrap and
 /// use `.into()` to convert it into this UIError wrapper.
 #[derive(Clone, Debug)]
 pub struct UIError {
-  pub inner: Rc<dyn UIErrorTrait>,
+  pub inner: Shared<dyn UIErrorTrait>,
 }
 impl UIError {
   /// Helper for downcasting into the internal error type
@@ -31,7 +31,7 @@ impl UIError {
   }
 }
 /// A trait to mark fulfilling the requirements put upon IO error types.
-pub trait IOErrorTrait: std::error::Error + as_any::AsAny + 'static {}
+pub trait IOErrorTrait: std::error::Error + as_any::AsAny + MaybeSend + 'static {}
 /// A wrapper type for any IO implementation's error type
 ///
 /// To use the wrapper implement [`IOErrorTrait`] on the error type to wrap. The
@@ -40,7 +40,7 @@ pub trait IOErrorTrait: std::error::Error + as_any::AsAny + 'static {}
 /// likely still needed to call `.into()` to convert.
 #[derive(Clone, Debug)]
 pub struct IOError {
-  pub inner: Rc<dyn IOErrorTrait>,
+  pub inner: Shared<dyn IOErrorTrait>,
 }
 impl IOError {
   /// Helper for downcasting into the internal error type
@@ -85,6 +85,12 @@ pub enum EdError {
   ///
   /// Contains no details until someone writes stack unwinding for it.
   InfiniteRecursion,
+  /// The response given to [`crate::Ed::step`] doesn't answer the request it
+  /// last returned.
+  ResponseUnexpected,
+  /// A plugin or command extender asked for input while run by
+  /// [`crate::Ed::step`], which only resumes built-in commands.
+  StepInputUnsupported,
 
   // Selection/index interpretation/validation errors
   /// Given index exceeds size of buffer.
@@ -108,6 +114,19 @@ pub enum EdError {
   UndoIndexNegative{relative_undo_limit: usize},
   /// Tried to redo past end of history.
   UndoIndexTooBig{index: usize, history_len: usize, relative_redo_limit: usize},
+  /// Tried to switch to a branch of history that doesn't exist.
+  /// Holds the number of older and newer branches that do exist.
+  UndoBranchNonexistent{older_branches: usize, newer_branches: usize},
+  /// Tried to go to the saved snapshot, but no snapshot is known to be saved.
+  UndoSavedUnknown,
+  /// No snapshot in history has the given checkpoint name.
+  UndoCheckpointUnknown(String),
+  /// No buffer with the given name is open.
+  BufferUnknown(String),
+  /// The given buffer name is empty or contains whitespace or `:`.
+  BufferNameInvalid(String),
+  /// Closing the only open buffer isn't possible.
+  BufferLastOpen,
   /// Tried to given shell escape where a file path is required.
   /// Holds given path string.
   CommandEscapeForbidden(String),
@@ -133,6 +152,13 @@ pub enum EdError {
   /// Tried to get default `s` arguments, but it isn't yet set
   DefaultSArgsUnset,
 
+  /// The stored undo history for a file couldn't be parsed.
+  /// Holds the path to the history file and a description of the problem.
+  HistoryFileInvalid{path: String, error: String},
+  /// The session given to [`crate::Ed::restore_session`] is inconsistent.
+  /// Holds a description of the problem.
+  SessionInvalid(String),
+
   // Index parsing errors
   /// Special index character found after start of index.
   IndexSpecialAfterStart{prior_index: String, special_index: char},
@@ -152,6 +178,10 @@ pub enum EdError {
   /// The given command doesn't exist.
   /// Holds given command char.
   CommandUndefined(char),
+  /// A parsed command given to `Ed::execute` holds a newline, which would
+  /// be run as another command.
+  /// Holds the command's text.
+  CommandNewline(String),
   /// Argument list ended with `\`.
   /// Holds whole argument list.
   ArgumentListEscapedEnd(String),
@@ -163,12 +193,24 @@ pub enum EdError {
   /// `u` or `U` command couldn't interpret nr of steps to undo/redo as integer.
   /// Holds given argument.
   UndoStepsNotInt(String),
+  /// `u` or `U` command couldn't interpret the time to move back in history.
+  /// Holds given argument.
+  UndoDurationInvalid(String),
   /// `J` command received a non numeric number of columns to reflow within.
   /// Holds given argument.
   ReflowNotInt{error: String, text: String},
   /// The macro invoked wasn't found.
   /// Holds given macro name.
   MacroUndefined(String),
+  /// The plugin invoked wasn't found (or is already running).
+  /// Holds given plugin name.
+  PluginUndefined(String),
+  /// The option given doesn't exist.
+  /// Holds given option name.
+  OptionUndefined(String),
+  /// The value given for an option isn't of the option's type.
+  /// Holds the option name, the value given and the name of the expected type.
+  OptionValueInvalid{name: String, value: String, expected: &'static str},
 
   // Flag parsing errors
   /// Same flag appears more than once.
@@ -188,7 +230,7 @@ impl From<UIError> for EdError {
 }
 impl<E: UIErrorTrait> From<E> for UIError {
   fn from(e: E) -> Self {
-    Self{ inner: Rc::new(e) }
+    Self{ inner: Shared::new(e) }
   }
 }
 // Causes conflicting trait bounds error for now. Instead use
@@ -205,7 +247,7 @@ impl From<IOError> for EdError {
 }
 impl<E: IOErrorTrait> From<E> for IOError {
   fn from(e: E) -> Self {
-    Self{ inner: Rc::new(e) }
+    Self{ inner: Shared::new(e) }
   }
 }
 // Causes conflicting trait bounds error for now. Instead use
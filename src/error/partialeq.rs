@@ -9,6 +9,8 @@ impl std::cmp::PartialEq for EdError {
       (UI(_),UI(_)) => true,
 
       (InfiniteRecursion, InfiniteRecursion) => true,
+      (ResponseUnexpected, ResponseUnexpected) => true,
+      (StepInputUnsupported, StepInputUnsupported) => true,
 
       (
         IndexTooBig{index: a, buffer_len: b},
@@ -22,12 +24,27 @@ impl std::cmp::PartialEq for EdError {
 
       (UnsavedChanges,UnsavedChanges) => true,
       (NoOp,NoOp) => true,
+      (
+        UndoIndexNegative{relative_undo_limit: a},
+        UndoIndexNegative{relative_undo_limit: b},
+      ) => a == b,
       (
         UndoIndexTooBig{index: a, history_len: b, relative_redo_limit: c},
         UndoIndexTooBig{index: d, history_len: e, relative_redo_limit: f},
       ) => {
         a == d && b == e && c == f
       },
+      (
+        UndoBranchNonexistent{older_branches: a, newer_branches: b},
+        UndoBranchNonexistent{older_branches: c, newer_branches: d},
+      ) => {
+        a == c && b == d
+      },
+      (UndoSavedUnknown,UndoSavedUnknown) => true,
+      (UndoCheckpointUnknown(x),UndoCheckpointUnknown(y)) => x == y,
+      (BufferUnknown(x),BufferUnknown(y)) => x == y,
+      (BufferNameInvalid(x),BufferNameInvalid(y)) => x == y,
+      (BufferLastOpen,BufferLastOpen) => true,
       (CommandEscapeForbidden(x),CommandEscapeForbidden(y)) => x == y,
       (TagInvalid(x),TagInvalid(y)) => x == y,
       (TagNoMatch(x),TagNoMatch(y)) => x == y,
@@ -44,6 +61,14 @@ impl std::cmp::PartialEq for EdError {
       (DefaultShellCommandUnset,DefaultShellCommandUnset) => true,
       (DefaultSArgsUnset,DefaultSArgsUnset) => true,
 
+      (
+        HistoryFileInvalid{path: a, error: b},
+        HistoryFileInvalid{path: c, error: d},
+      ) => {
+        a == c && b == d
+      },
+      (SessionInvalid(a), SessionInvalid(b)) => a == b,
+
       (
         IndexSpecialAfterStart{prior_index: a, special_index: b},
         IndexSpecialAfterStart{prior_index: c, special_index: d},
@@ -61,6 +86,7 @@ impl std::cmp::PartialEq for EdError {
       (IndexUnfinished(x),IndexUnfinished(y)) => x == y,
 
       (CommandUndefined(x),CommandUndefined(y)) => x == y,
+      (CommandNewline(x),CommandNewline(y)) => x == y,
       (ArgumentListEscapedEnd(x),ArgumentListEscapedEnd(y)) => x == y,
       (
         ArgumentsWrongNr{expected: a, received: b},
@@ -70,10 +96,19 @@ impl std::cmp::PartialEq for EdError {
       },
       (ScrollNotInt(x),ScrollNotInt(y)) => x == y,
       (UndoStepsNotInt(x),UndoStepsNotInt(y)) => x == y,
+      (UndoDurationInvalid(x),UndoDurationInvalid(y)) => x == y,
       (ReflowNotInt{error: a, text: b},ReflowNotInt{error: c, text: d}) => {
         a == c && b == d
       },
       (MacroUndefined(x),MacroUndefined(y)) => x == y,
+      (PluginUndefined(x),PluginUndefined(y)) => x == y,
+      (OptionUndefined(x),OptionUndefined(y)) => x == y,
+      (
+        OptionValueInvalid{name: a, value: b, expected: c},
+        OptionValueInvalid{name: d, value: e, expected: f},
+      ) => {
+        a == d && b == e && c == f
+      },
 
       (FlagDuplicate(x),FlagDuplicate(y)) => x == y,
       (FlagUndefined(x),FlagUndefined(y)) => x == y,
@@ -0,0 +1,821 @@
+//! Module for history snapshotting and management.
+
+use crate::{EdError, Result};
+use std::fmt::Debug;
+use crate::sync::{Shared, MaybeSend};
+use std::cell::Cell;
+use std::time::SystemTime;
+
+#[cfg(feature = "serde")]
+mod serialize;
+
+/// A special type of Clone for [`History`]
+///
+/// Needed because [`History`] requires a Clone that re-uses as much memory as
+/// possible, while normal Clone is usually expected to create unrelated copies
+/// of data.
+pub trait Snapshot{
+  /// Create a memory efficient copy of self
+  ///
+  /// Beware, mutation of the created copy (if even possible) may modify the
+  /// original.
+  fn create_snapshot(&self) -> Self;
+  /// Estimate how many bytes of memory self uses that aren't shared with base
+  ///
+  /// Used by [`History`] to enforce [`History::max_memory`], where base is the
+  /// snapshot self was created from. Only needs to be a rough estimate. The
+  /// default implementation just gives the size of Self.
+  fn estimate_unshared_memory(&self, _base: &Self) -> usize where Self: Sized {
+    std::mem::size_of::<Self>()
+  }
+  /// Compare only the content of self and other, as it would be saved
+  ///
+  /// Used by [`History::saved`] to detect that the viewed snapshot holds the
+  /// same content as the saved one. The default implementation uses PartialEq.
+  fn content_eq(&self, other: &Self) -> bool where Self: PartialEq + Sized {
+    self == other
+  }
+}
+
+/// Source of the time at which snapshots are created
+///
+/// Exists so the time can be controlled, mainly for testing.
+pub trait Clock: Debug + MaybeSend {
+  fn now(&self) -> SystemTime;
+}
+/// The default [`Clock`], which gives the current system time
+#[derive(Debug)]
+pub struct SystemClock;
+impl Clock for SystemClock {
+  fn now(&self) -> SystemTime {
+    SystemTime::now()
+  }
+}
+
+// Data History tracks for each snapshot, beside the snapshot itself
+#[derive(Clone, Debug)]
+struct SnapshotMeta {
+  // None only for the first snapshot
+  parent: Option<usize>,
+  // When the snapshot was created
+  time: SystemTime,
+  // Name given by the user, unique within history
+  checkpoint: Option<String>,
+  // Cached result of estimate_unshared_memory against parent. Never set for
+  // the last snapshot, since it may still be modified.
+  memory: Cell<Option<usize>>,
+}
+impl SnapshotMeta {
+  fn new(parent: Option<usize>, time: SystemTime) -> Self {
+    Self{ parent, time, checkpoint: None, memory: Cell::new(None) }
+  }
+}
+
+/// How [`History`] handles changes made while viewing a past snapshot
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum UndoStyle {
+  /// Add a snapshot reverting to the viewed state at the end of history, then
+  /// make the change after it. Keeps history linear.
+  ///
+  /// Inspired by
+  /// [this reasoning.](https://github.com/zaboople/klonk/blob/master/TheGURQ.md)
+  #[default]
+  Revert,
+  /// Make the change as a new branch from the viewed snapshot, creating a tree
+  /// of snapshots where every snapshot's parent is the state it was changed
+  /// from.
+  Tree,
+}
+
+/// A history abstraction over generic objects used by add-ed.
+///
+/// Handles snapshotting and moving over the history of snapshots. By default
+/// uses a revert style of undo, see [`UndoStyle`] for the alternatives.
+///
+/// Every snapshot knows its parent, the snapshot it was created from. With the
+/// revert style that is always the preceding snapshot, with the tree style
+/// changing from a past snapshot creates a new branch. Navigate with
+/// [`History::undo`], [`History::redo`] and [`History::switch_branch`], which
+/// follow these links.
+///
+/// Automatically manages snapshot creation upon mutable access to the current
+/// point in history. Further allows pausing snapshot creation via
+/// [`History.dont_snapshot`] as well as manual snapshot creation via
+/// [`History.snapshot`] (for use during script/macro execution, to make each
+/// snapshot correspond to a user action).
+///
+/// With the `serde` feature `History<Buffer>` implements Serialize and
+/// Deserialize, storing data shared between snapshots only once.
+#[derive(Clone, Debug)]
+pub struct History<T> where
+  T: Default + Debug + Snapshot + PartialEq,
+{
+  snapshots: Vec<(String, T)>,
+  // Same length as snapshots
+  meta: Vec<SnapshotMeta>,
+  viewed_i: usize,
+  saved_i: Option<usize>,
+  dropped: usize,
+  /// How changes to a past snapshot are handled, see [`UndoStyle`].
+  ///
+  /// Can be changed at any time, existing history is kept as is.
+  pub undo_style: UndoStyle,
+  /// Max number of snapshots to keep, if any.
+  ///
+  /// When a snapshot is created beyond this limit the oldest snapshots are
+  /// dropped, see [`History::prune`].
+  pub max_snapshots: Option<usize>,
+  /// Max estimated memory use in bytes of all snapshots, if any.
+  ///
+  /// When a snapshot is created and the estimate is above this limit the
+  /// oldest snapshots are dropped, see [`History::prune`]. Estimated using
+  /// [`Snapshot::estimate_unshared_memory`].
+  pub max_memory: Option<usize>,
+  /// The clock used to timestamp snapshots as they are created.
+  ///
+  /// Defaults to [`SystemClock`]. Replace it to control time during tests, or
+  /// give it to [`History::with_clock`] to also timestamp the first snapshot.
+  pub clock: Shared<dyn Clock>,
+  /// If true all calls to [`History::snapshot`] are ignored (including the
+  /// automatic call upon running `.current_mut()`).
+  ///
+  /// Intended for macro execution, when it would be confusing to create
+  /// multiple snapshots for what the user sees as a single action.
+  ///
+  /// (If a point in history is viewed a snapshot reverting to that point in
+  /// history will be created before mutable access no matter if this variable
+  /// is set to true.)
+  pub dont_snapshot: bool,
+}
+impl<T> Default for History<T> where
+  T: Default + Debug + Snapshot + PartialEq,
+{
+  fn default() -> Self { Self::new() }
+}
+impl <T> History<T> where
+  T: Default + Debug + Snapshot + PartialEq,
+{
+  /// Create new [`History`] instance
+  ///
+  /// - Only an empty present state exists.
+  /// - Considered saved at initial empty state.
+  pub fn new() -> Self {
+    Self::with_clock(Shared::new(SystemClock))
+  }
+  /// Create new [`History`] instance using the given [`Clock`]
+  ///
+  /// As [`History::new`], but the initial snapshot is also timestamped by the
+  /// given clock. (Replacing [`History::clock`] later doesn't change when the
+  /// initial snapshot is considered created.)
+  pub fn with_clock(clock: Shared<dyn Clock>) -> Self {
+    Self{
+      snapshots: vec![("Before reading in a file (empty)".to_owned(), T::default())],
+      meta: vec![SnapshotMeta::new(None, clock.now())],
+      viewed_i: 0,
+      saved_i: Some(0),
+      dropped: 0,
+      undo_style: UndoStyle::default(),
+      max_snapshots: None,
+      max_memory: None,
+      clock,
+      dont_snapshot: false,
+    }
+  }
+
+  /// Get if the buffer is saved
+  ///
+  /// It aims to be true when the viewed buffer matches the data last saved.
+  /// If it is uncertain or difficult to track it will return false.
+  ///
+  /// Compares the content of the viewed and saved snapshots (see
+  /// [`Snapshot::content_eq`]), so manually reverting changes or reaching the
+  /// same content by another route through history also counts as saved.
+  pub fn saved(&self) -> bool {
+    match self.saved_i {
+      Some(i) => i == self.viewed_i ||
+        self.snapshots[i].1.content_eq(self.current()),
+      None => false,
+    }
+  }
+  /// Mark the currently viewed buffer state as saved
+  ///
+  /// If `dont_snapshot` is set this instead behaves as
+  /// `set_unsaved()`, as we cannot be sure a snapshot will exist
+  /// corresponding to the state in which the buffer was saved.
+  pub fn set_saved(&mut self) {
+    if !self.dont_snapshot {
+      self.saved_i = Some(self.viewed_i);
+    } else {
+      self.saved_i = None;
+    }
+  }
+  /// Declare that no known buffer state is saved
+  ///
+  /// Mainly useful for testing, but may be relevant when knowing that file
+  /// was changed on disk.
+  pub fn set_unsaved(&mut self) {
+    self.saved_i = None;
+  }
+
+  /// Get an immutable view into the currently viewed point in history
+  pub fn current(&self) -> &T {
+    &self.snapshots[self.viewed_i].1
+  }
+  /// Get a mutable state to make new changes
+  ///
+  /// - Takes a string describing what is causing this new snapshot. (Should
+  ///   generally be the full command, if not be as clear as possible.)
+  /// - If currently viewing history, will create a revert snapshot at end of
+  ///   history (or with [`UndoStyle::Tree`] a new branch).
+  /// - Unless self.dont_snapshot, will create a new snapshot tagged with the
+  ///   given cause for modification.
+  /// - Returns mutable access to the snapshot at the end of history.
+  pub fn current_mut(&mut self,
+    modification_cause: String,
+  ) -> &mut T {
+    self.snapshot(modification_cause);
+    &mut self.snapshots[self.viewed_i].1
+  }
+
+  fn internal_create_snapshot(&mut self, label: String, parent: usize) {
+    // Push the current index to end of history with label
+    // (reverts if in history, snapshots if at end of history)
+    self.snapshots.push((label, self.snapshots[self.viewed_i].1.create_snapshot()));
+    self.meta.push(SnapshotMeta::new(Some(parent), self.clock.now()));
+    // Move to end of history
+    self.viewed_i = self.snapshots.len() - 1;
+  }
+  /// Manually add a snapshot
+  ///
+  /// Takes a String as an argument that should describe what causes the
+  /// change seen in the created snapshot relative to the preceding snapshot.
+  ///
+  /// The only case this should be needed is before setting `dont_snapshot` for
+  /// a script execution. If `dont_snapshot` isn't set snapshots are created
+  /// automatically whenever [`Self.current_mut`] is executed.
+  pub fn snapshot(&mut self,
+    modification_cause: String,
+  ) {
+    let in_past = self.viewed_i < self.snapshots.len() - 1;
+    match self.undo_style {
+      UndoStyle::Revert => {
+        // If we are in the past, create a revert snapshot
+        // This is needed even if snapshots are disabled, to not change history
+        if in_past {
+          self.internal_create_snapshot(
+            format!(
+              "u{}",
+              self.snapshots.len().saturating_sub(self.viewed_i + 1),
+            ),
+            self.snapshots.len() - 1,
+          );
+        }
+        // If snapshots aren't disabled, create one
+        if !self.dont_snapshot {
+          self.internal_create_snapshot(modification_cause, self.viewed_i);
+        }
+      },
+      UndoStyle::Tree => {
+        // Branch off from the viewed snapshot. Also needed if snapshots are
+        // disabled while in the past, to not change history.
+        if in_past || !self.dont_snapshot {
+          self.internal_create_snapshot(modification_cause, self.viewed_i);
+        }
+      },
+    }
+    self.prune();
+  }
+
+  /// Checks if the last snapshot in history is identical to its parent. If yes
+  /// deletes it.
+  ///
+  /// Intended for use by macros and scripts, as they have to add a snapshot
+  /// even for non-mutating scripts since they don't know if a script will
+  /// modify the buffer. By running this after macro execution the snapshot will
+  /// be deleted if extraneous and left if relevant.
+  pub fn dedup_present(&mut self) {
+    let last = self.snapshots.len() - 1;
+    if let Some(parent) = self.meta[last].parent {
+      if self.snapshots[parent].1 == self.snapshots[last].1 {
+        self.snapshots.pop();
+        // The parent holds the same state, so a checkpoint can move there
+        if let Some(name) = self.meta.pop().and_then(|m| m.checkpoint) {
+          self.meta[parent].checkpoint = Some(name);
+        }
+        // The new last snapshot mustn't have cached memory use
+        self.meta[last - 1].memory.set(None);
+        self.viewed_i = parent;
+      }
+    }
+  }
+
+  // Used by Ed::transaction to remove all snapshots from index len onwards,
+  // viewing the given index with the given state
+  //
+  // The state replaces the viewed snapshot, since with dont_snapshot set the
+  // snapshot may have been modified in place.
+  pub(crate) fn restore(&mut self, len: usize, viewed_i: usize, state: T) {
+    self.snapshots.truncate(len);
+    self.meta.truncate(len);
+    // The last snapshot mustn't have cached memory use
+    self.meta[len - 1].memory.set(None);
+    self.viewed_i = viewed_i;
+    self.snapshots[viewed_i].1 = state;
+    if self.saved_i.is_some_and(|i| i >= len) { self.saved_i = None; }
+  }
+
+  /// Accessor to view the full list of snapshots
+  ///
+  /// - Entries are in order of creation, the first operation is first in the
+  ///   list.
+  /// - The string beside the snapshot describes what caused the state in the
+  ///   snapshot (relative to the preceeding snapshot).
+  pub fn snapshots(&self) -> &Vec<(String, T)> {
+    &self.snapshots
+  }
+  /// Shorthand for `.snapshots().len()`
+  pub fn len(&self) -> usize {
+    self.snapshots.len()
+  }
+  /// Always false, since history always holds at least one snapshot
+  pub fn is_empty(&self) -> bool {
+    self.snapshots.is_empty()
+  }
+  /// Getter for what index was last saved
+  ///
+  /// Returns None if no index is believed to be saved.
+  ///
+  /// Intended to be used to enrich when listing snapshots by marking the one
+  /// considered saved.
+  pub fn saved_i(&self) -> Option<usize> {
+    self.saved_i
+  }
+  /// Getter for currently viewed snapshot index
+  pub fn viewed_i(&self) -> usize {
+    self.viewed_i
+  }
+  /// Get the snapshot the snapshot at the given index was created from
+  ///
+  /// Returns None for the first snapshot and for indices without a snapshot.
+  pub fn parent(&self, index: usize) -> Option<usize> {
+    self.meta.get(index).and_then(|m| m.parent)
+  }
+  /// Get the time at which the snapshot at the given index was created
+  ///
+  /// Returns None for indices without a snapshot.
+  pub fn time(&self, index: usize) -> Option<SystemTime> {
+    self.meta.get(index).map(|m| m.time)
+  }
+  /// Get the index of the snapshot which was newest at the given time
+  ///
+  /// That is the last created snapshot created no later than the given time,
+  /// or None if all snapshots were created after it.
+  pub fn index_at(&self, time: SystemTime) -> Option<usize> {
+    self.meta.iter().rposition(|m| m.time <= time)
+  }
+  /// Name the viewed snapshot as a checkpoint, to easily return to it later
+  ///
+  /// Names are unique, so if another snapshot has the name it is moved here.
+  /// A snapshot only holds one name, so any previous name is replaced.
+  ///
+  /// Checkpoints stay with the snapshot they were given to. Editing while
+  /// viewing the past creates new snapshots (revert or branch), the
+  /// checkpointed snapshot and its name is left unchanged. Pruning never drops
+  /// checkpointed snapshots.
+  pub fn set_checkpoint(&mut self, name: String) {
+    for m in self.meta.iter_mut() {
+      if m.checkpoint.as_ref() == Some(&name) { m.checkpoint = None; }
+    }
+    self.meta[self.viewed_i].checkpoint = Some(name);
+  }
+  /// Get the checkpoint name of the snapshot at the given index, if any
+  pub fn checkpoint(&self, index: usize) -> Option<&str> {
+    self.meta.get(index).and_then(|m| m.checkpoint.as_deref())
+  }
+  /// Get the index of the snapshot with the given checkpoint name, if any
+  pub fn find_checkpoint(&self, name: &str) -> Option<usize> {
+    self.meta.iter().position(|m| m.checkpoint.as_deref() == Some(name))
+  }
+  /// Get all checkpoints, as index and name in order of index
+  pub fn checkpoints(&self) -> Vec<(usize, &str)> {
+    self.meta.iter().enumerate()
+      .filter_map(|(i, m)| m.checkpoint.as_deref().map(|c| (i, c)))
+      .collect()
+  }
+  /// Get the snapshots created from the snapshot at the given index
+  ///
+  /// Ordered oldest first. More than one means history branches there.
+  pub fn children(&self, index: usize) -> Vec<usize> {
+    self.meta.iter().enumerate()
+      .filter(|(_, m)| m.parent == Some(index))
+      .map(|(i, _)| i)
+      .collect()
+  }
+  /// Setter for currently viewed snapshot index
+  ///
+  /// Returns the modification cause for the now viewed index.
+  ///
+  /// Will return error if given index doesn't hold a snapshot (aka. is too
+  /// big).
+  pub fn set_viewed_i(&mut self, new_i: usize) -> Result<&str> {
+    if new_i < self.len() {
+      self.viewed_i = new_i;
+      Ok(&self.snapshots[self.viewed_i].0)
+    }
+    else {
+      Err(EdError::UndoIndexTooBig{
+        index: new_i,
+        history_len: self.len(),
+        relative_redo_limit: self.len() - self.viewed_i - 1,
+      })
+    }
+  }
+  /// Move the given number of steps towards the first snapshot, following
+  /// parents
+  ///
+  /// Returns the modification cause for the now viewed index.
+  ///
+  /// Will return error if there are fewer snapshots before the viewed one.
+  pub fn undo(&mut self, steps: usize) -> Result<&str> {
+    let mut new_i = self.viewed_i;
+    for taken in 0 .. steps {
+      new_i = self.parent(new_i)
+        .ok_or(EdError::UndoIndexNegative{relative_undo_limit: taken})?
+      ;
+    }
+    self.set_viewed_i(new_i)
+  }
+  /// Move the given number of steps away from the first snapshot, following
+  /// the newest child at every step
+  ///
+  /// Returns the modification cause for the now viewed index.
+  ///
+  /// Will return error if there are fewer snapshots after the viewed one.
+  pub fn redo(&mut self, steps: usize) -> Result<&str> {
+    let mut new_i = self.viewed_i;
+    for taken in 0 .. steps {
+      new_i = match self.children(new_i).last() {
+        Some(i) => *i,
+        None => return Err(EdError::UndoIndexTooBig{
+          index: self.viewed_i + steps,
+          history_len: self.len(),
+          relative_redo_limit: taken,
+        }),
+      };
+    }
+    self.set_viewed_i(new_i)
+  }
+  /// Move to a sibling branch of the viewed snapshot
+  ///
+  /// Siblings are the snapshots with the same parent. Positive steps move to
+  /// newer siblings, negative to older.
+  ///
+  /// Returns the modification cause for the now viewed index.
+  ///
+  /// Will return error if there aren't enough siblings in the given direction.
+  pub fn switch_branch(&mut self, steps: isize) -> Result<&str> {
+    let siblings = match self.parent(self.viewed_i) {
+      Some(p) => self.children(p),
+      None => vec![self.viewed_i],
+    };
+    // Safe to unwrap, since the viewed snapshot is always among its siblings
+    let pos = siblings.iter().position(|i| *i == self.viewed_i).unwrap();
+    match pos.checked_add_signed(steps).and_then(|x| siblings.get(x)) {
+      Some(i) => self.set_viewed_i(*i),
+      None => Err(EdError::UndoBranchNonexistent{
+        older_branches: pos,
+        newer_branches: siblings.len() - pos - 1,
+      }),
+    }
+  }
+  /// Estimate the memory used by all snapshots, in bytes
+  ///
+  /// Sums [`Snapshot::estimate_unshared_memory`] for every snapshot against
+  /// the snapshot it was created from, so shared data is only counted once.
+  pub fn estimated_memory(&self) -> usize {
+    let last = self.len() - 1;
+    let empty = T::default();
+    self.meta.iter().enumerate().map(|(i, m)| {
+      if let Some(x) = m.memory.get() { return x; }
+      let base = m.parent.map(|p| &self.snapshots[p].1).unwrap_or(&empty);
+      let x = self.snapshots[i].1.estimate_unshared_memory(base);
+      if i != last { m.memory.set(Some(x)); }
+      x
+    }).sum()
+  }
+  /// Get the total number of snapshots dropped by pruning
+  pub fn dropped(&self) -> usize {
+    self.dropped
+  }
+  /// Drop the oldest snapshots until within [`History::max_snapshots`] and
+  /// [`History::max_memory`]
+  ///
+  /// Run automatically whenever a snapshot is created, so only needed to apply
+  /// changed limits right away.
+  ///
+  /// Only drops snapshots from the oldest linear history, that is from the
+  /// first snapshot up to where history first branches. Never drops the viewed,
+  /// saved or last snapshot, nor checkpoints. Dropping a snapshot merges its
+  /// changes into the snapshot created from it, keeping `viewed_i` and
+  /// `saved_i` pointing at the same states.
+  ///
+  /// Returns how many snapshots were dropped.
+  pub fn prune(&mut self) -> usize {
+    let mut dropped = 0;
+    loop {
+      let over_limit =
+        self.max_snapshots.is_some_and(|max| self.len() > max) ||
+        self.max_memory.is_some_and(|max| self.estimated_memory() > max)
+      ;
+      if !over_limit { break; }
+      match self.prune_candidate() {
+        Some(i) => self.remove(i),
+        None => break,
+      }
+      dropped += 1;
+    }
+    self.dropped += dropped;
+    dropped
+  }
+  // Find the oldest snapshot which may be dropped, walking from the first
+  // snapshot (always index 0, since parents precede their children) until
+  // history branches or ends
+  fn prune_candidate(&self) -> Option<usize> {
+    let mut i = 0;
+    loop {
+      let children = self.children(i);
+      if children.len() != 1 { return None; }
+      if
+        i != self.viewed_i &&
+        Some(i) != self.saved_i &&
+        self.meta[i].checkpoint.is_none()
+      {
+        return Some(i);
+      }
+      i = children[0];
+    }
+  }
+  // Remove a snapshot with exactly one child, making its parent the parent of
+  // that child
+  fn remove(&mut self, index: usize) {
+    let parent = self.meta[index].parent;
+    self.snapshots.remove(index);
+    self.meta.remove(index);
+    let shift = |i: usize| if i > index { i - 1 } else { i };
+    for m in self.meta.iter_mut() {
+      if m.parent == Some(index) {
+        m.parent = parent;
+        m.memory.set(None);
+      }
+      m.parent = m.parent.map(shift);
+    }
+    self.viewed_i = shift(self.viewed_i);
+    self.saved_i = self.saved_i.map(shift);
+  }
+  /// Get all snapshot indices as a tree, in depth first order
+  ///
+  /// Each index is given with its branch depth, which is how many times the
+  /// path from the first snapshot leaves the oldest child. (Always 0 if
+  /// history doesn't branch.) Newer branches are given before the older branch
+  /// they diverge from is continued, so the oldest line of history is at the
+  /// bottom.
+  pub fn tree(&self) -> Vec<(usize, usize)> {
+    let mut children = vec![Vec::new(); self.len()];
+    for (i, m) in self.meta.iter().enumerate() {
+      if let Some(p) = m.parent { children[p].push(i); }
+    }
+    let mut out = Vec::with_capacity(self.len());
+    // Walk with an explicit stack, since history may be too deep to recurse
+    let mut stack = vec![(0, 0)];
+    while let Some((i, depth)) = stack.pop() {
+      out.push((i, depth));
+      // Push so the oldest child is popped last, after all newer branches
+      if let Some((oldest, newer)) = children[i].split_first() {
+        stack.push((*oldest, depth));
+        for child in newer {
+          stack.push((*child, depth + 1));
+        }
+      }
+    }
+    out
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  // Minimal snapshottable state, to test History in isolation
+  #[derive(Default, Debug, PartialEq)]
+  struct State(usize);
+  impl Snapshot for State {
+    fn create_snapshot(&self) -> Self { State(self.0) }
+  }
+
+  // Creates history 0 -> 1 -> 2, then branches 1 -> 3 -> 4 and 1 -> 5
+  fn branched(style: UndoStyle) -> History<State> {
+    let mut history = History::<State>::new();
+    history.undo_style = style;
+    for (i, undo) in [(1, 0), (2, 0), (3, 1), (4, 0), (5, 2)] {
+      if undo > 0 { history.undo(undo).unwrap(); }
+      history.current_mut(format!("{}", i)).0 = i;
+    }
+    history
+  }
+
+  #[test]
+  fn revert_style_stays_linear() {
+    let history = branched(UndoStyle::Revert);
+    assert_eq!(
+      history.snapshots().iter().map(|x| &x.0[..]).collect::<Vec<_>>(),
+      vec!["Before reading in a file (empty)", "1", "2", "u1", "3", "4", "u2", "5"],
+    );
+    for i in 0 .. history.len() {
+      assert_eq!(history.parent(i), i.checked_sub(1));
+    }
+  }
+
+  #[test]
+  fn tree_style_branches() {
+    let mut history = branched(UndoStyle::Tree);
+    assert_eq!(history.len(), 6, "Tree style shouldn't create revert snapshots.");
+    assert_eq!(history.children(1), vec![2, 3, 5]);
+    assert_eq!(history.viewed_i(), 5);
+    // Undo and redo follow the newest branch
+    history.undo(1).unwrap();
+    assert_eq!(history.viewed_i(), 1);
+    history.redo(1).unwrap();
+    assert_eq!(history.viewed_i(), 5);
+    assert_eq!(
+      history.redo(1),
+      Err(EdError::UndoIndexTooBig{index: 6, history_len: 6, relative_redo_limit: 0}),
+    );
+    assert_eq!(
+      history.undo(3),
+      Err(EdError::UndoIndexNegative{relative_undo_limit: 2}),
+    );
+    // Switch between the branches from snapshot 1
+    history.switch_branch(-1).unwrap();
+    assert_eq!(history.current(), &State(3));
+    history.redo(1).unwrap();
+    assert_eq!(history.current(), &State(4));
+    history.undo(1).unwrap();
+    history.switch_branch(-1).unwrap();
+    assert_eq!(history.current(), &State(2));
+    assert_eq!(
+      history.switch_branch(-1),
+      Err(EdError::UndoBranchNonexistent{older_branches: 0, newer_branches: 2}),
+    );
+    // Newest branches are listed first, oldest line of history last
+    assert_eq!(
+      history.tree(),
+      vec![(0, 0), (1, 0), (5, 1), (3, 1), (4, 1), (2, 0)],
+    );
+  }
+
+  #[test]
+  fn tree_style_dedup_returns_to_parent() {
+    let mut history = branched(UndoStyle::Tree);
+    history.undo(2).unwrap();
+    history.snapshot("noop".into());
+    assert_eq!(history.parent(history.viewed_i()), Some(0));
+    history.dedup_present();
+    assert_eq!(history.len(), 6);
+    assert_eq!(history.viewed_i(), 0);
+  }
+
+  #[test]
+  fn prune_oldest() {
+    let mut history = History::<State>::new();
+    history.max_snapshots = Some(3);
+    for i in 1 ..= 5 {
+      history.current_mut(format!("{}", i)).0 = i;
+    }
+    // The initial snapshot is saved, so it is kept
+    assert_eq!(
+      history.snapshots().iter().map(|x| &x.0[..]).collect::<Vec<_>>(),
+      vec!["Before reading in a file (empty)", "4", "5"],
+    );
+    assert_eq!(history.dropped(), 3);
+    assert_eq!(history.parent(0), None);
+    assert_eq!(history.parent(1), Some(0));
+    assert_eq!(history.viewed_i(), 2);
+    assert_eq!(history.saved_i(), Some(0), "Saved snapshot was dropped.");
+    // Default memory estimate is the size of the state, so 2 states fit
+    history.max_memory = Some(2 * std::mem::size_of::<State>());
+    assert_eq!(history.prune(), 1);
+    assert_eq!(history.current(), &State(5));
+    assert_eq!(history.snapshots()[0].1, State(0));
+    history.set_viewed_i(0).unwrap();
+    assert!(history.saved());
+  }
+
+  #[test]
+  fn checkpoints_survive_revert_and_prune() {
+    let mut history = History::<State>::new();
+    for i in 1 ..= 2 {
+      history.current_mut(format!("{}", i)).0 = i;
+    }
+    history.undo(1).unwrap();
+    history.set_checkpoint("good".into());
+    // Editing in the past creates a revert snapshot, but the name stays put
+    history.current_mut("3".into()).0 = 3;
+    assert_eq!(history.len(), 5);
+    assert_eq!(history.find_checkpoint("good"), Some(1));
+    // Naming another snapshot the same moves the name
+    history.set_checkpoint("good".into());
+    assert_eq!(history.checkpoints(), vec![(4, "good")]);
+    history.set_viewed_i(1).unwrap();
+    history.set_checkpoint("first".into());
+    assert_eq!(history.checkpoints(), vec![(1, "first"), (4, "good")]);
+    // Pruning skips the checkpoints (and the saved initial snapshot)
+    history.max_snapshots = Some(3);
+    history.prune();
+    assert_eq!(history.checkpoints(), vec![(1, "first"), (2, "good")]);
+    assert_eq!(history.snapshots()[1].1, State(1));
+  }
+
+  #[test]
+  fn saved_compares_content() {
+    let mut history = History::<State>::new();
+    history.current_mut("1".into()).0 = 1;
+    history.set_saved();
+    history.current_mut("2".into()).0 = 2;
+    assert!(!history.saved());
+    history.current_mut("3".into()).0 = 1;
+    assert!(history.saved(), "Manually reverted change wasn't saved.");
+    history.set_unsaved();
+    assert!(!history.saved());
+  }
+
+  #[test]
+  fn index_at_time() {
+    #[derive(Debug)]
+    struct FakeClock(std::sync::Mutex<SystemTime>);
+    impl Clock for FakeClock {
+      fn now(&self) -> SystemTime { *self.0.lock().unwrap() }
+    }
+    let start = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1000);
+    let clock = Shared::new(FakeClock(std::sync::Mutex::new(start)));
+    let mut history = History::<State>::with_clock(clock.clone());
+    assert_eq!(history.time(0), Some(start));
+    for i in 1 .. 4 {
+      *clock.0.lock().unwrap() = start + std::time::Duration::from_secs(i * 10);
+      history.current_mut(format!("{}", i)).0 = i as usize;
+    }
+    assert_eq!(history.time(2), Some(start + std::time::Duration::from_secs(20)));
+    assert_eq!(history.index_at(start + std::time::Duration::from_secs(25)), Some(2));
+    assert_eq!(history.index_at(start + std::time::Duration::from_secs(30)), Some(3));
+    assert_eq!(history.index_at(start + std::time::Duration::from_secs(5)), Some(0));
+    assert_eq!(history.index_at(std::time::UNIX_EPOCH), None);
+  }
+
+  #[test]
+  fn prune_keeps_viewed_saved_and_branches() {
+    let mut history = branched(UndoStyle::Tree);
+    history.set_saved();
+    history.set_viewed_i(4).unwrap();
+    history.max_snapshots = Some(3);
+    // Only 0 is dropped, since history branches at 1. Snapshots on branches,
+    // such as the leaf 2, are never dropped.
+    assert_eq!(history.prune(), 1);
+    assert_eq!(
+      history.snapshots().iter().map(|x| &x.1).collect::<Vec<_>>(),
+      vec![&State(1), &State(2), &State(3), &State(4), &State(5)],
+    );
+    assert_eq!(history.current(), &State(4));
+    assert_eq!(history.saved_i(), Some(4));
+    assert_eq!(history.children(0), vec![1, 2, 4]);
+    assert_eq!(history.parent(3), Some(2));
+  }
+
+  #[test]
+  fn prune_keeps_saved() {
+    let mut history = History::<State>::new();
+    for i in 1 ..= 3 {
+      history.current_mut(format!("{}", i)).0 = i;
+    }
+    history.undo(1).unwrap();
+    history.set_saved();
+    history.redo(1).unwrap();
+    history.max_snapshots = Some(1);
+    // Drops 0 and 1, but neither the saved nor the viewed snapshot
+    assert_eq!(history.prune(), 2);
+    assert_eq!(history.saved_i(), Some(0));
+    assert!(!history.saved());
+    history.undo(1).unwrap();
+    assert_eq!(history.current(), &State(2));
+    assert!(history.saved());
+  }
+
+  #[test]
+  fn dedup_clears_memory_cache() {
+    let mut history = History::<State>::new();
+    history.current_mut("1".into()).0 = 1;
+    history.snapshot("noop".into());
+    // Caches the estimate of all but the last snapshot
+    history.estimated_memory();
+    history.dedup_present();
+    assert!(history.meta[1].memory.get().is_none());
+  }
+}
@@ -0,0 +1,311 @@
+//! Serialization of [`History<Buffer>`], enabled by the `serde` feature.
+//!
+//! Snapshots share almost all of their data, so the serialized format is
+//! built around tables of distinct data which the snapshots index into:
+//! - `texts` holds every distinct [`LineText`] once.
+//! - `lines` holds every distinct [`Line`] (as identified by its tag being
+//!   shared through history), so tags keep being shared after deserializing.
+//! - `chunks` holds every distinct chunk of lines in the [`Buffer`]s, so
+//!   unmodified parts of the buffer are only stored once.
+//!
+//! This means that the serialized size grows with the size of the changes
+//! made, rather than with the size of the buffer times the number of
+//! snapshots.
+
+use std::collections::HashMap;
+use crate::sync::Shared;
+use std::time::SystemTime;
+
+use serde::{Serialize, Deserialize};
+
+use crate::{Buffer, Line, LineText, PubLine};
+use super::{History, SnapshotMeta, SystemClock, UndoStyle};
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SerializedLine {
+  tag: char,
+  text: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SerializedSnapshot {
+  label: String,
+  chunks: Vec<usize>,
+  // Defaults to the unix epoch if not given
+  #[serde(default)]
+  time: Option<SystemTime>,
+  #[serde(default)]
+  checkpoint: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SerializedHistory {
+  texts: Vec<String>,
+  lines: Vec<SerializedLine>,
+  chunks: Vec<Vec<usize>>,
+  snapshots: Vec<SerializedSnapshot>,
+  // Defaults to linear history if not given
+  #[serde(default)]
+  parents: Vec<Option<usize>>,
+  viewed_i: usize,
+  saved_i: Option<usize>,
+}
+
+// Helper to give every distinct object an index in its table, using the
+// address of its shared data to identify it.
+struct Table<K, V> {
+  indices: HashMap<K, usize>,
+  entries: Vec<V>,
+}
+impl<K: std::hash::Hash + Eq, V> Table<K, V> {
+  fn new() -> Self {
+    Self{ indices: HashMap::new(), entries: Vec::new() }
+  }
+  fn index_of(&mut self, key: K, create: impl FnOnce(&mut Self) -> V) -> usize {
+    if let Some(i) = self.indices.get(&key) { return *i; }
+    let value = create(self);
+    self.entries.push(value);
+    self.indices.insert(key, self.entries.len() - 1);
+    self.entries.len() - 1
+  }
+}
+
+impl From<&History<Buffer>> for SerializedHistory {
+  fn from(history: &History<Buffer>) -> Self {
+    let mut texts = Table::new();
+    let mut lines = Table::new();
+    let mut chunks = Table::new();
+    let mut snapshots = Vec::new();
+    for ((label, buffer), meta) in history.snapshots.iter().zip(&history.meta) {
+      let snapshot_chunks = buffer.chunks().iter().map(|chunk| {
+        chunks.index_of(Shared::as_ptr(chunk), |_| {
+          chunk.iter().map(|line: &Line| {
+            lines.index_of(line.id(), |_| SerializedLine{
+              tag: line.tag(),
+              text: texts.index_of(line.text.id(), |_| line.text.to_string()),
+            })
+          }).collect::<Vec<usize>>()
+        })
+      }).collect();
+      snapshots.push(SerializedSnapshot{
+        label: label.clone(),
+        chunks: snapshot_chunks,
+        time: Some(meta.time),
+        checkpoint: meta.checkpoint.clone(),
+      });
+    }
+    Self{
+      texts: texts.entries,
+      lines: lines.entries,
+      chunks: chunks.entries,
+      snapshots,
+      parents: history.meta.iter().map(|m| m.parent).collect(),
+      viewed_i: history.viewed_i,
+      saved_i: history.saved_i,
+    }
+  }
+}
+
+impl TryFrom<SerializedHistory> for History<Buffer> {
+  type Error = String;
+  fn try_from(mut data: SerializedHistory) -> Result<Self, Self::Error> {
+    fn get<T>(table: &[T], index: usize, name: &str) -> Result<T, String>
+      where T: crate::history::Snapshot
+    {
+      table.get(index)
+        .map(|x| x.create_snapshot())
+        .ok_or_else(|| format!("{} index {} out of bounds", name, index))
+    }
+    let texts = data.texts.into_iter()
+      .map(|text| LineText::new(text).map_err(|e| e.to_string()))
+      .collect::<Result<Vec<_>, _>>()?
+    ;
+    let lines = data.lines.iter()
+      .map(|line| Ok((&PubLine{
+        tag: line.tag,
+        text: texts.get(line.text)
+          .ok_or_else(|| format!("text index {} out of bounds", line.text))?
+          .clone(),
+      }).into()))
+      .collect::<Result<Vec<Line>, String>>()?
+    ;
+    let chunks = data.chunks.iter()
+      .map(|chunk| chunk.iter()
+        .map(|i| get(&lines, *i, "line"))
+        .collect::<Result<Vec<_>, _>>()
+        .map(Shared::new)
+      )
+      .collect::<Result<Vec<_>, _>>()?
+    ;
+    let mut meta: Vec<SnapshotMeta> = Vec::with_capacity(data.snapshots.len());
+    for snapshot in data.snapshots.iter_mut() {
+      let checkpoint = snapshot.checkpoint.take();
+      if checkpoint.is_some() && meta.iter().any(|m| m.checkpoint == checkpoint) {
+        return Err(format!("duplicate checkpoint {:?}", checkpoint));
+      }
+      meta.push(SnapshotMeta{
+        checkpoint,
+        ..SnapshotMeta::new(None, snapshot.time.unwrap_or(SystemTime::UNIX_EPOCH))
+      });
+    }
+    let snapshots = data.snapshots.into_iter()
+      .map(|snapshot| Ok((
+        snapshot.label,
+        Buffer::from_chunks(snapshot.chunks.iter()
+          .map(|i| chunks.get(*i).cloned()
+            .ok_or_else(|| format!("chunk index {} out of bounds", i))
+          )
+          .collect::<Result<Vec<_>, String>>()?
+        ),
+      )))
+      .collect::<Result<Vec<_>, String>>()?
+    ;
+    let parents = if data.parents.is_empty() {
+      (0 .. snapshots.len()).map(|i| i.checked_sub(1)).collect()
+    } else {
+      data.parents
+    };
+    if parents.len() != snapshots.len() {
+      return Err(format!(
+        "got {} parents for {} snapshots", parents.len(), snapshots.len()
+      ));
+    }
+    // Only the first snapshot may lack a parent and parents must be older than
+    // their children, which guarantees that history forms a single tree
+    for (i, parent) in parents.into_iter().enumerate() {
+      let valid = match parent {
+        None => i == 0,
+        Some(p) => p < i,
+      };
+      if !valid {
+        return Err(format!("invalid parent {:?} for snapshot {}", parent, i));
+      }
+      meta[i].parent = parent;
+    }
+    if data.viewed_i >= snapshots.len() {
+      return Err(format!("viewed index {} out of bounds", data.viewed_i));
+    }
+    if data.saved_i.is_some_and(|i| i >= snapshots.len()) {
+      return Err(format!("saved index {:?} out of bounds", data.saved_i));
+    }
+    Ok(Self{
+      snapshots,
+      meta,
+      viewed_i: data.viewed_i,
+      saved_i: data.saved_i,
+      dropped: 0,
+      undo_style: UndoStyle::default(),
+      max_snapshots: None,
+      max_memory: None,
+      clock: Shared::new(SystemClock),
+      dont_snapshot: false,
+    })
+  }
+}
+
+impl History<Buffer> {
+  // Copy all snapshots into a new history, which doesn't share line tags with
+  // this one (as they are shared throughout a history, not between histories)
+  pub(crate) fn detached_copy(&self) -> Self {
+    // Cannot fail, since the data is created from a valid history
+    let mut copy = Self::try_from(SerializedHistory::from(self))
+      .unwrap_or_default()
+    ;
+    copy.dropped = self.dropped;
+    copy
+  }
+}
+
+/// Serializes all snapshots, their labels, parents, creation times and
+/// checkpoint names and which are viewed and saved.
+///
+/// `dont_snapshot`, `undo_style`, `max_snapshots`, `max_memory` and `clock`
+/// are configuration and thus not serialized.
+/// Neither is the internal state used by `g` and similar commands.
+impl Serialize for History<Buffer> {
+  fn serialize<S: serde::Serializer>(&self, serializer: S)
+    -> Result<S::Ok, S::Error>
+  {
+    SerializedHistory::from(self).serialize(serializer)
+  }
+}
+/// Validates all the data while deserializing, so any deserialized
+/// [`History`] is valid to use.
+impl<'de> Deserialize<'de> for History<Buffer> {
+  fn deserialize<D: serde::Deserializer<'de>>(deserializer: D)
+    -> Result<Self, D::Error>
+  {
+    SerializedHistory::deserialize(deserializer)?
+      .try_into()
+      .map_err(serde::de::Error::custom)
+  }
+}
+
+#[cfg(all(test, feature = "history_file"))]
+mod test {
+  use super::*;
+
+  fn history_with_edits() -> History<Buffer> {
+    let mut history = History::<Buffer>::new();
+    let buffer = history.current_mut("e file".into());
+    for line in ["a\n", "b\n", "c\n"] {
+      buffer.push(line.try_into().unwrap());
+    }
+    history.set_saved();
+    history.current()[0].set_tag('x');
+    let buffer = history.current_mut("2d".into());
+    let mut tail = buffer.split_off(2);
+    let _ = buffer.split_off(1);
+    buffer.append(&mut tail);
+    history.set_viewed_i(1).unwrap();
+    history.set_checkpoint("good".into());
+    history
+  }
+
+  #[test]
+  fn roundtrip() {
+    let history = history_with_edits();
+    let serialized = serde_json::to_string(&history).unwrap();
+    let deserialized: History<Buffer> = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(
+      deserialized.snapshots(),
+      history.snapshots(),
+      "Snapshots after deserializing (left) didn't match original (right)."
+    );
+    assert_eq!(deserialized.viewed_i(), history.viewed_i());
+    assert_eq!(deserialized.time(2), history.time(2));
+    assert_eq!(deserialized.checkpoints(), vec![(1, "good")]);
+    assert_eq!(deserialized.saved_i(), history.saved_i());
+    // Setting a tag should still affect the line throughout history
+    deserialized.snapshots()[1].1[0].set_tag('y');
+    assert_eq!(deserialized.snapshots()[2].1[0].tag(), 'y');
+  }
+
+  #[test]
+  fn tree_roundtrip() {
+    let mut history = history_with_edits();
+    history.undo_style = UndoStyle::Tree;
+    history.current_mut("a".into()).push("d\n".try_into().unwrap());
+    let serialized = serde_json::to_string(&history).unwrap();
+    let deserialized: History<Buffer> = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(deserialized.tree(), history.tree());
+  }
+
+  #[test]
+  fn shared_data_stored_once() {
+    let history = history_with_edits();
+    let serialized = SerializedHistory::from(&history);
+    assert_eq!(serialized.texts.len(), 3, "Shared texts were duplicated.");
+    assert_eq!(serialized.lines.len(), 3, "Shared lines were duplicated.");
+  }
+
+  #[test]
+  fn invalid_index_rejected() {
+    let mut serialized = SerializedHistory::from(&history_with_edits());
+    serialized.snapshots[0].chunks.push(17);
+    assert!(History::<Buffer>::try_from(serialized).is_err());
+  }
+}
@@ -2,6 +2,7 @@ use crate::{
   io::{
     IO,
     WriteType,
+    ShellContext,
   },
   ui::UILock,
   buffer::iters::LinesIter,
@@ -20,18 +21,21 @@ impl IO for DummyIO {
   fn run_command(&mut self,
     _ui: &mut UILock,
     _command: String,
+    _context: &ShellContext,
   ) -> Result<()> {
     unimplemented!()
   }
   fn run_read_command(&mut self,
     _ui: &mut UILock,
     _command: String,
+    _context: &ShellContext,
   ) -> Result<String> {
     unimplemented!()
   }
   fn run_write_command(&mut self,
     _ui: &mut UILock,
     _command: String,
+    _context: &ShellContext,
     _input: LinesIter,
   ) -> Result<usize> {
     unimplemented!()
@@ -39,6 +43,7 @@ impl IO for DummyIO {
   fn run_transform_command(&mut self,
     _ui: &mut UILock,
     _command: String,
+    _context: &ShellContext,
     _input: LinesIter,
   ) -> Result<String> {
     unimplemented!()
@@ -2,6 +2,7 @@ use crate::{
   io::{
     IO,
     WriteType,
+    ShellContext,
   },
   ui::UILock,
   buffer::iters::LinesIter,
@@ -49,6 +50,7 @@ impl IO for FakeIO {
   fn run_command(&mut self,
     _ui: &mut UILock,
     command: String,
+    _context: &ShellContext,
   ) -> Result<()> {
     if self.fake_shell.contains_key(
       &ShellCommand{command, input: String::new()}
@@ -64,6 +66,7 @@ impl IO for FakeIO {
   fn run_read_command(&mut self,
     _ui: &mut UILock,
     command: String,
+    _context: &ShellContext,
   ) -> Result<String> {
     match self.fake_shell.get(
       &ShellCommand{command, input: String::new()}
@@ -78,6 +81,7 @@ impl IO for FakeIO {
   fn run_write_command(&mut self,
     _ui: &mut UILock,
     command: String,
+    _context: &ShellContext,
     input: LinesIter,
   ) -> Result<usize> {
     let input = input.fold(String::new(), |mut s, x| {s.push_str(x); s});
@@ -95,6 +99,7 @@ impl IO for FakeIO {
   fn run_transform_command(&mut self,
     _ui: &mut UILock,
     command: String,
+    _context: &ShellContext,
     input: LinesIter,
   ) -> Result<String> {
     let input = input.fold(String::new(), |mut s, x| {s.push_str(x); s});
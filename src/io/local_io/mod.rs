@@ -7,6 +7,7 @@ use std::process::{
   Stdio,
 };
 use crate::{IO, WriteType};
+use super::ShellContext;
 use super::LinesIter;
 use crate::UILock;
 use super::Result;
@@ -84,12 +85,14 @@ impl IO for LocalIO {
   fn run_command(&mut self,
     _ui: &mut UILock,
     command: String,
+    context: &ShellContext,
   ) -> Result<()> {
     let shell = std::env::var("SHELL").unwrap_or("sh".to_owned());
     // Create and run child process, passing through all io
     let res = Command::new(shell)
       .arg("-c")
       .arg(command)
+      .envs(context.env_vars())
       .spawn() // When spawn io defaults to inherited
       .map_err(LocalIOError::ChildCreationFailed)?
       .wait()
@@ -104,12 +107,14 @@ impl IO for LocalIO {
   fn run_read_command(&mut self,
     _ui: &mut UILock,
     command: String,
+    context: &ShellContext,
   ) -> Result<String> {
     let shell = std::env::var("SHELL").unwrap_or("sh".to_owned());
     // Create child process
     let child = Command::new(shell)
       .arg("-c")
       .arg(command)
+      .envs(context.env_vars())
       .stdout(Stdio::piped())
       .spawn()
       .map_err(LocalIOError::ChildCreationFailed)?
@@ -130,6 +135,7 @@ impl IO for LocalIO {
   fn run_write_command(&mut self,
     _ui: &mut UILock,
     command: String,
+    context: &ShellContext,
     input: LinesIter,
   ) -> Result<usize> {
     let shell = std::env::var("SHELL").unwrap_or("sh".to_owned());
@@ -137,6 +143,7 @@ impl IO for LocalIO {
     let mut child = Command::new(shell)
       .arg("-c")
       .arg(command)
+      .envs(context.env_vars())
       .stdin(Stdio::piped())
       .spawn()
       .map_err(LocalIOError::ChildCreationFailed)?
@@ -161,6 +168,7 @@ impl IO for LocalIO {
   fn run_transform_command(&mut self,
     _ui: &mut UILock,
     command: String,
+    context: &ShellContext,
     input: LinesIter,
   ) -> Result<String> {
     let shell = std::env::var("SHELL").unwrap_or("sh".to_owned());
@@ -168,6 +176,7 @@ impl IO for LocalIO {
     let mut child = Command::new(shell)
       .arg("-c")
       .arg(command)
+      .envs(context.env_vars())
       .stdin(Stdio::piped())
       .stdout(Stdio::piped())
       .spawn()
@@ -99,10 +99,17 @@ fn test_command_io() {
   let mut io = LocalIO::new();
   let mut mock_ui = DummyUI{};
   let mut mock_ui_lock = mock_ui.lock_ui();
+  let context = ShellContext{
+    file: "io_test_file".to_owned(),
+    selection: (2, 3),
+    prev_shell_command: "echo hi".to_owned(),
+    script: false,
+  };
   // Verify basic command execution via side effects
   io.run_command(
     &mut mock_ui_lock,
     "echo \"hurr\ndurr\" > io_command_test_file".to_owned(),
+    &context,
   ).unwrap();
   let data = io.read_file("io_command_test_file", true).unwrap();
   assert_eq!(
@@ -114,6 +121,7 @@ fn test_command_io() {
   let res = io.run_command(
     &mut mock_ui_lock,
     "false".to_owned(),
+    &context,
   );
   match res {
     Ok(_) => panic!("No error returned when child process failed to run."),
@@ -134,6 +142,7 @@ fn test_command_io() {
   let data = io.run_read_command(
     &mut mock_ui_lock,
     "echo \"hurr\ndurr\"".to_owned(),
+    &context,
   ).unwrap();
   assert_eq!(
     &data,
@@ -145,6 +154,7 @@ fn test_command_io() {
   let written = io.run_write_command(
     &mut mock_ui_lock,
     "cat > io_command_test_file".to_owned(),
+    &context,
     Box::new("hurr\ndurr\ndunn\n".split_inclusive('\n')).into(),
   ).unwrap();
   assert_eq!(
@@ -164,6 +174,7 @@ fn test_command_io() {
   let output = io.run_transform_command(
     &mut mock_ui_lock,
     "sort -n".to_owned(),
+    &context,
     Box::new("4\n5\n8\n1\n3\n2\n6\n0\n9\n7\n10\n".split_inclusive('\n')).into(),
   ).unwrap();
   assert_eq!(
@@ -171,4 +182,15 @@ fn test_command_io() {
     "0\n1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n",
     "Transform command running did not have expected effect."
   );
-} 
+  // Verify that the context is exported to the command
+  let data = io.run_read_command(
+    &mut mock_ui_lock,
+    "echo \"$ED_FILE $ED_SEL_START $ED_SEL_END $ED_PREV_CMD $ED_SCRIPT\"".to_owned(),
+    &context,
+  ).unwrap();
+  assert_eq!(
+    &data,
+    "io_test_file 2 3 echo hi 0\n",
+    "The shell context wasn't exported as environment variables."
+  );
+}
@@ -1,4 +1,5 @@
-//! Defines IO Trait, LocalIO (if enabled) and some testing implementations.
+//! Defines IO Trait, LocalIO (if enabled), RestrictedIO and some testing
+//! implementations.
 //!
 //! Used to abstract filesystem and shell interactions.
 
@@ -9,6 +10,8 @@ use crate::LinesIter;
 
 pub mod fake_io;
 pub mod dummy_io;
+pub mod restricted_io;
+pub use restricted_io::RestrictedIO;
 
 #[cfg(feature = "local_io")]
 pub mod local_io;
@@ -26,14 +29,50 @@ pub enum WriteType {
   Overwrite,
 }
 
+/// The editor state a shell command is run in
+///
+/// Given to the shell methods on [`IO`], to make available to the command. See
+/// [`ShellContext::env_vars`] for the environment variables [`LocalIO`]
+/// exports it as.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShellContext {
+  /// The current file, empty if none is set
+  pub file: String,
+  /// The 1-indexed inclusive selection the command operates on
+  ///
+  /// The lines given to the command for `|` and `w !`, `(index, index)` for
+  /// `r !` and otherwise the current selection.
+  pub selection: (usize, usize),
+  /// The shell command run before this one, empty if none
+  pub prev_shell_command: String,
+  /// Wether the command is run by a `g`-family command or a macro
+  pub script: bool,
+}
+impl ShellContext {
+  /// The context as environment variables
+  ///
+  /// These are `ED_FILE`, `ED_SEL_START`, `ED_SEL_END`, `ED_PREV_CMD` and
+  /// `ED_SCRIPT` (`1` if run by a script, else `0`).
+  pub fn env_vars(&self) -> [(&'static str, String); 5] {
+    [
+      ("ED_FILE", self.file.clone()),
+      ("ED_SEL_START", self.selection.0.to_string()),
+      ("ED_SEL_END", self.selection.1.to_string()),
+      ("ED_PREV_CMD", self.prev_shell_command.clone()),
+      ("ED_SCRIPT", if self.script { "1" } else { "0" }.to_owned()),
+    ]
+  }
+}
+
 /// Trait that abstracts file interactions and running shell commands
 ///
 /// Intended to allow modifying how and where system interactions occur.
 /// Example cases for replacing this:
 /// - Dummy IO to prevent filesystem modifications while testing.
 /// - SSH forwarding to save to remote system and run commands remotely.
-/// - Restricted IO to forbid command running and restrict file paths.
-pub trait IO {
+/// - Restricted IO to forbid command running and restrict file paths, see
+///   [`RestrictedIO`].
+pub trait IO: crate::sync::MaybeSend {
   /// Run a lone command (unrelated from the buffer)
   ///
   /// Stdin, Stdout and Stderr passed through to UI
@@ -43,6 +82,8 @@ pub trait IO {
     ui: &mut UILock,
     // Command string from user (with basic substitutions interpreted)
     command: String,
+    // Context of the command, see ShellContext
+    context: &ShellContext,
   ) -> Result<()>;
 
   /// Run a read command, collecting stdout to add into buffer
@@ -57,6 +98,8 @@ pub trait IO {
     ui: &mut UILock,
     // Command string from user (with basic substitutions interpreted)
     command: String,
+    // Context of the command, see ShellContext
+    context: &ShellContext,
   ) -> Result<String>;
 
   /// Run a write command, receiving part of buffer via stdin
@@ -73,6 +116,8 @@ pub trait IO {
     ui: &mut UILock,
     // Command string from user (with basic substitutions interpreted)
     command: String,
+    // Context of the command, see ShellContext
+    context: &ShellContext,
     // Iterator over string slices to send over stdin
     input: LinesIter,
   ) -> Result<usize>;
@@ -94,6 +139,8 @@ pub trait IO {
     ui: &mut UILock,
     // Command string from user (with basic substitutions interpreted)
     command: String,
+    // Context of the command, see ShellContext
+    context: &ShellContext,
     // Iterator over string slices to send over stdin
     input: LinesIter,
   ) -> Result<String>;
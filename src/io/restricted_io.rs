@@ -0,0 +1,158 @@
+//! An [`IO`] wrapper forbidding shell commands and confining file access
+
+use std::path::{Path, PathBuf, Component};
+
+use crate::{IO, WriteType};
+use super::ShellContext;
+use super::LinesIter;
+use crate::UILock;
+use super::Result;
+
+/// Error type for [`RestrictedIO`]
+#[derive(Debug, PartialEq)]
+pub enum RestrictedIOError {
+  /// Running shell commands isn't allowed
+  CommandForbidden,
+  /// The path is outside of the allowed directories, or walks out of the root
+  #[allow(missing_docs)]
+  PathForbidden{path: String},
+}
+impl std::error::Error for RestrictedIOError {}
+impl crate::error::IOErrorTrait for RestrictedIOError {}
+impl std::fmt::Display for RestrictedIOError {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    match self {
+      Self::CommandForbidden => { write!(f,
+        "Running shell commands is not allowed."
+      )},
+      Self::PathForbidden{path} => { write!(f,
+        "Access to `{}` is not allowed, it is outside the allowed directories.",
+        path,
+      )},
+    }
+  }
+}
+
+// Lexically resolve `.` and `..` in the path, returning None if it walks out
+// of the root (or out of a relative path)
+fn normalize(path: &Path) -> Option<PathBuf> {
+  let mut normalized = PathBuf::new();
+  for component in path.components() {
+    match component {
+      Component::Prefix(_) | Component::RootDir | Component::Normal(_) => {
+        normalized.push(component);
+      },
+      Component::CurDir => (),
+      Component::ParentDir => {
+        if !normalized.pop() { return None; }
+      },
+    }
+  }
+  Some(normalized)
+}
+
+/// An [`IO`] wrapper which forbids shell commands and only allows reading and
+/// writing files within the allowed directories
+///
+/// Paths are resolved relative to the base directory given on construction,
+/// and `.` and `..` are resolved before checking that they are within an
+/// allowed directory. The wrapped IO is given the resolved path.
+///
+/// The resolution is lexical, since the wrapped IO may not be local, so
+/// symbolic links within the allowed directories are followed by the wrapped
+/// IO. Make sure none point out of them.
+pub struct RestrictedIO<'a> {
+  inner: &'a mut dyn IO,
+  base: PathBuf,
+  allowed: Vec<PathBuf>,
+}
+impl <'a> RestrictedIO<'a> {
+  /// Wrap the given IO, resolving relative paths from `base`
+  ///
+  /// No directories are allowed until added with [`RestrictedIO::allow_dir`].
+  pub fn new<P: Into<PathBuf>>(
+    inner: &'a mut dyn IO,
+    base: P,
+  ) -> Self {
+    Self{
+      inner,
+      base: base.into(),
+      allowed: Vec::new(),
+    }
+  }
+
+  /// Allow reading and writing files within the given directory, including its
+  /// subdirectories
+  ///
+  /// Relative directories are resolved from the base directory.
+  pub fn allow_dir<P: AsRef<Path>>(
+    mut self,
+    dir: P,
+  ) -> Self {
+    if let Some(dir) = normalize(&self.base.join(dir)) {
+      self.allowed.push(dir);
+    }
+    self
+  }
+
+  /// Resolve the path, returning it if within an allowed directory
+  pub fn resolve(&self, path: &str) -> Result<String> {
+    normalize(&self.base.join(path))
+      .filter(|p| self.allowed.iter().any(|dir| p.starts_with(dir)))
+      .and_then(|p| p.to_str().map(|p| p.to_owned()))
+      .ok_or_else(|| RestrictedIOError::PathForbidden{
+        path: path.to_owned(),
+      }.into())
+  }
+}
+
+impl IO for RestrictedIO<'_> {
+  fn run_command(&mut self,
+    _ui: &mut UILock,
+    _command: String,
+    _context: &ShellContext,
+  ) -> Result<()> {
+    Err(RestrictedIOError::CommandForbidden.into())
+  }
+  fn run_read_command(&mut self,
+    _ui: &mut UILock,
+    _command: String,
+    _context: &ShellContext,
+  ) -> Result<String> {
+    Err(RestrictedIOError::CommandForbidden.into())
+  }
+  fn run_write_command(&mut self,
+    _ui: &mut UILock,
+    _command: String,
+    _context: &ShellContext,
+    _input: LinesIter,
+  ) -> Result<usize> {
+    Err(RestrictedIOError::CommandForbidden.into())
+  }
+  fn run_transform_command(&mut self,
+    _ui: &mut UILock,
+    _command: String,
+    _context: &ShellContext,
+    _input: LinesIter,
+  ) -> Result<String> {
+    Err(RestrictedIOError::CommandForbidden.into())
+  }
+  fn write_file(&mut self,
+    path: &str,
+    wtype: WriteType,
+    data: LinesIter,
+  ) -> Result<usize> {
+    // Leave erroring on empty paths to the wrapped IO
+    if path.is_empty() { return self.inner.write_file(path, wtype, data); }
+    let path = self.resolve(path)?;
+    self.inner.write_file(&path, wtype, data)
+  }
+  fn read_file(&mut self,
+    path: &str,
+    must_exist: bool,
+  ) -> Result<String> {
+    if path.is_empty() { return self.inner.read_file(path, must_exist); }
+    let path = self.resolve(path)?;
+    self.inner.read_file(&path, must_exist)
+  }
+}
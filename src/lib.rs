@@ -53,6 +53,7 @@ pub use error::{
 };
 
 mod cmd;
+pub use cmd::{Sel, Ind, PrintingFlags, Command, parse_command, EditSummary};
 
 pub mod ui;
 use ui::{UI, UILock, ScriptedUI};
@@ -60,9 +61,20 @@ pub mod io;
 use io::{IO, WriteType};
 
 mod history;
-pub use history::History;
+pub use history::{History, UndoStyle, Clock, SystemClock};
+use history::Snapshot;
 pub mod macros;
 use macros::{Macro, MacroGetter};
+pub mod plugins;
+use plugins::{Plugin, CommandExtender};
+pub mod step;
+pub mod options;
+use options::CustomOption;
+pub mod sync;
+#[cfg(feature = "serde")]
+mod session;
+#[cfg(feature = "serde")]
+pub use session::Session;
 
 pub use buffer::iters::*;
 mod buffer;
@@ -75,6 +87,9 @@ pub use buffer::{
 };
 
 /// A ready parsed 's' invocation, including command and printing flags
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature="serde", serde(deny_unknown_fields))]
 pub struct Substitution {
   /// Regex pattern to match against
   pub pattern: String,
@@ -90,6 +105,20 @@ pub struct Substitution {
   pub l: bool,
 }
 
+/// The state of an open buffer which isn't the active one
+///
+/// The active buffer's state is held directly in [`Ed`], as `history`, `file`
+/// and `selection`. See [`Ed::open_buffer`] and [`Ed::switch_buffer`].
+#[derive(Debug, Default)]
+pub struct OpenBuffer {
+  /// The history of the buffer, see [`Ed::history`]
+  pub history: History<Buffer>,
+  /// The path to the buffer's file, see [`Ed::file`]
+  pub file: String,
+  /// The buffer's selection, see [`Ed::selection`]
+  pub selection: (usize, usize),
+}
+
 /// The state variable used to track the editor's internal state.
 ///
 /// It is designed to support mutation and analysis by library users, but be
@@ -119,6 +148,13 @@ pub struct Ed <'a> {
   pub io: &'a mut dyn IO,
   /// The path to the currently selected file.
   pub file: String,
+  /// The name of the active buffer.
+  ///
+  /// `history`, `file` and `selection` belong to the active buffer, while the
+  /// clipboard and everything else is shared between buffers.
+  pub buffer_name: String,
+  /// The open buffers which aren't active, by name.
+  pub buffers: std::collections::BTreeMap<String, OpenBuffer>,
 
   /// Shell command last given by the user
   ///
@@ -134,6 +170,10 @@ pub struct Ed <'a> {
   ///
   /// Traditionally ':' so set to that by default.
   pub cmd_prefix: Option<char>,
+  /// Wether to give the UI `cmd_prefix` to print before command input.
+  ///
+  /// Toggled by `P` without flags, as GNU Ed's prompt.
+  pub show_cmd_prefix: bool,
   /// Set default to print numbered lines.
   ///
   /// If set `n` printing flag behaviour inverts and disables line numbers.
@@ -147,6 +187,22 @@ pub struct Ed <'a> {
   /// If not true Ed prints ? on error, expecting use of `h` command to get
   /// the error.
   pub print_errors: bool,
+  /// Whether to skip informational messages.
+  ///
+  /// If set the byte counts after reading and writing, and the `!` printed
+  /// after shell command output, aren't printed.
+  pub quiet: bool,
+  /// Whether to shell quote the file name and previous command when
+  /// substituting `%` and `!` in shell commands.
+  ///
+  /// Quoting is skipped for text without special characters, and `%%` resp.
+  /// `!!` substitute the text as is. A command that is only `!` repeats the
+  /// previous command as is. (If unset `%%` and `!!` substitute the text twice,
+  /// like before quoting was added.)
+  ///
+  /// Off in [`Ed::new`], to not change existing shell commands. classic-ed
+  /// turns it on.
+  pub shell_quote: bool,
   /// The previous error that occured.
   ///
   /// Is printed by `h` command.
@@ -162,6 +218,22 @@ pub struct Ed <'a> {
   /// 2 is likely to interfere with basic use, 4 will require that macros don't
   /// call into eachother, 16 is unlikely to abort needlessly.
   pub recursion_limit: usize,
+  /// Plugins run by the `@` command, by name. See [`Ed::with_plugin`].
+  pub plugins: std::collections::HashMap<String, &'a mut dyn Plugin>,
+  /// Handles commands add-ed doesn't recognize, see
+  /// [`Ed::with_command_extender`].
+  pub command_extender: Option<&'a mut dyn CommandExtender>,
+  /// Options registered by the library user, by name. See [`Ed::with_option`].
+  pub options: std::collections::BTreeMap<String, &'a mut dyn CustomOption>,
+  /// Whether to store undo history next to the edited file.
+  ///
+  /// If set the history is loaded by `e` and `E` (if the stored history
+  /// matches the file) and stored whenever the whole buffer is written to the
+  /// current file. See [`Ed::save_history`] and [`Ed::load_history`].
+  #[cfg(feature = "history_file")]
+  pub persistent_history: bool,
+  // State for Ed::step, between calls
+  stepping: step::Stepping,
 }
 
 impl <'a, > Ed <'a> {
@@ -169,13 +241,21 @@ impl <'a, > Ed <'a> {
   ///
   /// Defaults are as follow:
   /// - `file`: empty string
+  /// - `buffer_name`: `"main"`, with no other buffers open
   /// - `clipboard`: empty clipboard
   /// - `error`: `None`
   /// - `print_errors`: `true`
+  /// - `quiet`: `false`
+  /// - `shell_quote`: `false`
   /// - `n`: `false`,
   /// - `l`: `false`,
   /// - `cmd_prefix`: `Some(':')`
+  /// - `show_cmd_prefix`: `true`
   /// - `recursion_limit`: `16`
+  /// - `plugins`: none, add with [`Ed::with_plugin`]
+  /// - `command_extender`: `None`
+  /// - `options`: none, add with [`Ed::with_option`]
+  /// - `persistent_history`: `false` (with the `history_file` feature)
   pub fn new(
     io: &'a mut dyn IO,
     macro_getter: &'a dyn MacroGetter,
@@ -189,19 +269,65 @@ impl <'a, > Ed <'a> {
       prev_shell_command: String::new(),
       // Sane defaults for externally visible variables
       file: String::new(),
+      buffer_name: "main".to_owned(),
+      buffers: std::collections::BTreeMap::new(),
       clipboard: Clipboard::new(),
       error: None,
       print_errors: true,
+      quiet: false,
+      shell_quote: false,
       n: false,
       l: false,
       cmd_prefix: Some(':'),
+      show_cmd_prefix: true,
       recursion_limit: 16,
+      plugins: std::collections::HashMap::new(),
+      command_extender: None,
+      options: std::collections::BTreeMap::new(),
+      #[cfg(feature = "history_file")]
+      persistent_history: false,
+      stepping: step::Stepping::default(),
       // And the given values
       io,
       macro_getter,
     }
   }
 
+  /// Register a plugin, to be run by the `@` command under the given name
+  ///
+  /// Replaces any plugin already registered with the name.
+  pub fn with_plugin<T: Into<String>>(
+    mut self,
+    name: T,
+    plugin: &'a mut dyn Plugin,
+  ) -> Self {
+    self.plugins.insert(name.into(), plugin);
+    self
+  }
+
+  /// Set the command extender, which is given commands add-ed doesn't
+  /// recognize before they error
+  pub fn with_command_extender(
+    mut self,
+    extender: &'a mut dyn CommandExtender,
+  ) -> Self {
+    self.command_extender = Some(extender);
+    self
+  }
+
+  /// Interpret a parsed selection into 1-indexed inclusive line indices
+  ///
+  /// Uses the default if no selection is given. Intended for
+  /// [`CommandExtender`]s, which are given the selection as parsed. Doesn't
+  /// verify that the selection is within the buffer.
+  pub fn interpret_selection(
+    &self,
+    selection: Option<Sel>,
+    default: (usize, usize),
+  ) -> Result<(usize, usize)> {
+    cmd::interpret_selection(self, selection, default)
+  }
+
   /// Run the given command
   ///
   /// Returns true if the command was to quit
@@ -212,6 +338,29 @@ impl <'a, > Ed <'a> {
   ) -> Result<bool> {
     self.private_run_command(ui, command, 0)
   }
+  /// Run the given parsed command
+  ///
+  /// The command's canonical text (see [`Command`]) is used as label of any
+  /// undo snapshot it creates. Returns [`EdError::CommandNewline`] if any
+  /// field holds a newline, as commands are single lines. (A newline in the
+  /// commands of [`Command::Global`] would for example run as more commands.)
+  ///
+  /// Returns true if the command was to quit
+  pub fn execute(
+    &mut self,
+    ui: &mut dyn UI,
+    command: Command,
+  ) -> Result<bool> {
+    let label = command.to_string();
+    let res = if label.contains('\n') {
+      Err(EdError::CommandNewline(label))
+    } else {
+      cmd::execute(self, ui, command, &label, 0)
+    };
+    // Note any error in state, like private_run_command
+    if let Err(e) = &res { self.error = Some(e.clone()); }
+    res
+  }
   // Exists to handle nesting depth, for nested 'g' invocations, without
   // exposing that argument to the public interface (since it will always be 0
   // when called from the public API).
@@ -253,7 +402,8 @@ impl <'a, > Ed <'a> {
     // Define a temporary closure to catch UI errors, needed since try blocks
     // aren't stabilized
     let mut clos = || {
-      let cmd = ui.get_command(self, self.cmd_prefix)?;
+      let prefix = self.cmd_prefix.filter(|_| self.show_cmd_prefix);
+      let cmd = ui.get_command(self, prefix)?;
       self.private_run_command(ui, &cmd, recursion_depth)
     };
     // Run it, save any error, and forward result
@@ -301,14 +451,228 @@ impl <'a, > Ed <'a> {
       print_ui: Some(ui),
     };
     // Loop over it, handling errors, until quit received
-    loop {
-      if self.private_get_and_run_command(&mut script_ui, recursion_depth)? {
-        break;
+    let mut run = |ed: &mut Self| -> Result<()> {
+      loop {
+        if ed.private_get_and_run_command(&mut script_ui, recursion_depth)? {
+          break;
+        }
+      }
+      Ok(())
+    };
+    if mac.transaction { self.transaction(run) }
+    else { run(self) }
+  }
+
+  /// Switch the active buffer to the open buffer with the given name
+  ///
+  /// Returns error if no buffer with that name is open.
+  pub fn switch_buffer(&mut self, name: &str) -> Result<()> {
+    if name == self.buffer_name { return Ok(()); }
+    let new = self.buffers.remove(name)
+      .ok_or_else(|| EdError::BufferUnknown(name.to_owned()))?
+    ;
+    let old = OpenBuffer{
+      history: std::mem::replace(&mut self.history, new.history),
+      file: std::mem::replace(&mut self.file, new.file),
+      selection: std::mem::replace(&mut self.selection, new.selection),
+    };
+    let old_name = std::mem::replace(&mut self.buffer_name, name.to_owned());
+    self.buffers.insert(old_name, old);
+    Ok(())
+  }
+  // Get the history of the open buffer with the given name, active or not
+  pub(crate) fn buffer_history_mut(
+    &mut self,
+    name: &str,
+  ) -> Option<&mut History<Buffer>> {
+    if name == self.buffer_name { Some(&mut self.history) }
+    else { self.buffers.get_mut(name).map(|b| &mut b.history) }
+  }
+  /// Open a new empty buffer with the given name and switch to it
+  ///
+  /// The new buffer's history is configured like the active buffer's (undo
+  /// style, limits and clock). If a buffer with the name is already open this
+  /// just switches to it.
+  ///
+  /// Returns error if the name is empty or contains whitespace or `:`.
+  pub fn open_buffer(&mut self, name: &str) -> Result<()> {
+    if name.is_empty() || name.contains(|c: char| c.is_whitespace() || c == ':') {
+      return Err(EdError::BufferNameInvalid(name.to_owned()));
+    }
+    if name != self.buffer_name && !self.buffers.contains_key(name) {
+      let mut history = History::with_clock(self.history.clock.clone());
+      history.undo_style = self.history.undo_style;
+      history.max_snapshots = self.history.max_snapshots;
+      history.max_memory = self.history.max_memory;
+      self.buffers.insert(name.to_owned(), OpenBuffer{
+        history,
+        file: String::new(),
+        selection: (1, 0),
+      });
+    }
+    self.switch_buffer(name)
+  }
+  /// Close the open buffer with the given name
+  ///
+  /// If the active buffer is closed the first other buffer (by name) becomes
+  /// active.
+  ///
+  /// Returns error if no buffer with the name is open, if it is the only open
+  /// buffer or if it is unsaved and not forced.
+  pub fn close_buffer(&mut self, name: &str, force: bool) -> Result<()> {
+    if name == self.buffer_name {
+      let next = self.buffers.keys().next()
+        .ok_or(EdError::BufferLastOpen)?
+        .clone()
+      ;
+      if !force && !self.history.saved() { return Err(EdError::UnsavedChanges); }
+      self.switch_buffer(&next)?;
+    }
+    let buffer = self.buffers.get(name)
+      .ok_or_else(|| EdError::BufferUnknown(name.to_owned()))?
+    ;
+    if !force && !buffer.history.saved() { return Err(EdError::UnsavedChanges); }
+    self.buffers.remove(name);
+    Ok(())
+  }
+
+  /// Run the given closure as a transaction, all or nothing
+  ///
+  /// If the closure returns error the buffer, selection and clipboard are
+  /// restored to their state before the closure ran, and any snapshots it
+  /// created are removed from history. (If the buffer was saved during the
+  /// closure it is instead considered unsaved after the rollback.)
+  ///
+  /// History isn't pruned until the closure has finished, so that all the
+  /// snapshots needed for the rollback remain.
+  ///
+  /// Only the buffer active when the transaction started is rolled back. If
+  /// the closure switched buffer the rollback switches back to it, but other
+  /// buffers keep their changes. (If the buffer was closed by the closure
+  /// there is nothing to roll back but the clipboard.)
+  ///
+  /// Returns the result of the closure.
+  pub fn transaction<T>(
+    &mut self,
+    f: impl FnOnce(&mut Self) -> Result<T>,
+  ) -> Result<T> {
+    let buffer_name = self.buffer_name.clone();
+    let len = self.history.len();
+    let viewed_i = self.history.viewed_i();
+    let saved_i = self.history.saved_i();
+    let buffer = self.history.current().create_snapshot();
+    let selection = self.selection;
+    let clipboard = self.clipboard.clone();
+    let max_snapshots = self.history.max_snapshots.take();
+    let max_memory = self.history.max_memory.take();
+    let res = f(self);
+    if res.is_err() {
+      if self.switch_buffer(&buffer_name).is_ok() {
+        self.history.restore(len, viewed_i, buffer);
+        if self.history.saved_i() != saved_i { self.history.set_unsaved(); }
+        self.selection = selection;
       }
+      self.clipboard = clipboard;
+    }
+    if let Some(history) = self.buffer_history_mut(&buffer_name) {
+      history.max_snapshots = max_snapshots;
+      history.max_memory = max_memory;
+      history.prune();
     }
+    res
+  }
+
+  /// Get the path at which the undo history for the given file is stored
+  ///
+  /// A hidden file next to the given file, `dir/file` giving
+  /// `dir/.file.ed-history`.
+  #[cfg(feature = "history_file")]
+  pub fn history_path(file: &str) -> String {
+    let path = std::path::Path::new(file);
+    let name = path.file_name()
+      .map(|x| x.to_string_lossy())
+      .unwrap_or_default()
+    ;
+    path.with_file_name(format!(".{}.ed-history", name))
+      .to_string_lossy()
+      .into_owned()
+  }
+
+  /// Store the undo history next to the current file
+  ///
+  /// Writes via `io`, to the path given by [`Ed::history_path`]. Any existing
+  /// stored history for the file is overwritten.
+  ///
+  /// Returns error if no file is set or if writing fails.
+  #[cfg(feature = "history_file")]
+  pub fn save_history(&mut self) -> Result<()> {
+    if self.file.is_empty() { return Err(EdError::DefaultFileUnset); }
+    let path = Self::history_path(&self.file);
+    let data = serde_json::to_string_pretty(&self.history)
+      .map_err(|e| EdError::HistoryFileInvalid{
+        path: path.clone(),
+        error: e.to_string(),
+      })?
+    ;
+    // Convert into lines, since that is what IO writes
+    let lines = data.lines()
+      .map(|x| Line::new(format!("{}\n", x)))
+      .collect::<core::result::Result<Buffer, _>>()
+      .map_err(error::InternalError::InvalidLineText)?
+    ;
+    self.io.write_file(
+      &path,
+      WriteType::Overwrite,
+      lines.get_lines((1, lines.len()))?,
+    )?;
     Ok(())
   }
 
+  /// Load stored undo history for the current file
+  ///
+  /// Intended to be run right after reading in the file. The stored history is
+  /// only loaded if the buffer is saved and its contents match the snapshot
+  /// stored as saved, so a file changed since the history was stored won't get
+  /// a history it doesn't belong to.
+  ///
+  /// History configuration, such as `undo_style`, is kept, and the loaded
+  /// history is pruned to the configured limits.
+  ///
+  /// Returns true if history was loaded, false if there was no stored history
+  /// or it didn't match the buffer. Returns error if no file is set, if
+  /// reading fails or if the stored history is invalid.
+  #[cfg(feature = "history_file")]
+  pub fn load_history(&mut self) -> Result<bool> {
+    if self.file.is_empty() { return Err(EdError::DefaultFileUnset); }
+    let path = Self::history_path(&self.file);
+    let data = self.io.read_file(&path, false)?;
+    if data.is_empty() || !self.history.saved() { return Ok(false); }
+    let mut history: History<Buffer> = serde_json::from_str(&data)
+      .map_err(|e| EdError::HistoryFileInvalid{
+        path,
+        error: e.to_string(),
+      })?
+    ;
+    // Compare only content, since tags set after saving are shared into the
+    // stored snapshot
+    let current = self.history.current();
+    let matches = history.saved_i().is_some_and(|i| {
+      history.snapshots()[i].1.content_eq(current)
+    });
+    if !matches { return Ok(false); }
+    // Safe to unwrap, since we verified saved_i exists above
+    history.set_viewed_i(history.saved_i().unwrap())?;
+    // Keep the configuration, which isn't stored
+    history.undo_style = self.history.undo_style;
+    history.max_snapshots = self.history.max_snapshots;
+    history.max_memory = self.history.max_memory;
+    history.clock = self.history.clock.clone();
+    history.dont_snapshot = self.history.dont_snapshot;
+    history.prune();
+    self.history = history;
+    Ok(true)
+  }
+
   /// Run until quit by command
   ///
   /// Prints ? or error message as errors occur (depending on `print_errors`).
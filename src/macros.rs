@@ -60,6 +60,12 @@ pub struct Macro {
   /// nr of arguments given, and if `None` is set no argument substitution is 
   /// run on the macro (which means '$'s don't need to be doubled in the macro).
   pub nr_arguments: NrArguments,
+  /// If the macro is run as a transaction
+  ///
+  /// If set and the macro errors, all its changes to the buffer, selection and
+  /// clipboard are rolled back. See [`crate::Ed::transaction`].
+  #[cfg_attr(feature="serde", serde(default, skip_serializing_if="is_false"))]
+  pub transaction: bool,
   // TODO, enable this later
   // /// How the macro execution interacts with undo/redo snapshotting
   // snapshotting_mode: MacroSnapshottingMode,
@@ -76,6 +82,7 @@ impl Macro {
     Self{
       input: input.into(),
       nr_arguments: NrArguments::Any,
+      transaction: false,
     }
   }
   /// Configure required nr of arguments for the macro
@@ -83,8 +90,17 @@ impl Macro {
     self.nr_arguments = nr;
     self
   }
+  /// Configure if the macro is run as a transaction
+  pub fn transaction(mut self, transaction: bool) -> Self {
+    self.transaction = transaction;
+    self
+  }
 }
 
+// Skip serializing options at their default, for backwards compatibility
+#[cfg(feature="serde")]
+fn is_false(x: &bool) -> bool { !x }
+
 /// Trait over different ways to get macros by name
 ///
 /// The intent is to allow for different methods of storing macros without
@@ -94,7 +110,7 @@ impl Macro {
 /// A ready implementation exists for HashMap, if you prefer to load in at
 /// startup for infallible macro getting during execution. A very good option if
 /// if you embedd your macro declarations in your editor's main config file.
-pub trait MacroGetter {
+pub trait MacroGetter: crate::sync::MaybeSend {
   fn get_macro(&self, name: &str) -> Result<Option<&Macro>>;
 }
 
@@ -7,3 +7,5 @@ pub const COMMAND_DOCUMENTATION: &str = include_str!("../COMMANDS.md");
 pub const NO_ERROR: &str = "No errors recorded.";
 /// Printed when 'f' command is called and no default path is yet set.
 pub const NO_FILE: &str = "No default file currently set.";
+/// Printed when 'U:' command is called and no checkpoints are set.
+pub const NO_CHECKPOINTS: &str = "No checkpoints in history.";
@@ -0,0 +1,195 @@
+//! Named options, which configure the editor and can be set by the `o` command
+//!
+//! The built-in options are listed in [`BUILTIN_OPTIONS`] and set the matching
+//! fields on [`Ed`]. Library users can register their own options with
+//! [`Ed::with_option`], which are then listed, printed and set the same way.
+
+use crate::{Ed, EdError, Result};
+use crate::error::InternalError;
+use crate::sync::MaybeSend;
+
+/// The names of the built-in options, each setting the same named field on
+/// [`Ed`]
+pub const BUILTIN_OPTIONS: &[&str] = &[
+  "n",
+  "l",
+  "print_errors",
+  "quiet",
+  "shell_quote",
+  "cmd_prefix",
+  "show_cmd_prefix",
+  "recursion_limit",
+];
+
+/// The typed value of an option
+///
+/// An option keeps the type of its value, so setting it to another type of
+/// value is an error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OptionValue {
+  /// Written as `true` or `false`
+  Bool(bool),
+  /// Written as a non-negative integer
+  Number(usize),
+  /// Written as the character, or `none` for no character
+  Char(Option<char>),
+  /// Written as is
+  Text(String),
+}
+impl OptionValue {
+  /// Parse text into the same type of value as self
+  ///
+  /// Returns None if the text isn't a valid value of that type.
+  pub fn parse_as(&self, text: &str) -> Option<Self> {
+    Some(match self {
+      Self::Bool(_) => Self::Bool(text.parse().ok()?),
+      Self::Number(_) => Self::Number(text.parse().ok()?),
+      Self::Char(_) => {
+        if text == "none" { return Some(Self::Char(None)); }
+        let mut chars = text.chars();
+        match (chars.next(), chars.next()) {
+          (Some(c), None) => Self::Char(Some(c)),
+          _ => return None,
+        }
+      },
+      Self::Text(_) => Self::Text(text.to_owned()),
+    })
+  }
+  /// The name of the type of value, as used in errors
+  pub fn type_name(&self) -> &'static str {
+    match self {
+      Self::Bool(_) => "bool",
+      Self::Number(_) => "number",
+      Self::Char(_) => "char",
+      Self::Text(_) => "text",
+    }
+  }
+}
+impl std::fmt::Display for OptionValue {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    match self {
+      Self::Bool(b) => write!(f, "{}", b),
+      Self::Number(n) => write!(f, "{}", n),
+      Self::Char(Some(c)) => write!(f, "{}", c),
+      Self::Char(None) => write!(f, "none"),
+      Self::Text(t) => write!(f, "{}", t),
+    }
+  }
+}
+
+/// An option registered by the library user, see [`Ed::with_option`]
+///
+/// Implemented for [`OptionValue`], which simply stores the value set. Read it
+/// with [`Ed::get_option`] while `Ed` holds it.
+pub trait CustomOption: MaybeSend {
+  /// Get the current value of the option
+  fn get(&self) -> OptionValue;
+  /// Set the option to the given value
+  ///
+  /// The value is always of the same type as `get` returns. Return an error to
+  /// reject the value.
+  fn set(&mut self, value: OptionValue) -> Result<()>;
+}
+impl CustomOption for OptionValue {
+  fn get(&self) -> OptionValue {
+    self.clone()
+  }
+  fn set(&mut self, value: OptionValue) -> Result<()> {
+    *self = value;
+    Ok(())
+  }
+}
+
+impl <'a> Ed <'a> {
+  /// Register an option, to be listed and set by the `o` command
+  ///
+  /// Replaces any option already registered with the name. Options can't
+  /// replace the built-in options, see [`BUILTIN_OPTIONS`].
+  pub fn with_option<T: Into<String>>(
+    mut self,
+    name: T,
+    option: &'a mut dyn CustomOption,
+  ) -> Self {
+    self.options.insert(name.into(), option);
+    self
+  }
+
+  /// Get the names of all options, built-in options first
+  pub fn option_names(&self) -> Vec<String> {
+    BUILTIN_OPTIONS.iter()
+      .map(|x| (*x).to_owned())
+      .chain(self.options.keys()
+        .filter(|x| !BUILTIN_OPTIONS.contains(&&x[..]))
+        .cloned()
+      )
+      .collect()
+  }
+
+  /// Get the value of the named option
+  ///
+  /// Returns [`EdError::OptionUndefined`] if there is no such option.
+  pub fn get_option(&self, name: &str) -> Result<OptionValue> {
+    Ok(match name {
+      "n" => OptionValue::Bool(self.n),
+      "l" => OptionValue::Bool(self.l),
+      "print_errors" => OptionValue::Bool(self.print_errors),
+      "quiet" => OptionValue::Bool(self.quiet),
+      "shell_quote" => OptionValue::Bool(self.shell_quote),
+      "cmd_prefix" => OptionValue::Char(self.cmd_prefix),
+      "show_cmd_prefix" => OptionValue::Bool(self.show_cmd_prefix),
+      "recursion_limit" => OptionValue::Number(self.recursion_limit),
+      _ => self.options.get(name)
+        .ok_or_else(|| EdError::OptionUndefined(name.to_owned()))?
+        .get(),
+    })
+  }
+
+  /// Set the named option to the given value
+  ///
+  /// Returns [`EdError::OptionUndefined`] if there is no such option and
+  /// [`EdError::OptionValueInvalid`] if the value is of another type than the
+  /// option's current value.
+  pub fn set_option(&mut self, name: &str, value: OptionValue) -> Result<()> {
+    let current = self.get_option(name)?;
+    if std::mem::discriminant(&current) != std::mem::discriminant(&value) {
+      return Err(EdError::OptionValueInvalid{
+        name: name.to_owned(),
+        value: value.to_string(),
+        expected: current.type_name(),
+      });
+    }
+    match (name, value) {
+      ("n", OptionValue::Bool(x)) => self.n = x,
+      ("l", OptionValue::Bool(x)) => self.l = x,
+      ("print_errors", OptionValue::Bool(x)) => self.print_errors = x,
+      ("quiet", OptionValue::Bool(x)) => self.quiet = x,
+      ("shell_quote", OptionValue::Bool(x)) => self.shell_quote = x,
+      ("cmd_prefix", OptionValue::Char(x)) => self.cmd_prefix = x,
+      ("show_cmd_prefix", OptionValue::Bool(x)) => self.show_cmd_prefix = x,
+      ("recursion_limit", OptionValue::Number(x)) => self.recursion_limit = x,
+      (_, value) => match self.options.get_mut(name) {
+        Some(option) => option.set(value)?,
+        // Cannot happen, as getting the option succeeded above
+        None => return ed_unreachable!(),
+      },
+    }
+    Ok(())
+  }
+
+  /// Set the named option from its text representation
+  ///
+  /// Parses the text into the same type as the option's current value, see
+  /// [`OptionValue`] for how each type is written. Errors as
+  /// [`Ed::set_option`].
+  pub fn set_option_text(&mut self, name: &str, text: &str) -> Result<()> {
+    let current = self.get_option(name)?;
+    let value = current.parse_as(text)
+      .ok_or_else(|| EdError::OptionValueInvalid{
+        name: name.to_owned(),
+        value: text.to_owned(),
+        expected: current.type_name(),
+      })?
+    ;
+    self.set_option(name, value)
+  }
+}
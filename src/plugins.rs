@@ -0,0 +1,92 @@
+//! Ways to add commands compiled in with the editor
+//!
+//! [`Plugin`]s are run by name by the `@` command, while a [`CommandExtender`]
+//! can add commands of its own.
+
+use crate::{Ed, Result, Sel, PrintingFlags};
+use crate::ui::UI;
+use crate::sync::MaybeSend;
+
+/// A trait for commands added by the embedding application
+///
+/// Plugins are registered by name on [`Ed`], using [`Ed::with_plugin`], and
+/// invoked by the `@` command as `(<selection>)@<name>( <arguments>)`.
+///
+/// Unlike macros and shell commands plugins get mutable access to the editor
+/// itself, so they can modify [`crate::Buffer`] lines directly and keep the
+/// line data shared with history. Modifications are made via
+/// `ed.history.current_mut(..)` as usual, and are squashed into one snapshot
+/// labeled with the invoking command (same as for macros).
+///
+/// Implemented for closures with the same signature as [`Plugin::run`].
+pub trait Plugin: MaybeSend {
+  /// Run the plugin
+  ///
+  /// - `ed` is the editor state, with the plugin itself removed from
+  ///   `ed.plugins` while it runs.
+  /// - `ui` is the UI to print with.
+  /// - `selection` is the selection given to the command, interpreted but not
+  ///   verified against the buffer. If none given use `ed.selection` as default
+  ///   if fitting.
+  /// - `arguments` is everything after the plugin name, without the separating
+  ///   space.
+  fn run(&mut self,
+    ed: &mut Ed<'_>,
+    ui: &mut dyn UI,
+    selection: Option<(usize, usize)>,
+    arguments: &str,
+  ) -> Result<()>;
+}
+
+impl<F> Plugin for F where
+  F: FnMut(&mut Ed<'_>, &mut dyn UI, Option<(usize, usize)>, &str) -> Result<()>
+    + MaybeSend,
+{
+  fn run(&mut self,
+    ed: &mut Ed<'_>,
+    ui: &mut dyn UI,
+    selection: Option<(usize, usize)>,
+    arguments: &str,
+  ) -> Result<()> {
+    self(ed, ui, selection, arguments)
+  }
+}
+
+/// A trait for handling commands add-ed doesn't recognize
+///
+/// Set on [`Ed`] with [`Ed::with_command_extender`]. Whenever a command
+/// character isn't recognized the command is handed to the extender, which
+/// may claim it instead of it causing [`crate::EdError::CommandUndefined`].
+///
+/// This allows adding single character commands that act like the built-in
+/// ones. Beware that commands added to add-ed in the future will shadow those
+/// of the extender.
+pub trait CommandExtender: MaybeSend {
+  /// Try to run a command add-ed didn't recognize
+  ///
+  /// - `ed` is the editor state, with the extender itself removed from
+  ///   `ed.command_extender` while it runs.
+  /// - `ui` is the UI to print with and get input from.
+  /// - `pflags` are the printing flags, set them to print the selection
+  ///   after the command (as flags `p`, `n` and `l` do for built-in commands).
+  /// - `full_command` is the whole command without the newline, which should
+  ///   be given as label when modifying the buffer with
+  ///   `ed.history.current_mut(..)`.
+  /// - `selection` is the selection as parsed, use [`Ed::interpret_selection`]
+  ///   to get the lines it refers to.
+  /// - `command` is the command character.
+  /// - `tail` is everything after the command character, without the newline.
+  ///
+  /// Returns true if the command was claimed and run, false if the command
+  /// is unknown to the extender as well.
+  #[allow(clippy::too_many_arguments)]
+  fn run_command(&mut self,
+    ed: &mut Ed<'_>,
+    ui: &mut dyn UI,
+    pflags: &mut PrintingFlags,
+    full_command: &str,
+    selection: Option<Sel>,
+    command: char,
+    tail: &str,
+  ) -> Result<bool>;
+}
@@ -0,0 +1,135 @@
+//! Serializable editing session state, enabled by the `serde` feature.
+
+use serde::{Serialize, Deserialize};
+
+use crate::{
+  Ed,
+  EdError,
+  Result,
+  Buffer,
+  Clipboard,
+  History,
+  PubLine,
+  Substitution,
+};
+
+/// The state of an editing session, to store it and later restore it
+///
+/// Created by [`Ed::session`] and applied by [`Ed::restore_session`], so a
+/// session can be suspended and resumed in another process.
+///
+/// Only the active buffer is included, not other open buffers. Neither is
+/// configuration given when constructing [`Ed`] (IO, macros, plugins, etc.),
+/// `quiet`, `shell_quote`, `cmd_prefix`, `show_cmd_prefix`, `recursion_limit`
+/// nor the last error.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Session {
+  /// The lines in the buffer, with their tags
+  pub buffer: Vec<PubLine>,
+  /// If the buffer is saved to `file`
+  pub saved: bool,
+  /// The undo history of the buffer, if included
+  ///
+  /// If given, its viewed snapshot must match `buffer` and `saved`.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub history: Option<History<Buffer>>,
+  /// See [`Ed::selection`]
+  pub selection: (usize, usize),
+  /// See [`Ed::clipboard`]
+  pub clipboard: Clipboard,
+  /// See [`Ed::file`]
+  pub file: String,
+  /// See [`Ed::prev_s`]
+  pub prev_s: Option<Substitution>,
+  /// See [`Ed::prev_shell_command`]
+  pub prev_shell_command: String,
+  /// See [`Ed::n`]
+  pub n: bool,
+  /// See [`Ed::l`]
+  pub l: bool,
+  /// See [`Ed::print_errors`]
+  pub print_errors: bool,
+}
+
+impl <'a> Ed <'a> {
+  /// Get the state of the editing session, see [`Session`]
+  ///
+  /// The undo history is only included if `include_history`, since it can be
+  /// much larger than the buffer.
+  pub fn session(&self, include_history: bool) -> Session {
+    Session{
+      buffer: self.history.current().iter().map(|l| l.into()).collect(),
+      saved: self.history.saved(),
+      history: if include_history {
+        Some(self.history.detached_copy())
+      } else {
+        None
+      },
+      selection: self.selection,
+      clipboard: self.clipboard.clone(),
+      file: self.file.clone(),
+      prev_s: self.prev_s.clone(),
+      prev_shell_command: self.prev_shell_command.clone(),
+      n: self.n,
+      l: self.l,
+      print_errors: self.print_errors,
+    }
+  }
+
+  /// Restore the state of an editing session, see [`Session`]
+  ///
+  /// Replaces the active buffer's history. If the session holds no history a
+  /// new one is created, holding the buffer as its only change (saved if the
+  /// session was). History configuration, such as `undo_style`, is kept.
+  ///
+  /// Returns [`EdError::SessionInvalid`] without changing anything if the
+  /// session's history doesn't match its buffer.
+  pub fn restore_session(&mut self, session: Session) -> Result<()> {
+    let mut history = match session.history {
+      Some(history) => {
+        let matches = history.current().len() == session.buffer.len()
+          && history.current().iter()
+            .zip(&session.buffer)
+            .all(|(a, b)| &PubLine::from(a) == b)
+        ;
+        if !matches {
+          return Err(EdError::SessionInvalid(
+            "buffer doesn't match the viewed snapshot in history".to_owned()
+          ));
+        }
+        if history.saved() != session.saved {
+          return Err(EdError::SessionInvalid(
+            "saved doesn't match the viewed snapshot in history".to_owned()
+          ));
+        }
+        history
+      },
+      None => {
+        let mut history = History::<Buffer>::new();
+        history.current_mut("Restored session".into())
+          .extend(session.buffer.iter().map(|l| l.into()))
+        ;
+        if session.saved { history.set_saved(); }
+        else { history.set_unsaved(); }
+        history
+      },
+    };
+    // Keep the configuration, which isn't part of the session
+    history.undo_style = self.history.undo_style;
+    history.max_snapshots = self.history.max_snapshots;
+    history.max_memory = self.history.max_memory;
+    history.clock = self.history.clock.clone();
+    history.dont_snapshot = self.history.dont_snapshot;
+    self.history = history;
+    self.selection = session.selection;
+    self.clipboard = session.clipboard;
+    self.file = session.file;
+    self.prev_s = session.prev_s;
+    self.prev_shell_command = session.prev_shell_command;
+    self.n = session.n;
+    self.l = session.l;
+    self.print_errors = session.print_errors;
+    Ok(())
+  }
+}
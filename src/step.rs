@@ -0,0 +1,508 @@
+//! Stepping API, for frontends that can't block in [`UI`] methods
+//!
+//! Instead of handing [`Ed`] a UI to call, the frontend calls [`Ed::step`]
+//! with a [`Response`] and gets a [`Request`] back. Start with
+//! [`Response::Continue`], which gives a [`Request::Command`]. Requests to
+//! print are answered with [`Response::Continue`] once printed, while
+//! [`Request::Command`] and [`Request::Input`] are answered with the
+//! corresponding response whenever the user is done.
+//!
+//! Commands that take input, such as `a`, are run again from the start when
+//! resumed, with the undo history, buffer, selection and clipboard rolled back
+//! and the earlier input given again. Built-in commands verify their arguments
+//! before taking input and change nothing before that, so this repeats no
+//! work. Plugins and command extenders could have effects outside the editor
+//! before taking input, so they aren't run again. Instead
+//! [`EdError::StepInputUnsupported`] is returned if they ask for input while
+//! stepping.
+//!
+//! `g`, `v`, `G` and `V` run other commands, so they are never run again. `g`
+//! and `v` get any commands not given on the command line as input before
+//! running, and `G` and `V` are driven line by line, as their input is taken
+//! in between running the commands.
+//!
+//! Minimal usage example:
+//! ```
+//! use add_ed::{
+//!   io::dummy_io::DummyIO,
+//!   step::{Request, Response},
+//!   Ed,
+//! };
+//!
+//! let macro_store = std::collections::HashMap::new();
+//! let mut io = DummyIO::new();
+//! let mut ed = Ed::new(&mut io, &macro_store);
+//! let mut commands = vec!["a\n", "Q\n"].into_iter();
+//! let mut response = Response::Continue;
+//! loop {
+//!   response = match ed.step(response) {
+//!     Ok(Request::Command{..}) => Response::Command(
+//!       commands.next().unwrap().to_owned()
+//!     ),
+//!     Ok(Request::Input{..}) => Response::Input(vec!["text\n".to_owned()]),
+//!     Ok(Request::Quit) => break,
+//!     // Print the requested output here
+//!     Ok(_) => Response::Continue,
+//!     // Print the error here
+//!     Err(_) => Response::Continue,
+//!   };
+//! }
+//! ```
+
+use std::collections::VecDeque;
+
+use crate::{Ed, Command, PubLine, Sel, parse_command};
+use crate::cmd;
+use crate::error::{Result, EdError, InternalError, UIError, UIErrorTrait};
+use crate::ui::{UI, UILock};
+
+/// What the editor needs the frontend to do, returned by [`Ed::step`]
+///
+/// There is no request for confirmation, as add-ed never asks for it. Commands
+/// that would discard unsaved changes instead return
+/// [`EdError::UnsavedChanges`], and the user confirms by giving the forced
+/// command (such as `Q` or `E`).
+#[derive(Debug, Clone, PartialEq)]
+pub enum Request {
+  /// Get a command from the user, then give it as [`Response::Command`]
+  ///
+  /// See [`UI::get_command`].
+  Command{ prefix: Option<char> },
+  /// Get input lines from the user, until the terminator is entered alone on
+  /// a line, then give them as [`Response::Input`]
+  ///
+  /// Initial data is only given with the `initial_input_data` feature. See
+  /// [`UI::get_input`].
+  Input{ terminator: char, initial: Option<Vec<String>> },
+  /// Print the message, see [`UI::print_message`]
+  PrintMessage(String),
+  /// Print the lines, as they were when the print was requested
+  ///
+  /// `selection` is the line numbers of the lines. See [`UI::print_selection`].
+  PrintSelection{
+    selection: (usize, usize),
+    lines: Vec<PubLine>,
+    numbered: bool,
+    literal: bool,
+  },
+  /// Print the commands listing, see [`UI::print_commands`]
+  PrintCommands,
+  /// Print the commands documentation, see
+  /// [`UI::print_command_documentation`]
+  PrintCommandDocumentation,
+  /// The active buffer was switched, see [`UI::buffer_switched`]
+  BufferSwitched,
+  /// The editor was told to quit
+  Quit,
+}
+
+/// The frontend's answer to the last [`Request`], given to [`Ed::step`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Response {
+  /// Answers any request that doesn't need data (and starts the stepping)
+  Continue,
+  /// Answers [`Request::Command`] with the command to run
+  Command(String),
+  /// Answers [`Request::Input`] with the newline terminated input lines,
+  /// excluding the terminating line
+  Input(Vec<String>),
+}
+
+// What the last returned request awaits
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+enum Awaiting {
+  #[default]
+  Continue,
+  Command,
+  Input,
+}
+
+// A command waiting for input
+#[derive(Debug)]
+enum Suspended {
+  // Run again from the start with all the input given so far
+  Replay{
+    command: String,
+    inputs: Vec<Vec<String>>,
+    // Nr of requests already returned, to not return them again
+    delivered: usize,
+  },
+  // A top level 'g' or 'v', waiting for the commands to run
+  Global{
+    label: String,
+    command: Command,
+  },
+  // A top level 'G' or 'V', on the given marked line
+  Interactive{
+    index: usize,
+    terminator: char,
+    // The buffer to restore snapshotting in, even if the commands switch
+    buffer_name: String,
+    orig_dont_snapshot: bool,
+  },
+}
+
+/// The state of [`Ed::step`]
+#[derive(Debug, Default)]
+pub(crate) struct Stepping {
+  queue: VecDeque<Result<Request>>,
+  awaiting: Awaiting,
+  suspended: Option<Suspended>,
+}
+
+// Returned by StepUI::get_input to abort the command when out of input
+#[derive(Debug)]
+struct NeedInput{}
+impl std::fmt::Display for NeedInput {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    write!(f, "Command needs input from Ed::step.")
+  }
+}
+impl std::error::Error for NeedInput {}
+impl UIErrorTrait for NeedInput {}
+
+// Records what commands do with the UI as requests
+struct StepUI {
+  inputs: VecDeque<Vec<String>>,
+  // Nr of requests to drop, as they were returned by an earlier run
+  skip: usize,
+  requests: Vec<Request>,
+  // Set if the command asked for more input than given
+  need_input: Option<Request>,
+  // Set when running a plugin or command extender, which can't be run again
+  input_unsupported: bool,
+}
+impl StepUI {
+  fn new(inputs: Vec<Vec<String>>, skip: usize) -> Self {
+    Self{
+      inputs: inputs.into(),
+      skip,
+      requests: Vec::new(),
+      need_input: None,
+      input_unsupported: false,
+    }
+  }
+  fn push(&mut self, request: Request) {
+    if self.skip > 0 { self.skip -= 1; }
+    else { self.requests.push(request); }
+  }
+}
+impl UI for StepUI {
+  fn print_message(&mut self, data: &str) -> Result<()> {
+    self.push(Request::PrintMessage(data.to_owned()));
+    Ok(())
+  }
+  fn print_commands(&mut self) -> Result<()> {
+    self.push(Request::PrintCommands);
+    Ok(())
+  }
+  fn print_command_documentation(&mut self) -> Result<()> {
+    self.push(Request::PrintCommandDocumentation);
+    Ok(())
+  }
+  fn get_command(&mut self,
+    _ed: &Ed,
+    _prefix: Option<char>,
+  ) -> Result<String> {
+    // Only the top level asks for commands, which Ed::step handles itself
+    ed_unreachable!()
+  }
+  fn get_input(&mut self,
+    _ed: &Ed,
+    terminator: char,
+    #[cfg(feature = "initial_input_data")]
+    initial_buffer: Option<Vec<String>>,
+  ) -> Result<Vec<String>> {
+    #[cfg(not(feature = "initial_input_data"))]
+    let initial_buffer = None;
+    match self.inputs.pop_front() {
+      Some(input) => Ok(input),
+      None if self.input_unsupported => Err(EdError::StepInputUnsupported),
+      None => {
+        self.need_input = Some(Request::Input{
+          terminator,
+          initial: initial_buffer,
+        });
+        Err(EdError::UI(UIError::from(NeedInput{})))
+      },
+    }
+  }
+  fn print_selection(&mut self,
+    ed: &Ed,
+    selection: (usize, usize),
+    numbered: bool,
+    literal: bool,
+  ) -> Result<()> {
+    let lines = ed.history.current()
+      .range(selection.0 - 1 .. selection.1)
+      .map(|l| l.into())
+      .collect()
+    ;
+    self.push(Request::PrintSelection{ selection, lines, numbered, literal });
+    Ok(())
+  }
+  fn buffer_switched(&mut self, _ed: &Ed) -> Result<()> {
+    self.push(Request::BufferSwitched);
+    Ok(())
+  }
+  // The frontend isn't told, since it cannot act on it before the shell
+  // command runs
+  fn lock_ui(&mut self) -> UILock<'_> {
+    UILock::new(self)
+  }
+  fn unlock_ui(&mut self) {}
+}
+
+// If a 'g' or 'v' command takes more commands as input
+fn global_needs_input(command: &Command) -> bool {
+  match command {
+    Command::Global{commands, ..} => commands.last().map(|c| c.trim()) != Some(""),
+    _ => false,
+  }
+}
+
+impl <'a> Ed <'a> {
+  /// Give the frontend's response and get the next request
+  ///
+  /// See the [`step`](crate::step) module for how to use this. A command
+  /// error is returned after the requests the command made before failing,
+  /// answer it with [`Response::Continue`] like other requests. (It is also
+  /// saved into `Ed.error`, so it is printed by `h`.)
+  ///
+  /// Returns [`EdError::ResponseUnexpected`] if the response doesn't answer
+  /// the last request, in which case the request stays unanswered.
+  pub fn step(&mut self, response: Response) -> Result<Request> {
+    let mut stepping = std::mem::take(&mut self.stepping);
+    let res = self.inner_step(&mut stepping, response);
+    self.stepping = stepping;
+    res
+  }
+  fn inner_step(
+    &mut self,
+    stepping: &mut Stepping,
+    response: Response,
+  ) -> Result<Request> {
+    match (stepping.awaiting, response) {
+      (Awaiting::Continue, Response::Continue) => (),
+      (Awaiting::Command, Response::Command(command)) => {
+        match parse_command(&command) {
+          // Top level 'G' and 'V' take input between running other commands,
+          // so they are driven line by line instead of run again
+          Ok(Command::InteractiveGlobal{selection, inverse, separator, pattern}) => {
+            self.start_interactive(stepping, &command, selection, inverse,
+              separator, &pattern,
+            );
+          },
+          // Top level 'g' and 'v' without a terminated command list take the
+          // rest as input, which is requested before running them once
+          Ok(global @ Command::Global{..}) if global_needs_input(&global) => {
+            self.start_global(stepping, command, global);
+          },
+          _ => {
+            stepping.suspended = Some(Suspended::Replay{
+              command,
+              inputs: Vec::new(),
+              delivered: 0,
+            });
+            self.replay(stepping);
+          },
+        }
+      },
+      (Awaiting::Input, Response::Input(input)) => {
+        match stepping.suspended.take() {
+          Some(Suspended::Replay{command, mut inputs, delivered}) => {
+            inputs.push(input);
+            stepping.suspended = Some(Suspended::Replay{command, inputs, delivered});
+            self.replay(stepping);
+          },
+          Some(Suspended::Global{label, command}) => {
+            self.finish_global(stepping, &label, command, input);
+          },
+          Some(Suspended::Interactive{index, terminator, buffer_name, orig_dont_snapshot}) => {
+            let mut ui = StepUI::new(Vec::new(), 0);
+            let res = cmd::global_interactive_line(self, &mut ui, index, input, 0);
+            stepping.queue.extend(ui.requests.drain(..).map(Ok));
+            stepping.suspended = Some(Suspended::Interactive{
+              index,
+              terminator,
+              buffer_name,
+              orig_dont_snapshot,
+            });
+            match res {
+              Ok(()) => self.next_interactive(stepping),
+              Err(e) => self.fail_interactive(stepping, e),
+            }
+          },
+          None => return ed_unreachable!(),
+        }
+      },
+      _ => return Err(EdError::ResponseUnexpected),
+    }
+    // Return the next queued request, or ask for the next command
+    let next = stepping.queue.pop_front()
+      .unwrap_or(Ok(Request::Command{
+        prefix: self.cmd_prefix.filter(|_| self.show_cmd_prefix),
+      }))
+    ;
+    stepping.awaiting = match next {
+      Ok(Request::Command{..}) => Awaiting::Command,
+      Ok(Request::Input{..}) => Awaiting::Input,
+      _ => Awaiting::Continue,
+    };
+    next
+  }
+
+  // Run the suspended command from the start, rolling back if it needs more
+  // input than given so far
+  fn replay(&mut self, stepping: &mut Stepping) {
+    let (command, inputs, delivered) = match &stepping.suspended {
+      Some(Suspended::Replay{command, inputs, delivered}) =>
+        (command.clone(), inputs.clone(), *delivered),
+      _ => return,
+    };
+    let mut ui = StepUI::new(inputs, delivered);
+    ui.input_unsupported = matches!(
+      parse_command(&command),
+      Ok(Command::Plugin{..} | Command::Other{..}),
+    );
+    // Only error from the transaction if out of input, to roll back
+    let res = self.transaction(|ed| {
+      let res = cmd::run(ed, &mut ui, &command, 0);
+      match ui.need_input {
+        Some(_) => res.map(|_| Ok(false)),
+        None => Ok(res),
+      }
+    });
+    let new_requests = ui.requests.len();
+    stepping.queue.extend(ui.requests.drain(..).map(Ok));
+    match (ui.need_input.take(), res) {
+      (Some(request), _) => {
+        stepping.queue.push_back(Ok(request));
+        if let Some(Suspended::Replay{delivered, ..}) = &mut stepping.suspended {
+          *delivered += new_requests;
+        }
+      },
+      (None, Ok(Ok(quit))) => {
+        if quit { stepping.queue.push_back(Ok(Request::Quit)); }
+        stepping.suspended = None;
+      },
+      (None, Ok(Err(e)) | Err(e)) => {
+        self.error = Some(e.clone());
+        stepping.queue.push_back(Err(e));
+        stepping.suspended = None;
+      },
+    }
+  }
+
+  // Mark lines like the 'g' command, to verify it before requesting the rest
+  // of its commands as input
+  fn start_global(
+    &mut self,
+    stepping: &mut Stepping,
+    label: String,
+    command: Command,
+  ) {
+    let (selection, inverse, separator, pattern) = match &command {
+      Command::Global{selection, inverse, separator, pattern, ..} =>
+        (selection.clone(), *inverse, *separator, pattern),
+      _ => return,
+    };
+    match cmd::global_interactive_mark(self, selection, inverse, pattern, 0) {
+      Ok(()) => {
+        stepping.queue.push_back(Ok(Request::Input{
+          terminator: separator,
+          initial: None,
+        }));
+        stepping.suspended = Some(Suspended::Global{label, command});
+      },
+      Err(e) => {
+        self.error = Some(e.clone());
+        stepping.queue.push_back(Err(e));
+      },
+    }
+  }
+  // Run the 'g' command with the input appended to its commands
+  fn finish_global(
+    &mut self,
+    stepping: &mut Stepping,
+    label: &str,
+    mut command: Command,
+    input: Vec<String>,
+  ) {
+    if let Command::Global{commands, ..} = &mut command {
+      commands.extend(input.iter().map(|c| c.trim_end_matches('\n').to_owned()));
+      // Terminate the command list, so no more input is taken
+      commands.push(String::new());
+    }
+    let mut ui = StepUI::new(Vec::new(), 0);
+    let res = cmd::execute(self, &mut ui, command, label.trim_end_matches('\n'), 0);
+    stepping.queue.extend(ui.requests.drain(..).map(Ok));
+    match res {
+      Ok(quit) => if quit { stepping.queue.push_back(Ok(Request::Quit)); },
+      Err(e) => {
+        self.error = Some(e.clone());
+        stepping.queue.push_back(Err(e));
+      },
+    }
+  }
+
+  // Snapshot and mark lines like the 'G' command, then request input for the
+  // first marked line
+  fn start_interactive(
+    &mut self,
+    stepping: &mut Stepping,
+    command: &str,
+    selection: Option<Sel>,
+    inverse: bool,
+    separator: char,
+    pattern: &str,
+  ) {
+    self.history.snapshot(command.trim_end_matches('\n').to_owned());
+    let orig_dont_snapshot = self.history.dont_snapshot;
+    self.history.dont_snapshot = true;
+    stepping.suspended = Some(Suspended::Interactive{
+      index: 0,
+      terminator: separator,
+      buffer_name: self.buffer_name.clone(),
+      orig_dont_snapshot,
+    });
+    match cmd::global_interactive_mark(self, selection, inverse, pattern, 0) {
+      Ok(()) => self.next_interactive(stepping),
+      Err(e) => self.fail_interactive(stepping, e),
+    }
+  }
+  // Request input for the next marked line, or finish if none remain
+  fn next_interactive(&mut self, stepping: &mut Stepping) {
+    let next = cmd::get_marked(self, 1);
+    match (&mut stepping.suspended, next) {
+      (Some(Suspended::Interactive{index, terminator, ..}), Some(i)) => {
+        *index = i;
+        let mut ui = StepUI::new(Vec::new(), 0);
+        // Can't fail, since StepUI doesn't
+        let _ = ui.print_selection(self, (i, i), self.n, self.l);
+        stepping.queue.extend(ui.requests.drain(..).map(Ok));
+        stepping.queue.push_back(Ok(Request::Input{
+          terminator: *terminator,
+          initial: None,
+        }));
+      },
+      _ => self.finish_interactive(stepping),
+    }
+  }
+  fn fail_interactive(&mut self, stepping: &mut Stepping, error: EdError) {
+    self.finish_interactive(stepping);
+    self.error = Some(error.clone());
+    stepping.queue.push_back(Err(error));
+  }
+  // Restore snapshotting like the 'G' command does after running
+  fn finish_interactive(&mut self, stepping: &mut Stepping) {
+    if let Some(Suspended::Interactive{buffer_name, orig_dont_snapshot, ..}) =
+      stepping.suspended.take()
+    {
+      if let Some(history) = self.buffer_history_mut(&buffer_name) {
+        history.dont_snapshot = orig_dont_snapshot;
+        if !orig_dont_snapshot { history.dedup_present(); }
+      }
+    }
+  }
+}
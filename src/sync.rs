@@ -0,0 +1,115 @@
+//! Shared data types, which become thread safe with the `send` feature
+//!
+//! By default add-ed shares data between undo snapshots using [`std::rc::Rc`]
+//! and [`std::cell`] types, which are cheaper but keep [`crate::Ed`] on the
+//! thread it was created on. With the `send` feature these are replaced by
+//! [`std::sync::Arc`] and atomic or locked equivalents, making `Ed`,
+//! `History<Buffer>` and the error types `Send`.
+
+#[cfg(not(feature = "send"))]
+use std::cell::{Cell, RefCell, RefMut};
+#[cfg(feature = "send")]
+use std::sync::{Mutex, MutexGuard, atomic::{AtomicU32, Ordering}};
+
+/// The reference counted pointer used for shared data
+///
+/// [`std::rc::Rc`] by default, [`std::sync::Arc`] with the `send` feature.
+/// Use this when setting [`crate::History::clock`].
+#[cfg(not(feature = "send"))]
+pub use std::rc::Rc as Shared;
+/// The reference counted pointer used for shared data
+///
+/// [`std::rc::Rc`] by default, [`std::sync::Arc`] with the `send` feature.
+/// Use this when setting [`crate::History::clock`].
+#[cfg(feature = "send")]
+pub use std::sync::Arc as Shared;
+
+/// Requires `Send + Sync` with the `send` feature, else nothing
+///
+/// A supertrait of the traits whose implementations `Ed` holds, such as
+/// [`crate::io::IO`] and [`crate::plugins::Plugin`], so `Ed` can be `Send`
+/// with the feature. Implemented for all types fulfilling the requirements.
+#[cfg(not(feature = "send"))]
+pub trait MaybeSend {}
+#[cfg(not(feature = "send"))]
+impl<T: ?Sized> MaybeSend for T {}
+/// Requires `Send + Sync` with the `send` feature, else nothing
+///
+/// A supertrait of the traits whose implementations `Ed` holds, such as
+/// [`crate::io::IO`] and [`crate::plugins::Plugin`], so `Ed` can be `Send`
+/// with the feature. Implemented for all types fulfilling the requirements.
+#[cfg(feature = "send")]
+pub trait MaybeSend: Send + Sync {}
+#[cfg(feature = "send")]
+impl<T: Send + Sync + ?Sized> MaybeSend for T {}
+
+// A char which can be changed through a shared reference
+#[derive(Debug)]
+pub(crate) struct CharCell(
+  #[cfg(not(feature = "send"))]
+  Cell<char>,
+  #[cfg(feature = "send")]
+  AtomicU32,
+);
+impl CharCell {
+  pub(crate) fn new(c: char) -> Self {
+    #[cfg(not(feature = "send"))]
+    { Self(Cell::new(c)) }
+    #[cfg(feature = "send")]
+    { Self(AtomicU32::new(c.into())) }
+  }
+  pub(crate) fn get(&self) -> char {
+    #[cfg(not(feature = "send"))]
+    { self.0.get() }
+    // Only ever set from a char, so always valid
+    #[cfg(feature = "send")]
+    { char::from_u32(self.0.load(Ordering::Relaxed)).unwrap_or('\0') }
+  }
+  pub(crate) fn set(&self, c: char) {
+    #[cfg(not(feature = "send"))]
+    { self.0.set(c) }
+    #[cfg(feature = "send")]
+    { self.0.store(c.into(), Ordering::Relaxed) }
+  }
+}
+impl PartialEq for CharCell {
+  fn eq(&self, other: &Self) -> bool {
+    self.get() == other.get()
+  }
+}
+impl Eq for CharCell {}
+
+// Data which can be modified through a shared reference
+#[derive(Debug, Default)]
+pub(crate) struct Lock<T>(
+  #[cfg(not(feature = "send"))]
+  RefCell<T>,
+  #[cfg(feature = "send")]
+  Mutex<T>,
+);
+impl<T> Lock<T> {
+  pub(crate) fn new(data: T) -> Self {
+    #[cfg(not(feature = "send"))]
+    { Self(RefCell::new(data)) }
+    #[cfg(feature = "send")]
+    { Self(Mutex::new(data)) }
+  }
+  // Panics if already locked (by this thread), same as RefCell::borrow_mut
+  #[cfg(not(feature = "send"))]
+  pub(crate) fn lock(&self) -> RefMut<'_, T> {
+    self.0.borrow_mut()
+  }
+  // A panic while locked cannot leave the data invalid, so ignore poisoning
+  #[cfg(feature = "send")]
+  pub(crate) fn lock(&self) -> MutexGuard<'_, T> {
+    self.0.lock().unwrap_or_else(|e| e.into_inner())
+  }
+}
+// Compares the data, like RefCell does
+impl<T: PartialEq> PartialEq for Lock<T> {
+  fn eq(&self, other: &Self) -> bool {
+    // Locking the same Mutex twice would deadlock
+    std::ptr::eq(self, other) || *self.lock() == *other.lock()
+  }
+}
+impl<T: Eq> Eq for Lock<T> {}
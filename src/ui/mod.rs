@@ -73,6 +73,15 @@ pub trait UI {
     literal: bool,
   ) -> Result<()>;
 
+  /// Called after a command switched which buffer is active
+  ///
+  /// For UIs that show which buffer is being edited, the new buffer's name is
+  /// in `ed.buffer_name`. Default implementation does nothing.
+  fn buffer_switched(&mut self,
+    _ed: &Ed,
+  ) -> Result<()> {
+    Ok(())
+  }
   /// Prepare UI before handing down stdin/out/err to child process
   ///
   /// The returned UIHandle should hold a mutable reference to its parent UI.
@@ -96,6 +96,12 @@ impl <'a> UI for ScriptedUI<'a> {
       None => Ok(()),
     }
   }
+  fn buffer_switched(&mut self, ed: &Ed) -> Result<()> {
+    match &mut self.print_ui {
+      Some(ui) => ui.buffer_switched(ed),
+      None => Ok(()),
+    }
+  }
   fn print_command_documentation(&mut self) -> Result<()> {
     match &mut self.print_ui {
       Some(ui) => ui.print_command_documentation(),
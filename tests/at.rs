@@ -0,0 +1,120 @@
+// Tests for '@' command
+
+mod shared;
+use shared::fixtures::{
+  ErrorTest,
+};
+use shared::mock_ui::{Print, MockUI};
+use shared::dummy_io::DummyIO;
+use add_ed::{
+  ui::{UI, ScriptedUI},
+  plugins::Plugin,
+  Ed,
+  Line,
+  error::{EdError, Result},
+};
+
+// Verify behaviour of '@' command
+//
+// - Takes an optional selection, handed to the plugin after interpretation
+// - Requires first argument: name of a registered plugin
+// - Everything after the name and a separating space is handed to the plugin
+// - Modifications made by the plugin are squashed into one snapshot, labeled
+//   with the invoking command (removed if no modification was made)
+// - Errors if the plugin isn't registered or if the plugin errors
+
+// Replaces the selected lines with upper case versions, prints the arguments
+struct Upper {
+  runs: usize,
+}
+impl Plugin for Upper {
+  fn run(&mut self,
+    ed: &mut Ed<'_>,
+    ui: &mut dyn UI,
+    selection: Option<(usize, usize)>,
+    arguments: &str,
+  ) -> Result<()> {
+    self.runs += 1;
+    let sel = selection.unwrap_or(ed.selection);
+    ed.history.current().verify_selection(sel)?;
+    let buffer = ed.history.current_mut("unused label".into());
+    let mut tail = buffer.split_off(sel.1);
+    let selected = buffer.split_off(sel.0 - 1);
+    for line in selected.iter() {
+      let upper: Line = line.text.to_uppercase()[..].try_into().unwrap();
+      buffer.push(upper);
+    }
+    buffer.append(&mut tail);
+    ed.selection = sel;
+    ui.print_message(arguments)
+  }
+}
+
+fn run(
+  plugin: &mut dyn Plugin,
+  commands: &[&str],
+) -> (Vec<String>, Vec<String>, Vec<Print>) {
+  let mut io = DummyIO::new();
+  let macros = std::collections::HashMap::new();
+  let mut inner_ui = MockUI{ prints_history: Vec::new() };
+  let mut ed = Ed::new(
+    &mut io,
+    &macros,
+  ).with_plugin("upper", plugin);
+  ed.history.current_mut("initial load".into()).extend(
+    ["a\n", "b\n", "c\n"].iter().map(|x| (*x).try_into().unwrap())
+  );
+  ed.selection = (1, 3);
+  {
+    let mut ui = ScriptedUI{
+      print_ui: Some(&mut inner_ui),
+      input: commands.iter().map(|x| format!("{}\n", x)).collect(),
+    };
+    for _ in commands {
+      ed.get_and_run_command(&mut ui).expect("Error running test");
+    }
+  }
+  (
+    ed.history.current().iter().map(|l| l.text.to_string()).collect(),
+    ed.history.snapshots()[2..].iter().map(|x| x.0.clone()).collect(),
+    inner_ui.prints_history,
+  )
+}
+
+#[test]
+fn plugin() {
+  let mut plugin = Upper{ runs: 0 };
+  let (buffer, labels, prints) = run(&mut plugin, &["2,3@upper some args"]);
+  assert_eq!(buffer, vec!["a\n", "B\n", "C\n"]);
+  assert_eq!(labels, vec!["2,3@upper some args"]);
+  assert_eq!(prints, vec![Print{
+    text: vec!["some args".to_owned()],
+    n: false,
+    l: false,
+  }]);
+  assert_eq!(plugin.runs, 1);
+}
+
+// Closures are plugins too, and no snapshot remains if nothing was modified
+#[test]
+fn plugin_closure_nomodification() {
+  let mut plugin = |ed: &mut Ed<'_>, _: &mut dyn UI, sel, args: &str| {
+    assert_eq!(sel, None);
+    assert_eq!(args, "");
+    ed.selection = (2, 2);
+    Ok(())
+  };
+  let (buffer, labels, prints) = run(&mut plugin, &["@upper"]);
+  assert_eq!(buffer, vec!["a\n", "b\n", "c\n"]);
+  assert!(labels.is_empty(), "Plugin without modification left a snapshot.");
+  assert!(prints.is_empty());
+}
+
+#[test]
+fn plugin_undefined() {
+  ErrorTest{
+    init_buffer: vec!["a"],
+    command_input: vec!["@missing arg"],
+    expected_error: EdError::PluginUndefined("missing".to_owned()),
+  }.run()
+}
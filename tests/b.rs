@@ -0,0 +1,173 @@
+// Tests for 'b' command
+// Cross buffer 't' and 'm' are tested here as well, since they need buffers
+
+mod shared;
+use shared::fixtures::{
+  ErrorTest,
+};
+use shared::mock_ui::{Print, MockUI};
+use shared::dummy_io::DummyIO;
+use add_ed::{
+  ui::ScriptedUI,
+  Ed,
+  error::EdError,
+  macros::Macro,
+};
+
+// Verify behaviour of 'b' command
+//
+// - Doesn't allow selection
+// - Without argument lists open buffers, marking the active one with '>' and
+//   unsaved ones with "(unsaved)"
+// - `b <name>` switches to the named buffer, opening a new empty buffer if none
+//   has that name
+// - `bq (<name>)` closes the named buffer (default active), erroring if unsaved
+//   or if it is the only open buffer. `bQ` closes even if unsaved.
+// - Each buffer has its own history, file and selection, the clipboard is shared
+// - `t` and `m` accept `@<name>:` before their index, to transfer into the named
+//   buffer. The index is interpreted in that buffer.
+// - `q` errors if any open buffer is unsaved
+// - Switching buffer in a macro doesn't leave snapshotting disabled in the
+//   buffer the macro started in
+
+fn text(ed: &Ed) -> Vec<String> {
+  ed.history.current().iter().map(|l| l.text.trim_end().to_owned()).collect()
+}
+
+// Runs the commands on an editor with "a", "b" and "c" saved in buffer main
+fn run(commands: &[&str], check: impl FnOnce(&mut Ed, Vec<Print>)) {
+  let mut io = DummyIO::new();
+  let macros = std::collections::HashMap::new();
+  let mut inner_ui = MockUI{ prints_history: Vec::new() };
+  let mut ed = Ed::new(
+    &mut io,
+    &macros,
+  );
+  ed.file = "path".to_owned();
+  ed.history.current_mut("initial load".into()).extend(
+    ["a\n", "b\n", "c\n"].iter().map(|x| (*x).try_into().unwrap())
+  );
+  ed.history.set_saved();
+  ed.selection = (1, 3);
+  {
+    let mut ui = ScriptedUI{
+      print_ui: Some(&mut inner_ui),
+      input: commands.iter().map(|x| format!("{}\n", x)).collect(),
+    };
+    for _ in commands {
+      ed.get_and_run_command(&mut ui).expect("Error running test");
+    }
+  }
+  check(&mut ed, inner_ui.prints_history);
+}
+
+// Open a buffer, edit it, switch back and list
+#[test]
+fn buffers() {
+  run(&["b notes", "a", "note", ".", "b main", "b"], |ed, prints| {
+    assert_eq!(ed.buffer_name, "main");
+    assert_eq!(ed.selection, (1, 3), "Selection wasn't kept per buffer.");
+    assert_eq!(text(ed), vec!["a", "b", "c"]);
+    assert_eq!(
+      prints,
+      vec![Print{
+        text: vec!["> main\tpath\n  notes\t- (unsaved)".to_owned()],
+        n: false,
+        l: false,
+      }],
+    );
+    ed.switch_buffer("notes").unwrap();
+    assert_eq!(text(ed), vec!["note"]);
+    assert_eq!(ed.history.snapshots()[1].0, "a");
+  });
+}
+
+// The clipboard is shared, so 'x' pastes what was cut in another buffer
+#[test]
+fn buffers_shared_clipboard() {
+  run(&["2d", "b notes", "x"], |ed, _| {
+    assert_eq!(text(ed), vec!["b"]);
+  });
+}
+
+// Copy and move lines into another buffer
+#[test]
+fn buffers_transfer() {
+  run(&["b notes", "b main", "1,2t@notes:", "2m@notes:0"], |ed, _| {
+    assert_eq!(text(ed), vec!["a", "c"]);
+    assert_eq!(ed.selection, (2, 2));
+    ed.switch_buffer("notes").unwrap();
+    assert_eq!(text(ed), vec!["b", "a", "b"]);
+    assert_eq!(ed.selection, (1, 1));
+    assert_eq!(
+      ed.history.snapshots()[1..].iter().map(|x| &x.0[..]).collect::<Vec<_>>(),
+      vec!["1,2t@notes:", "2m@notes:0"],
+    );
+  });
+}
+
+// Closing buffers, with and without force
+#[test]
+fn buffers_close() {
+  run(&["b notes", "a", "note", ".", "bQ", "b other", "bq"], |ed, _| {
+    assert_eq!(ed.buffer_name, "main");
+    assert!(ed.buffers.is_empty(), "Closed buffers remain open.");
+    assert_eq!(ed.close_buffer("main", false), Err(EdError::BufferLastOpen));
+  });
+}
+
+// Unsaved buffers prevent 'q' and 'bq'
+#[test]
+fn buffers_unsaved() {
+  run(&["b notes", "a", "note", ".", "b main"], |ed, _| {
+    let mut ui = ScriptedUI{ print_ui: None, input: Default::default() };
+    assert_eq!(ed.run_command(&mut ui, "q\n"), Err(EdError::UnsavedChanges));
+    assert_eq!(ed.run_command(&mut ui, "bq notes\n"), Err(EdError::UnsavedChanges));
+  });
+}
+
+// Invalid name
+#[test]
+fn buffers_invalid_name() {
+  ErrorTest{
+    init_buffer: vec!["a"],
+    command_input: vec!["b bad:name"],
+    expected_error: EdError::BufferNameInvalid("bad:name".to_owned()),
+  }.run()
+}
+
+// Transfer into buffer that isn't open
+#[test]
+fn buffers_transfer_unknown() {
+  ErrorTest{
+    init_buffer: vec!["a"],
+    command_input: vec!["1t@notes:"],
+    expected_error: EdError::BufferUnknown("notes".to_owned()),
+  }.run()
+}
+
+// Snapshotting is re-enabled in the buffer the macro was run in
+#[test]
+fn buffers_switch_in_macro() {
+  let mut io = DummyIO::new();
+  let mut macros = std::collections::HashMap::new();
+  macros.insert("switch", Macro::new("1d\nb notes"));
+  let mut ed = Ed::new(
+    &mut io,
+    &macros,
+  );
+  ed.history.current_mut("initial load".into()).extend(
+    ["a\n", "b\n", "c\n"].iter().map(|x| (*x).try_into().unwrap())
+  );
+  let mut ui = ScriptedUI{ print_ui: None, input: Default::default() };
+  ed.run_command(&mut ui, ":switch\n").unwrap();
+  assert_eq!(ed.buffer_name, "notes");
+  ed.run_command(&mut ui, "b main\n").unwrap();
+  assert!(!ed.history.dont_snapshot, "Snapshotting left disabled.");
+  ed.run_command(&mut ui, "1d\n").unwrap();
+  ed.run_command(&mut ui, "1d\n").unwrap();
+  assert_eq!(
+    ed.history.snapshots()[1..].iter().map(|x| &x.0[..]).collect::<Vec<_>>(),
+    vec!["initial load", ":switch", "1d", "1d"],
+  );
+}
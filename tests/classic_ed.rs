@@ -0,0 +1,60 @@
+// Tests for running the classic-ed binary as a script, as GNU Ed
+#![cfg(feature = "bin_deps")]
+
+use std::io::Write;
+use std::process::{Command, Stdio, Output};
+use std::path::PathBuf;
+
+// Verify behaviour of classic-ed running scripts
+//
+// - Input that isn't a terminal, such as a pipe, is run as a script which
+//   quits at the first error with exit status 1
+// - Failing to open the file given exits with status 1 in a script (0 with
+//   --loose-exit-status)
+
+// Runs classic-ed in a new directory holding `file`, piping in the script
+fn run(test: &str, args: &[&str], script: &str) -> (Output, PathBuf) {
+  let dir = std::env::temp_dir()
+    .join(format!("classic-ed-{}-{}", test, std::process::id()))
+  ;
+  std::fs::create_dir_all(&dir).unwrap();
+  std::fs::write(dir.join("file"), "a\nb\n").unwrap();
+  let mut child = Command::new(env!("CARGO_BIN_EXE_classic-ed"))
+    .args(args)
+    .current_dir(&dir)
+    // To not use any config of the user running the tests
+    .env("XDG_CONFIG_HOME", &dir)
+    .env("XDG_CONFIG_DIRS", "")
+    .stdin(Stdio::piped())
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped())
+    .spawn()
+    .unwrap()
+  ;
+  // Ignore errors, as it may quit before reading the script
+  let _ = child.stdin.take().unwrap().write_all(script.as_bytes());
+  (child.wait_with_output().unwrap(), dir)
+}
+
+#[test]
+fn classic_ed_piped_script() {
+  let (output, dir) = run("piped", &["-s", "file"], "1d\n5d\n,d\nw\nq\n");
+  assert_eq!(output.status.code(), Some(1));
+  assert_eq!(String::from_utf8_lossy(&output.stdout), "?\n");
+  assert_eq!(
+    std::fs::read_to_string(dir.join("file")).unwrap(),
+    "a\nb\n",
+    "The script continued after the error.",
+  );
+  std::fs::remove_dir_all(dir).unwrap();
+}
+
+// A directory can't be read, unlike a missing file which is a new file
+#[test]
+fn classic_ed_unreadable_file() {
+  let (output, dir) = run("unreadable", &["-s", "."], "q\n");
+  assert_eq!(output.status.code(), Some(1));
+  let (output, _) = run("unreadable", &["-s", "-l", "."], "q\n");
+  assert_eq!(output.status.code(), Some(0));
+  std::fs::remove_dir_all(dir).unwrap();
+}
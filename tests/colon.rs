@@ -23,6 +23,7 @@ use add_ed::{
 //   substitution via $<argument nr>
 // - Errors if macro execution errors or if nr of given arguments doesn't match
 //   the nr of arguments the macro accepts.
+// - If the macro is a transaction and errors, all its changes are rolled back
 
 
 fn create_macro_store() -> std::collections::HashMap<&'static str, Macro> {
@@ -31,6 +32,7 @@ fn create_macro_store() -> std::collections::HashMap<&'static str, Macro> {
   store.insert("append_word", Macro::new(",a\n$1\n.").nr_arguments(NrArguments::Exactly(1)));
   store.insert("append_words", Macro::new(",a\n$0\n."));
   store.insert("recursion", Macro::new(":recursion").nr_arguments(NrArguments::Exactly(0)));
+  store.insert("failing", Macro::new("1d\n1t1\n9p").transaction(true));
   store
 }
 
@@ -108,3 +110,16 @@ fn macro_recursion() {
     expected_error: EdError::InfiniteRecursion,
   }.run();
 }
+
+// The changes before the error are rolled back, leaving no snapshot
+#[test]
+fn macro_transaction() {
+  MacroErrorTest{
+    init_buffer: vec!["a","b"],
+    // We use a standard macro store
+    macro_store: create_macro_store(),
+    // and specify which macro to test in each test
+    macro_invocation: ":failing",
+    expected_error: EdError::IndexTooBig{index: 9, buffer_len: 2},
+  }.run();
+}
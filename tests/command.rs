@@ -0,0 +1,165 @@
+// Tests for parse_command and Ed::execute
+
+mod shared;
+use shared::dummy_io::DummyIO;
+use add_ed::{
+  ui::ScriptedUI,
+  Ed,
+  Command,
+  Sel,
+  Ind,
+  PrintingFlags,
+  Substitution,
+  parse_command,
+  error::EdError,
+};
+
+// Verify behaviour of parse_command and Ed::execute
+//
+// - parse_command executes nothing, and errors for what can be seen without
+//   editor state
+// - Displaying a parsed command gives text which parses into the same command
+// - Ed::execute runs the command, labeling snapshots with its text
+// - Ed::execute refuses commands with newlines in their fields, so they can't
+//   inject more commands
+
+// Every command should survive being printed and parsed again
+#[test]
+fn command_roundtrip() {
+  let commands = [
+    "", "3", ",", "'a;/b/+2", "?c?-", "q", "Q", "h", "help", "H", "Help", ".=",
+    "1,2#comment", "Pnl", "b", "b notes", "bq", "bQ notes", "o", "o n",
+    "o name=a b", "set", "set name = a", "f", "f path", "!ls %", "2,3|sort", "e", "E !cat x",
+    "$r file", "w", "W file", "wq", "1,2w !wc", "w q", "2pl", "n", "z", "5Z2n",
+    "a", "ip", "A", "In", "c", "dn", "y", "x", "Xl", "u", "u@10m", "U",
+    "U:name", "k", "Ka", "1,2t", "m0p", "t@notes:", "m@notes:$-1", "j", "s",
+    "s/a/b/", "s|a/b|c\\|d|gpn", "g/re/", "g/re/p", "v/re/s|a|b|/p/", "G/re/",
+    "V#re#", ":name", "1,2:name a  b", "@plugin", "@plugin some args", "O",
+    "2Ofoo",
+  ];
+  for text in commands {
+    let command = parse_command(text)
+      .unwrap_or_else(|e| panic!("Failed to parse `{}`: {:?}", text, e));
+    let printed = command.to_string();
+    assert_eq!(
+      parse_command(&printed),
+      Ok(command),
+      "`{}` was printed as `{}`, which parsed differently.",
+      text,
+      printed,
+    );
+  }
+}
+
+#[test]
+fn command_parse() {
+  assert_eq!(
+    parse_command("'a,/b/+2s|a/b|c|gp\n"),
+    Ok(Command::Substitute{
+      selection: Some(Sel::Pair(
+        Ind::Tag('a'),
+        Ind::Add(Box::new(Ind::Pattern("b".to_owned())), 2),
+      )),
+      arguments: Some(Substitution{
+        pattern: "a/b".to_owned(),
+        substitute: "c".to_owned(),
+        global: true,
+        p: true,
+        n: false,
+        l: false,
+      }),
+    }),
+  );
+  assert_eq!(
+    parse_command("t@notes:3n"),
+    Ok(Command::Transfer{
+      selection: None,
+      copy: true,
+      buffer: Some("notes".to_owned()),
+      index: Some(Ind::Literal(3)),
+      flags: PrintingFlags{ p: false, n: true, l: false },
+    }),
+  );
+  // The escaped separator is printed with escape again
+  assert_eq!(
+    parse_command("s|a/b|c|gp").unwrap().to_string(),
+    "s/a\\/b/c/gp",
+  );
+}
+
+#[test]
+fn command_parse_errors() {
+  assert_eq!(parse_command("1q"), Err(EdError::SelectionForbidden));
+  assert_eq!(parse_command("dx"), Err(EdError::FlagUndefined('x')));
+  assert_eq!(parse_command("z0"), Err(EdError::NoOp));
+  assert_eq!(parse_command("kab"), Err(EdError::TagInvalid("ab".to_owned())));
+  assert_eq!(parse_command("'"), Err(EdError::IndexUnfinished("'".to_owned())));
+  assert_eq!(
+    parse_command("s/a/b"),
+    Err(EdError::ArgumentsWrongNr{ expected: "none or 3".into(), received: 2 }),
+  );
+}
+
+// Executing a built command runs it, labeling the snapshot with its text
+#[test]
+fn command_execute() {
+  let mut io = DummyIO::new();
+  let macros = std::collections::HashMap::new();
+  let mut ed = Ed::new(
+    &mut io,
+    &macros,
+  );
+  ed.history.current_mut("initial load".into()).extend(
+    ["a\n", "b\n", "c\n"].iter().map(|x| (*x).try_into().unwrap())
+  );
+  let mut ui = ScriptedUI{ print_ui: None, input: Default::default() };
+  ed.execute(&mut ui, Command::Cut{
+    selection: Some(Sel::Pair(Ind::Literal(1), Ind::Literal(2))),
+    flags: PrintingFlags::default(),
+  }).expect("Error running test");
+  assert_eq!(
+    ed.history.current().iter().map(|l| &l.text[..]).collect::<Vec<_>>(),
+    vec!["c\n"],
+  );
+  assert_eq!(ed.history.snapshots()[2].0, "1,2d");
+  assert_eq!(ed.execute(&mut ui, Command::Quit{ force: true }), Ok(true));
+}
+
+// Newlines in fields of a built command error instead of running anything
+#[test]
+fn command_execute_newline() {
+  let mut io = DummyIO::new();
+  let macros = std::collections::HashMap::new();
+  let mut ed = Ed::new(
+    &mut io,
+    &macros,
+  );
+  ed.history.current_mut("initial load".into()).extend(
+    ["a\n", "b\n", "c\n"].iter().map(|x| (*x).try_into().unwrap())
+  );
+  let mut ui = ScriptedUI{ print_ui: None, input: Default::default() };
+  let commands = [
+    Command::Comment{ selection: None, text: "x\n,d".into() },
+    Command::SwitchBuffer{ name: "notes\n,d".into() },
+    Command::Global{
+      selection: None,
+      inverse: false,
+      separator: '/',
+      pattern: "a".into(),
+      commands: vec!["p\n,d".into(), "".into()],
+    },
+  ];
+  for command in commands {
+    let text = command.to_string();
+    assert_eq!(
+      ed.execute(&mut ui, command),
+      Err(EdError::CommandNewline(text)),
+    );
+  }
+  assert_eq!(
+    ed.history.current().iter().map(|l| &l.text[..]).collect::<Vec<_>>(),
+    vec!["a\n", "b\n", "c\n"],
+  );
+  assert_eq!(ed.buffer_name, "main");
+  assert_eq!(ed.history.len(), 2, "Refused commands created snapshots.");
+}
@@ -0,0 +1,131 @@
+// Tests for the typed editing API on Ed
+
+mod shared;
+use shared::dummy_io::DummyIO;
+use shared::dummy_ui::DummyUI;
+use add_ed::{
+  Ed,
+  EditSummary,
+  PubLine,
+  error::EdError,
+};
+
+// Verify behaviour of the typed editing API
+//
+// - Each method modifies the buffer, selection and clipboard like the
+//   matching command
+// - Each creates one snapshot, labeled as the matching command
+// - Invalid selections and indices error without modifying anything
+
+fn lines(texts: &[&str]) -> Vec<PubLine> {
+  texts.iter().map(|t| (*t).try_into().unwrap()).collect()
+}
+
+// Runs the check on an editor with "a", "b", "c" and "d" in its buffer
+fn run(check: impl FnOnce(&mut Ed)) {
+  let mut io = DummyIO::new();
+  let macros = std::collections::HashMap::new();
+  let mut ed = Ed::new(
+    &mut io,
+    &macros,
+  );
+  ed.history.current_mut("initial load".into()).extend(
+    ["a\n", "b\n", "c\n", "d\n"].iter().map(|x| (*x).try_into().unwrap())
+  );
+  ed.selection = (1, 4);
+  check(&mut ed);
+}
+
+fn text(ed: &Ed) -> Vec<String> {
+  ed.history.current().iter().map(|l| l.text.trim_end().to_owned()).collect()
+}
+fn clipboard(ed: &Ed) -> Vec<String> {
+  ed.clipboard.iter().map(|l| l.text.trim_end().to_owned()).collect()
+}
+fn labels(ed: &Ed) -> Vec<String> {
+  ed.history.snapshots()[2..].iter().map(|s| s.0.clone()).collect()
+}
+
+#[test]
+fn api_insert_lines() {
+  run(|ed| {
+    let res = ed.insert_lines(1, &lines(&["x\n", "y\n"])).unwrap();
+    assert_eq!(res, EditSummary{ selection: (2, 3), removed: 0, added: 2 });
+    assert_eq!(text(ed), vec!["a", "x", "y", "b", "c", "d"]);
+    assert_eq!(ed.selection, (2, 3));
+    assert_eq!(labels(ed), vec!["1a"]);
+  });
+}
+
+#[test]
+fn api_delete() {
+  run(|ed| {
+    let res = ed.delete((2, 3)).unwrap();
+    assert_eq!(res, EditSummary{ selection: (2, 2), removed: 2, added: 0 });
+    assert_eq!(text(ed), vec!["a", "d"]);
+    assert_eq!(clipboard(ed), vec!["b", "c"]);
+    assert_eq!(labels(ed), vec!["2,3d"]);
+  });
+}
+
+#[test]
+fn api_replace() {
+  run(|ed| {
+    let res = ed.replace((1, 2), &lines(&["x\n"])).unwrap();
+    assert_eq!(res, EditSummary{ selection: (1, 1), removed: 2, added: 1 });
+    assert_eq!(text(ed), vec!["x", "c", "d"]);
+    assert_eq!(clipboard(ed), vec!["a", "b"]);
+    assert_eq!(labels(ed), vec!["1,2c"]);
+  });
+}
+
+#[test]
+fn api_substitute() {
+  run(|ed| {
+    let res = ed.substitute((1, 3), r"b\n", "b/", true).unwrap();
+    assert_eq!(res, EditSummary{ selection: (1, 2), removed: 3, added: 2 });
+    assert_eq!(text(ed), vec!["a", "b/c", "d"]);
+    assert_eq!(clipboard(ed), vec!["a", "b", "c"]);
+    assert_eq!(labels(ed), vec![r"1,3s/b\n/b\//g"]);
+    // No match errors without a snapshot
+    assert_eq!(
+      ed.substitute((1, 1), "q", "", false),
+      Err(EdError::RegexNoMatch("q".to_owned())),
+    );
+    assert_eq!(labels(ed).len(), 1);
+    // A lone 's' repeats the last successful substitution
+    ed.substitute((1, 1), "[a-z]", "X", false).unwrap();
+    ed.run_command(&mut DummyUI{}, "3s\n").unwrap();
+    assert_eq!(text(ed), vec!["X", "b/c", "X"]);
+  });
+}
+
+#[test]
+fn api_move_copy() {
+  run(|ed| {
+    let res = ed.move_lines((1, 2), 4).unwrap();
+    assert_eq!(res, EditSummary{ selection: (3, 4), removed: 0, added: 0 });
+    assert_eq!(text(ed), vec!["c", "d", "a", "b"]);
+    let res = ed.copy_lines((1, 1), 0).unwrap();
+    assert_eq!(res, EditSummary{ selection: (1, 1), removed: 0, added: 1 });
+    assert_eq!(text(ed), vec!["c", "c", "d", "a", "b"]);
+    assert_eq!(labels(ed), vec!["1,2m4", "1,1t0"]);
+    assert_eq!(ed.move_lines((1, 3), 2), Err(EdError::NoOp));
+  });
+}
+
+#[test]
+fn api_invalid_selection() {
+  run(|ed| {
+    assert_eq!(
+      ed.delete((3, 5)),
+      Err(EdError::IndexTooBig{ index: 5, buffer_len: 4 }),
+    );
+    assert_eq!(
+      ed.insert_lines(5, &lines(&["x\n"])),
+      Err(EdError::IndexTooBig{ index: 5, buffer_len: 4 }),
+    );
+    assert_eq!(text(ed), vec!["a", "b", "c", "d"]);
+    assert!(labels(ed).is_empty());
+  });
+}
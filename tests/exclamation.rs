@@ -17,6 +17,9 @@ use shared::fake_io::{
 //   (stdin, stdout, stderr passed in)
 // - Selection after is unmodified
 // - Doesn't modify state.path
+// - Substitutes % with state.path and ! with the previous command, shell
+//   quoted if the shell_quote option is set, and %% resp. !! with them as is if
+//   quoting (else twice). A lone ! re-runs the previous command as is.
 
 // Function to set up the "filesystem" for these tests
 fn test_io() -> FakeIO {
@@ -33,6 +36,41 @@ fn test_io() -> FakeIO {
         },
         "hi\n".to_owned(),
       ),
+      (
+        ShellCommand{
+          command:"wc 'a b; rm -rf x'".to_owned(),
+          input: String::new(),
+        },
+        String::new(),
+      ),
+      (
+        ShellCommand{
+          command:"wc a b; rm -rf x".to_owned(),
+          input: String::new(),
+        },
+        String::new(),
+      ),
+      (
+        ShellCommand{
+          command:"echo 'echo hi'".to_owned(),
+          input: String::new(),
+        },
+        "echo hi\n".to_owned(),
+      ),
+      (
+        ShellCommand{
+          command:"wc a b; rm -rf xa b; rm -rf x".to_owned(),
+          input: String::new(),
+        },
+        String::new(),
+      ),
+      (
+        ShellCommand{
+          command:"wc 'it'\\''s \"$(here)\"'".to_owned(),
+          input: String::new(),
+        },
+        String::new(),
+      ),
       (
         ShellCommand{
           command:"sort -n".to_owned(),
@@ -66,3 +104,105 @@ fn shell_escape() {
     expected_filepath: "text",
   }.run();
 }
+
+// Paths with spaces and shell metacharacters are quoted
+#[test]
+fn shell_escape_quoted_path() {
+  let test_io = test_io();
+  IOTest{
+    init_buffer: vec!["text"],
+    init_io: test_io.clone(),
+    init_clipboard: vec!["dummy"],
+    init_filepath: "a b; rm -rf x",
+    command_input: vec![
+      "o shell_quote=true",
+      "!wc %",
+    ],
+    expected_buffer: vec![
+      "text",
+    ],
+    expected_buffer_saved: true,
+    expected_selection: (1,1),
+    expected_file_changes: vec![], // No changes to the fs
+    expected_clipboard: vec!["dummy"],
+    expected_filepath: "a b; rm -rf x",
+  }.run();
+}
+
+// Quotes in paths are escaped
+#[test]
+fn shell_escape_quoted_quotes() {
+  let test_io = test_io();
+  IOTest{
+    init_buffer: vec!["text"],
+    init_io: test_io.clone(),
+    init_clipboard: vec!["dummy"],
+    init_filepath: "it's \"$(here)\"",
+    command_input: vec![
+      "o shell_quote=true",
+      "!wc %",
+    ],
+    expected_buffer: vec![
+      "text",
+    ],
+    expected_buffer_saved: true,
+    expected_selection: (1,1),
+    expected_file_changes: vec![], // No changes to the fs
+    expected_clipboard: vec!["dummy"],
+    expected_filepath: "it's \"$(here)\"",
+  }.run();
+}
+
+// %% and unsetting shell_quote substitute the path as is, but without quoting
+// %% is the path twice
+#[test]
+fn shell_escape_raw_path() {
+  let test_io = test_io();
+  IOTest{
+    init_buffer: vec!["text"],
+    init_io: test_io.clone(),
+    init_clipboard: vec!["dummy"],
+    init_filepath: "a b; rm -rf x",
+    command_input: vec![
+      "o shell_quote=true",
+      "!wc %%",
+      "o shell_quote=false",
+      "!wc %",
+      "!wc %%",
+    ],
+    expected_buffer: vec![
+      "text",
+    ],
+    expected_buffer_saved: true,
+    expected_selection: (1,1),
+    expected_file_changes: vec![], // No changes to the fs
+    expected_clipboard: vec!["dummy"],
+    expected_filepath: "a b; rm -rf x",
+  }.run();
+}
+
+// The previous command is quoted when substituted, unless re-run alone
+#[test]
+fn shell_escape_quoted_command() {
+  let test_io = test_io();
+  IOTest{
+    init_buffer: vec!["text"],
+    init_io: test_io.clone(),
+    init_clipboard: vec!["dummy"],
+    init_filepath: "text",
+    command_input: vec![
+      "o shell_quote=true",
+      "!echo hi",
+      "!echo !",
+      "!!",
+    ],
+    expected_buffer: vec![
+      "text",
+    ],
+    expected_buffer_saved: true,
+    expected_selection: (1,1),
+    expected_file_changes: vec![], // No changes to the fs
+    expected_clipboard: vec!["dummy"],
+    expected_filepath: "text",
+  }.run();
+}
@@ -0,0 +1,111 @@
+// Tests for the CommandExtender fallback
+
+mod shared;
+use shared::mock_ui::{Print, MockUI};
+use shared::dummy_io::DummyIO;
+use add_ed::{
+  ui::{UI, ScriptedUI},
+  plugins::CommandExtender,
+  Ed,
+  Line,
+  Sel,
+  PrintingFlags,
+  error::{EdError, Result},
+};
+
+// Verify behaviour of the command extender
+//
+// - Is given commands add-ed doesn't recognize, with the selection as parsed
+//   and the tail without newline
+// - May modify the editor state and set printing flags, which are applied as
+//   for built-in commands
+// - Commands it doesn't claim error with CommandUndefined
+
+// Claims 'Y', which duplicates the selected lines after the selection
+struct Duplicate {
+  calls: Vec<(char, String)>,
+}
+impl CommandExtender for Duplicate {
+  fn run_command(&mut self,
+    ed: &mut Ed<'_>,
+    _ui: &mut dyn UI,
+    pflags: &mut PrintingFlags,
+    full_command: &str,
+    selection: Option<Sel>,
+    command: char,
+    tail: &str,
+  ) -> Result<bool> {
+    self.calls.push((command, tail.to_owned()));
+    if command != 'Y' { return Ok(false); }
+    let sel = ed.interpret_selection(selection, ed.selection)?;
+    ed.history.current().verify_selection(sel)?;
+    let buffer = ed.history.current_mut(full_command.into());
+    let mut after = buffer.split_off(sel.1);
+    let copies: Vec<Line> = buffer.iter().skip(sel.0 - 1)
+      .map(|l| l.text[..].try_into().unwrap())
+      .collect();
+    buffer.extend(copies);
+    buffer.append(&mut after);
+    ed.selection = (sel.1 + 1, 2 * sel.1 + 1 - sel.0);
+    pflags.p = tail == "p";
+    Ok(true)
+  }
+}
+
+#[test]
+fn extender() {
+  let mut io = DummyIO::new();
+  let macros = std::collections::HashMap::new();
+  let mut extender = Duplicate{ calls: Vec::new() };
+  let mut inner_ui = MockUI{ prints_history: Vec::new() };
+  {
+    let mut ed = Ed::new(
+      &mut io,
+      &macros,
+    ).with_command_extender(&mut extender);
+    ed.history.current_mut("initial load".into()).extend(
+      ["a\n", "b\n", "c\n"].iter().map(|x| (*x).try_into().unwrap())
+    );
+    let mut ui = ScriptedUI{
+      print_ui: Some(&mut inner_ui),
+      input: Default::default(),
+    };
+    ed.run_command(&mut ui, "1,2Yp\n").expect("Error running test");
+    assert_eq!(
+      ed.run_command(&mut ui, "O\n"),
+      Err(EdError::CommandUndefined('O')),
+    );
+    assert!(ed.command_extender.is_some(), "Extender wasn't given back.");
+    assert_eq!(
+      ed.history.current().iter().map(|l| &l.text[..]).collect::<Vec<_>>(),
+      vec!["a\n", "b\n", "a\n", "b\n", "c\n"],
+    );
+    assert_eq!(ed.selection, (3, 4));
+    assert_eq!(ed.history.snapshots()[2].0, "1,2Yp");
+  }
+  assert_eq!(extender.calls, vec![
+    ('Y', "p".to_owned()),
+    ('O', "".to_owned()),
+  ]);
+  assert_eq!(inner_ui.prints_history, vec![Print{
+    text: vec!["a\n".to_owned(), "b\n".to_owned()],
+    n: false,
+    l: false,
+  }]);
+}
+
+// Without an extender unknown commands error as before
+#[test]
+fn extender_unset() {
+  let mut io = DummyIO::new();
+  let macros = std::collections::HashMap::new();
+  let mut ed = Ed::new(
+    &mut io,
+    &macros,
+  );
+  let mut ui = ScriptedUI{ print_ui: None, input: Default::default() };
+  assert_eq!(
+    ed.run_command(&mut ui, "Y\n"),
+    Err(EdError::CommandUndefined('Y')),
+  );
+}
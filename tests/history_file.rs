@@ -0,0 +1,136 @@
+// Tests for storing undo history next to the edited file
+#![cfg(feature = "history_file")]
+
+use std::collections::HashMap;
+mod shared;
+use shared::fake_io::FakeIO;
+use shared::mock_ui::MockUI;
+use add_ed::{
+  error::EdError,
+  Ed,
+  UndoStyle,
+};
+
+// Verify behaviour of persistent history
+//
+// - Writing the whole buffer to the current file stores history next to it
+// - Reading the file with 'e' loads the history, viewing the saved snapshot
+// - History isn't loaded if the file no longer matches the saved snapshot
+// - Invalid history files are reported without failing 'e'
+
+fn test_io() -> FakeIO {
+  FakeIO{
+    fake_fs: HashMap::from([
+      ("dir/file".to_owned(), "a\nb\n".to_owned()),
+    ]),
+    fake_shell: HashMap::new(),
+  }
+}
+
+#[test]
+fn history_path() {
+  assert_eq!(Ed::history_path("dir/file"), "dir/.file.ed-history");
+  assert_eq!(Ed::history_path("file"), ".file.ed-history");
+}
+
+// Edit, write and reopen the file in a new editor, then undo past the reopen
+#[test]
+fn history_survives_reopen() {
+  let mut io = test_io();
+  let macros = HashMap::new();
+  let mut ui = MockUI{ prints_history: Vec::new() };
+  {
+    let mut ed = Ed::new(&mut io, &macros);
+    ed.persistent_history = true;
+    ed.run_command(&mut ui, "e dir/file\n").unwrap();
+    ed.run_command(&mut ui, "1d\n").unwrap();
+    ed.run_command(&mut ui, "w\n").unwrap();
+  }
+  assert!(
+    io.fake_fs.contains_key("dir/.file.ed-history"),
+    "Writing to the file didn't store the history."
+  );
+  let mut ed = Ed::new(&mut io, &macros);
+  ed.persistent_history = true;
+  ed.run_command(&mut ui, "e dir/file\n").unwrap();
+  assert!(ed.history.saved(), "Loading history didn't keep buffer saved.");
+  assert_eq!(
+    ed.history.snapshots()[1..].iter().map(|x| &x.0[..]).collect::<Vec<_>>(),
+    vec!["e dir/file", "1d"],
+  );
+  ed.run_command(&mut ui, "u\n").unwrap();
+  assert_eq!(
+    ed.history.current().get_lines((1, 2)).unwrap().collect::<String>(),
+    "a\nb\n",
+    "Undoing after reopening didn't restore the state from before reopening."
+  );
+}
+
+// The loaded history keeps the editor's configuration and limits
+#[test]
+fn history_keeps_configuration() {
+  let mut io = test_io();
+  let macros = HashMap::new();
+  let mut ui = MockUI{ prints_history: Vec::new() };
+  {
+    let mut ed = Ed::new(&mut io, &macros);
+    ed.persistent_history = true;
+    ed.run_command(&mut ui, "e dir/file\n").unwrap();
+    ed.run_command(&mut ui, "1d\n").unwrap();
+    ed.run_command(&mut ui, "s/b/c/\n").unwrap();
+    ed.run_command(&mut ui, "s/c/d/\n").unwrap();
+    ed.run_command(&mut ui, "w\n").unwrap();
+  }
+  let mut ed = Ed::new(&mut io, &macros);
+  ed.persistent_history = true;
+  ed.history.undo_style = UndoStyle::Tree;
+  ed.history.max_snapshots = Some(2);
+  ed.run_command(&mut ui, "e dir/file\n").unwrap();
+  assert!(ed.history.saved(), "Loading history didn't keep buffer saved.");
+  assert_eq!(ed.history.undo_style, UndoStyle::Tree);
+  assert_eq!(ed.history.max_snapshots, Some(2));
+  assert_eq!(
+    ed.history.snapshots().iter().map(|x| &x.0[..]).collect::<Vec<_>>(),
+    vec!["s/b/c/", "s/c/d/"],
+    "Loaded history wasn't pruned to the configured limit."
+  );
+}
+
+// If the file changed after history was stored the history is ignored
+#[test]
+fn history_ignored_if_file_changed() {
+  let mut io = test_io();
+  let macros = HashMap::new();
+  let mut ui = MockUI{ prints_history: Vec::new() };
+  {
+    let mut ed = Ed::new(&mut io, &macros);
+    ed.persistent_history = true;
+    ed.run_command(&mut ui, "e dir/file\n").unwrap();
+    ed.run_command(&mut ui, "w\n").unwrap();
+  }
+  io.fake_fs.insert("dir/file".to_owned(), "changed\n".to_owned());
+  let mut ed = Ed::new(&mut io, &macros);
+  ed.file = "dir/file".to_owned();
+  ed.run_command(&mut ui, "e\n").unwrap();
+  assert_eq!(ed.load_history(), Ok(false));
+  assert_eq!(ed.history.len(), 2);
+}
+
+// Invalid history is an error for the API, but only a message for 'e'
+#[test]
+fn history_invalid() {
+  let mut io = test_io();
+  io.fake_fs.insert("dir/.file.ed-history".to_owned(), "{}\n".to_owned());
+  let macros = HashMap::new();
+  let mut ui = MockUI{ prints_history: Vec::new() };
+  let mut ed = Ed::new(&mut io, &macros);
+  ed.persistent_history = true;
+  ed.run_command(&mut ui, "e dir/file\n").unwrap();
+  assert_eq!(ed.history.len(), 2);
+  match ed.load_history() {
+    Err(EdError::HistoryFileInvalid{path, ..}) => {
+      assert_eq!(path, "dir/.file.ed-history");
+    },
+    x => panic!("Expected HistoryFileInvalid, got {:?}", x),
+  }
+}
@@ -0,0 +1,170 @@
+// Tests for 'o' command
+
+mod shared;
+use shared::fixtures::{
+  ErrorTest,
+};
+use shared::mock_ui::{Print, MockUI};
+use shared::dummy_io::DummyIO;
+use add_ed::{
+  ui::ScriptedUI,
+  options::{OptionValue, CustomOption},
+  Ed,
+  error::{EdError, Result},
+};
+
+// Verify behaviour of 'o' command
+//
+// - Doesn't allow selection
+// - Without arguments lists all options, built-in options first
+// - With a name prints the named option
+// - With a name and value sets the named option, keeping its type
+//   - Whitespace around the name and value is ignored
+//   - Sets the matching field for built-in options
+//   - Calls the registered option for custom options, which may reject values
+// - Errors if the option isn't defined or the value is of the wrong type
+// - `set` is an alias for `o`
+
+// A number option which only accepts even numbers
+struct Even(usize);
+impl CustomOption for Even {
+  fn get(&self) -> OptionValue {
+    OptionValue::Number(self.0)
+  }
+  fn set(&mut self, value: OptionValue) -> Result<()> {
+    match value {
+      OptionValue::Number(x) if x % 2 == 0 => { self.0 = x; Ok(()) },
+      _ => Err(EdError::OptionValueInvalid{
+        name: "even".to_owned(),
+        value: value.to_string(),
+        expected: "even number",
+      }),
+    }
+  }
+}
+
+// Runs the commands with options "even" and "name" registered, returning
+// the prints and the final editor state as options
+fn run(
+  commands: &[&str],
+) -> (Result<()>, Vec<Print>, Vec<(String, OptionValue)>) {
+  let mut io = DummyIO::new();
+  let macros = std::collections::HashMap::new();
+  let mut inner_ui = MockUI{ prints_history: Vec::new() };
+  let mut even = Even(2);
+  let mut name = OptionValue::Text("x".to_owned());
+  let mut ed = Ed::new(
+    &mut io,
+    &macros,
+  )
+    .with_option("even", &mut even)
+    .with_option("name", &mut name)
+  ;
+  let mut res = Ok(());
+  {
+    let mut ui = ScriptedUI{
+      print_ui: Some(&mut inner_ui),
+      input: commands.iter().map(|x| format!("{}\n", x)).collect(),
+    };
+    for _ in commands {
+      res = ed.get_and_run_command(&mut ui).map(|_| ());
+      if res.is_err() { break; }
+    }
+  }
+  let options = ed.option_names().into_iter()
+    .map(|n| { let v = ed.get_option(&n).unwrap(); (n, v) })
+    .collect();
+  (res, inner_ui.prints_history, options)
+}
+
+fn message(text: &str) -> Print {
+  Print{ text: vec![text.to_owned()], n: false, l: false }
+}
+
+#[test]
+fn options_list() {
+  let (res, prints, _) = run(&["o"]);
+  assert_eq!(res, Ok(()));
+  assert_eq!(prints, vec![message(
+    "n=false\nl=false\nprint_errors=true\nquiet=false\nshell_quote=false\ncmd_prefix=:\nshow_cmd_prefix=true\nrecursion_limit=16\neven=2\nname=x"
+  )]);
+}
+
+#[test]
+fn options_get_set() {
+  let (res, prints, options) = run(&[
+    "o n=true",
+    "o cmd_prefix=none",
+    "o recursion_limit=4",
+    "o even=8",
+    "o name=some text",
+    "o even",
+    "o cmd_prefix",
+  ]);
+  assert_eq!(res, Ok(()));
+  assert_eq!(prints, vec![message("even=8"), message("cmd_prefix=none")]);
+  assert_eq!(options, vec![
+    ("n".to_owned(), OptionValue::Bool(true)),
+    ("l".to_owned(), OptionValue::Bool(false)),
+    ("print_errors".to_owned(), OptionValue::Bool(true)),
+    ("quiet".to_owned(), OptionValue::Bool(false)),
+    ("shell_quote".to_owned(), OptionValue::Bool(false)),
+    ("cmd_prefix".to_owned(), OptionValue::Char(None)),
+    ("show_cmd_prefix".to_owned(), OptionValue::Bool(true)),
+    ("recursion_limit".to_owned(), OptionValue::Number(4)),
+    ("even".to_owned(), OptionValue::Number(8)),
+    ("name".to_owned(), OptionValue::Text("some text".to_owned())),
+  ]);
+}
+
+#[test]
+fn options_set_spaced() {
+  let (res, prints, options) = run(&[
+    "o even = 6",
+    "o name =  some text ",
+    "set n=true",
+    "set even",
+  ]);
+  assert_eq!(res, Ok(()));
+  assert_eq!(prints, vec![message("even=6")]);
+  assert_eq!(options[0].1, OptionValue::Bool(true));
+  assert_eq!(options[8].1, OptionValue::Number(6));
+  assert_eq!(options[9].1, OptionValue::Text("some text".to_owned()));
+}
+
+#[test]
+fn options_invalid_value() {
+  let (res, _, options) = run(&["o n=yes"]);
+  assert_eq!(res, Err(EdError::OptionValueInvalid{
+    name: "n".to_owned(),
+    value: "yes".to_owned(),
+    expected: "bool",
+  }));
+  assert_eq!(options[0].1, OptionValue::Bool(false));
+  // Custom options may reject values of the right type
+  let (res, _, options) = run(&["o even=3"]);
+  assert_eq!(res, Err(EdError::OptionValueInvalid{
+    name: "even".to_owned(),
+    value: "3".to_owned(),
+    expected: "even number",
+  }));
+  assert_eq!(options[8].1, OptionValue::Number(2));
+}
+
+#[test]
+fn options_undefined() {
+  ErrorTest{
+    init_buffer: vec!["a"],
+    command_input: vec!["o missing=1"],
+    expected_error: EdError::OptionUndefined("missing".to_owned()),
+  }.run()
+}
+
+#[test]
+fn options_selection() {
+  ErrorTest{
+    init_buffer: vec!["a"],
+    command_input: vec!["1o n"],
+    expected_error: EdError::SelectionForbidden,
+  }.run()
+}
@@ -10,6 +10,7 @@ use shared::dummy_io::DummyIO;
 use add_ed::{
   ui::ScriptedUI,
   Ed,
+  step::{Request, Response},
 };
 
 // Verify behaviour of 'p' command
@@ -22,6 +23,11 @@ use add_ed::{
 // - Prints numbered if state.n is set
 // - state.selection is set to printed selection
 // - Does not change unsaved
+//
+// And of 'P' command
+// - Toggles state.n and state.l for the flags given
+// - Without flags toggles state.show_cmd_prefix, and with it the prefix given
+//   to the UI
 
 // Normal case, just print some lines
 #[test]
@@ -183,3 +189,24 @@ fn toggle_literal_off() {
   assert_eq!(ed.l, false);
   assert!(ed.history.current().is_empty());
 }
+// Verify that 'P' without flags toggles the prefix, and only without flags
+#[test]
+fn toggle_prefix() {
+  let mut io = DummyIO::new();
+  let macros = std::collections::HashMap::new();
+  let mut ed = Ed::new(
+    &mut io,
+    &macros,
+  );
+  let command = |x: &str| Response::Command(format!("{}\n", x));
+  assert_eq!(
+    ed.step(Response::Continue),
+    Ok(Request::Command{ prefix: Some(':') }),
+  );
+  assert_eq!(ed.step(command("P")), Ok(Request::Command{ prefix: None }));
+  assert!(!ed.show_cmd_prefix);
+  assert_eq!(ed.step(command("Pn")), Ok(Request::Command{ prefix: None }));
+  assert!(ed.n);
+  assert_eq!(ed.step(command("P")), Ok(Request::Command{ prefix: Some(':') }));
+  assert!(ed.show_cmd_prefix);
+}
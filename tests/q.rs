@@ -3,6 +3,7 @@
 mod shared;
 use shared::fixtures::{
   ErrorTest,
+  BasicTest,
 };
 use shared::dummy_io::DummyIO;
 use shared::dummy_ui::DummyUI;
@@ -15,6 +16,8 @@ use add_ed::{
 //
 // - Takes no selection
 // - If unsaved and 'q' errors on UNSAVED_CHANGES
+//   (Unsaved means the buffer content differs from the saved content, so
+//   changes reverted by hand or via another path through history are saved)
 // - If no error, returns true from command execution to signify being done
 
 // Normal quit when saved
@@ -56,3 +59,18 @@ fn force_quit_unsaved() {
   ed.history.set_unsaved();
   assert!(ed.run_command(&mut ui, "Q\n").expect("Error running test"));
 }
+
+// Quit after manually reverting changes, which is saved
+#[test]
+fn quit_after_manual_revert() {
+  BasicTest{
+    init_buffer: vec!["a","b","c"],
+    init_clipboard: vec![],
+    command_input: vec!["2d","1a","b",".","q"],
+    expected_buffer: vec!["a","b","c"],
+    expected_buffer_saved: true,
+    expected_selection: (2,2),
+    expected_clipboard: vec!["b"],
+    expected_history_tags: vec!["2d","1a"],
+  }.run();
+}
@@ -0,0 +1,129 @@
+// Tests for RestrictedIO
+
+use std::collections::HashMap;
+mod shared;
+use shared::fake_io::{
+  FakeIO,
+  ShellCommand,
+};
+use shared::mock_ui::MockUI;
+use add_ed::{
+  Ed,
+  error::{EdError, Result},
+  io::RestrictedIO,
+  io::restricted_io::RestrictedIOError,
+};
+
+// Verify behaviour of RestrictedIO
+//
+// - Forbids all shell commands, even those the wrapped IO would run
+// - Resolves paths relative to the base directory, resolving `.` and `..`
+// - Forbids paths outside of the allowed directories, including those walking
+//   out of them with `..`
+// - Gives the wrapped IO the resolved path
+
+fn test_io() -> FakeIO {
+  FakeIO{
+    fake_fs: HashMap::from([
+      ("/sandbox/file".to_owned(), "in sandbox\n".to_owned()),
+      ("/sandbox2/file".to_owned(), "beside sandbox\n".to_owned()),
+      ("/etc/passwd".to_owned(), "root\n".to_owned()),
+    ]),
+    fake_shell: HashMap::from([
+      (
+        ShellCommand{ command: "echo hi".to_owned(), input: String::new() },
+        "hi\n".to_owned(),
+      ),
+    ]),
+  }
+}
+
+// Runs the commands on an editor with restricted access to /sandbox, returning
+// the result of each command, the buffer and the resulting fake filesystem
+fn run(commands: &[&str]) -> (Vec<Result<bool>>, Vec<String>, FakeIO) {
+  let mut inner_io = test_io();
+  let macros = HashMap::new();
+  let mut ui = MockUI{ prints_history: Vec::new() };
+  let mut io = RestrictedIO::new(&mut inner_io, "/sandbox").allow_dir(".");
+  let mut ed = Ed::new(&mut io, &macros);
+  let results = commands.iter()
+    .map(|c| ed.run_command(&mut ui, &format!("{}\n", c)))
+    .collect();
+  let buffer = ed.history.current().iter()
+    .map(|l| l.text.trim_end().to_owned())
+    .collect();
+  drop(ed);
+  drop(io);
+  (results, buffer, inner_io)
+}
+
+fn restricted_error(res: &Result<bool>) -> Option<&RestrictedIOError> {
+  match res {
+    Err(EdError::IO(e)) => e.downcast_ref(),
+    _ => None,
+  }
+}
+
+#[test]
+fn restricted_commands() {
+  let (results, buffer, _) = run(&[
+    "e file",
+    "!echo hi",
+    "r !echo hi",
+    "w !echo hi",
+    "|echo hi",
+  ]);
+  assert_eq!(results[0], Ok(false));
+  for res in &results[1..] {
+    assert_eq!(restricted_error(res), Some(&RestrictedIOError::CommandForbidden));
+  }
+  assert_eq!(buffer, vec!["in sandbox"]);
+}
+
+#[test]
+fn restricted_paths_allowed() {
+  let (results, buffer, io) = run(&[
+    "e file",
+    "r /sandbox/./file",
+    "r sub/../file",
+    "w sub/./new",
+  ]);
+  for res in &results {
+    assert_eq!(res, &Ok(false));
+  }
+  assert_eq!(buffer, vec!["in sandbox", "in sandbox", "in sandbox"]);
+  assert_eq!(
+    io.fake_fs.get("/sandbox/sub/new").map(|x| &x[..]),
+    Some("in sandbox\nin sandbox\nin sandbox\n"),
+  );
+}
+
+#[test]
+fn restricted_paths_forbidden() {
+  let paths = [
+    "/etc/passwd",
+    "../etc/passwd",
+    "sub/../../etc/passwd",
+    "/sandbox/../sandbox2/file",
+    "../sandbox2/file",
+    "/../../sandbox/file",
+  ];
+  for path in paths {
+    let (results, buffer, io) = run(&[
+      "e file",
+      &format!("e {}", path),
+      &format!("w {}", path),
+    ]);
+    assert_eq!(results[0], Ok(false));
+    for res in &results[1..] {
+      assert_eq!(
+        restricted_error(res),
+        Some(&RestrictedIOError::PathForbidden{ path: path.to_owned() }),
+        "`{}` wasn't forbidden",
+        path,
+      );
+    }
+    assert_eq!(buffer, vec!["in sandbox"]);
+    assert_eq!(io.fake_fs, test_io().fake_fs);
+  }
+}
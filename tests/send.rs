@@ -0,0 +1,53 @@
+// Tests for the send feature
+#![cfg(feature = "send")]
+
+mod shared;
+use shared::dummy_io::DummyIO;
+use add_ed::{
+  Ed,
+  Buffer,
+  History,
+  error::{EdError, UIError, IOError},
+};
+
+// Verify that the send feature makes the editor state Send
+//
+// - Ed, History<Buffer> and the error types are Send
+// - An editor can be moved to another thread and used there
+
+fn assert_send<T: Send>() {}
+
+#[test]
+fn send_types() {
+  assert_send::<Ed<'static>>();
+  assert_send::<History<Buffer>>();
+  assert_send::<EdError>();
+  assert_send::<UIError>();
+  assert_send::<IOError>();
+}
+
+#[test]
+fn send_move_to_thread() {
+  let mut io = DummyIO::new();
+  let macros = std::collections::HashMap::new();
+  let mut ed = Ed::new(
+    &mut io,
+    &macros,
+  );
+  ed.history.current_mut("initial load".into()).extend(
+    ["a\n", "b\n"].iter().map(|x| (*x).try_into().unwrap())
+  );
+  let tag = std::thread::scope(|s| {
+    s.spawn(move || {
+      ed.history.current()[1].set_tag('x');
+      let mut ui = add_ed::ui::ScriptedUI{
+        input: vec!["'xd\n".to_owned()].into(),
+        print_ui: None,
+      };
+      ed.run(&mut ui).unwrap();
+      ed.history.snapshots()[1].1[1].tag()
+    }).join().unwrap()
+  });
+  // Tags are shared throughout history
+  assert_eq!(tag, 'x');
+}
@@ -0,0 +1,103 @@
+// Tests for storing and restoring the editing session
+#![cfg(feature = "history_file")]
+
+use std::collections::HashMap;
+mod shared;
+use shared::dummy_io::DummyIO;
+use shared::mock_ui::MockUI;
+use add_ed::{
+  error::EdError,
+  Ed,
+  Session,
+};
+
+// Verify behaviour of Ed::session and Ed::restore_session
+//
+// - All session state survives serializing into a new editor
+// - Tags survive, both with and without history
+// - Without history the restored buffer is the only change, saved as before
+// - With history undo works past the restore
+// - A history not matching the buffer is rejected without changes
+
+// Runs some editing and returns the serialized session
+fn edited_session(include_history: bool) -> String {
+  let mut io = DummyIO::new();
+  let macros = HashMap::new();
+  let mut ui = MockUI{ prints_history: Vec::new() };
+  let mut ed = Ed::new(&mut io, &macros);
+  ed.run_command(&mut ui, "f file\n").unwrap();
+  ed.history.current_mut("initial load".into()).extend(
+    ["a\n", "b\n", "c\n"].iter().map(|x| (*x).try_into().unwrap())
+  );
+  ed.history.set_saved();
+  ed.run_command(&mut ui, "2kx\n").unwrap();
+  ed.run_command(&mut ui, "1s/a/A/\n").unwrap();
+  ed.run_command(&mut ui, "3y\n").unwrap();
+  ed.prev_shell_command = "echo hi".to_owned();
+  ed.n = true;
+  ed.print_errors = false;
+  ed.selection = (1, 2);
+  serde_json::to_string(&ed.session(include_history)).unwrap()
+}
+
+fn text(ed: &Ed) -> Vec<String> {
+  ed.history.current().iter().map(|l| l.text.trim_end().to_owned()).collect()
+}
+
+#[test]
+fn session_without_history() {
+  let session: Session = serde_json::from_str(&edited_session(false)).unwrap();
+  assert!(session.history.is_none());
+  let mut io = DummyIO::new();
+  let macros = HashMap::new();
+  let mut ed = Ed::new(&mut io, &macros);
+  ed.restore_session(session).unwrap();
+  assert_eq!(text(&ed), vec!["A", "b", "c"]);
+  assert_eq!(ed.history.current()[1].tag(), 'x');
+  assert!(!ed.history.saved());
+  assert_eq!(ed.history.snapshots().len(), 2);
+  assert_eq!(ed.selection, (1, 2));
+  assert_eq!(ed.clipboard.len(), 1);
+  assert_eq!(&ed.clipboard[0].text[..], "c\n");
+  assert_eq!(ed.file, "file");
+  assert_eq!(ed.prev_s.as_ref().map(|s| &s.pattern[..]), Some("a"));
+  assert_eq!(ed.prev_shell_command, "echo hi");
+  assert!(ed.n);
+  assert!(!ed.l);
+  assert!(!ed.print_errors);
+}
+
+#[test]
+fn session_with_history() {
+  let session: Session = serde_json::from_str(&edited_session(true)).unwrap();
+  let mut io = DummyIO::new();
+  let macros = HashMap::new();
+  let mut ui = MockUI{ prints_history: Vec::new() };
+  let mut ed = Ed::new(&mut io, &macros);
+  ed.restore_session(session).unwrap();
+  assert_eq!(text(&ed), vec!["A", "b", "c"]);
+  assert!(!ed.history.saved());
+  // Tags stay shared throughout the restored history
+  ed.history.current()[1].set_tag('y');
+  ed.run_command(&mut ui, "u\n").unwrap();
+  assert_eq!(text(&ed), vec!["a", "b", "c"]);
+  assert_eq!(ed.history.current()[1].tag(), 'y');
+  assert!(ed.history.saved());
+}
+
+#[test]
+fn session_mismatched_history() {
+  let mut session: Session = serde_json::from_str(&edited_session(true)).unwrap();
+  session.buffer.pop();
+  let mut io = DummyIO::new();
+  let macros = HashMap::new();
+  let mut ed = Ed::new(&mut io, &macros);
+  assert_eq!(
+    ed.restore_session(session),
+    Err(EdError::SessionInvalid(
+      "buffer doesn't match the viewed snapshot in history".to_owned()
+    )),
+  );
+  assert!(ed.file.is_empty());
+  assert_eq!(ed.history.snapshots().len(), 1);
+}
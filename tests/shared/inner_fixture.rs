@@ -66,6 +66,7 @@ pub fn inner_fixture(
   });
   ed.history.current_mut("initial load".into()).append(&mut (&init_buffer).try_into().unwrap());
   if init_buffer_saved { ed.history.set_saved(); }
+  else { ed.history.set_unsaved(); }
   // Create scripted UI (with mock UI, which tracks print invocations)
   let mut inner_ui = MockUI{ prints_history: Vec::new() };
   let mut ui = ScriptedUI{
@@ -91,7 +92,7 @@ pub fn inner_fixture(
 
   // Verify state after execution
   assert_eq!(
-    ed.history.current()[..].iter()
+    ed.history.current().iter()
       .map(|l| l.text.trim_end_matches('\n'))
       .collect::<Vec<&str>>()
     ,
@@ -0,0 +1,126 @@
+// Tests for the ShellContext given to IO with shell commands
+
+use std::collections::HashMap;
+mod shared;
+use shared::mock_ui::MockUI;
+use add_ed::{
+  ui::{ScriptedUI, UILock},
+  Ed,
+  LinesIter,
+  error::IOError,
+  io::{IO, ShellContext, WriteType},
+};
+
+// Verify the ShellContext given with each shell command
+//
+// - file is the current file
+// - selection is the lines given to the command for '|' and 'w !', the index
+//   for 'r !' and the current selection otherwise
+// - prev_shell_command is the command run before, not the current one
+// - script is set only when run by 'g' or a macro
+
+// An IO recording the context of each shell command, returning its input
+#[derive(Default)]
+struct ContextIO {
+  contexts: Vec<(String, ShellContext)>,
+}
+impl IO for ContextIO {
+  fn run_command(&mut self,
+    _ui: &mut UILock,
+    command: String,
+    context: &ShellContext,
+  ) -> Result<(), IOError> {
+    self.contexts.push((command, context.clone()));
+    Ok(())
+  }
+  fn run_read_command(&mut self,
+    _ui: &mut UILock,
+    command: String,
+    context: &ShellContext,
+  ) -> Result<String, IOError> {
+    self.contexts.push((command, context.clone()));
+    Ok("read\n".to_owned())
+  }
+  fn run_write_command(&mut self,
+    _ui: &mut UILock,
+    command: String,
+    context: &ShellContext,
+    input: LinesIter,
+  ) -> Result<usize, IOError> {
+    self.contexts.push((command, context.clone()));
+    Ok(input.map(|x| x.len()).sum())
+  }
+  fn run_transform_command(&mut self,
+    _ui: &mut UILock,
+    command: String,
+    context: &ShellContext,
+    input: LinesIter,
+  ) -> Result<String, IOError> {
+    self.contexts.push((command, context.clone()));
+    Ok(input.collect())
+  }
+  fn write_file(&mut self,
+    _path: &str,
+    _wtype: WriteType,
+    _data: LinesIter,
+  ) -> Result<usize, IOError> {
+    unimplemented!()
+  }
+  fn read_file(&mut self,
+    _path: &str,
+    _must_exist: bool,
+  ) -> Result<String, IOError> {
+    unimplemented!()
+  }
+}
+
+fn context(
+  selection: (usize, usize),
+  prev_shell_command: &str,
+  script: bool,
+) -> ShellContext {
+  ShellContext{
+    file: "file".to_owned(),
+    selection,
+    prev_shell_command: prev_shell_command.to_owned(),
+    script,
+  }
+}
+
+#[test]
+fn shell_context() {
+  let mut io = ContextIO::default();
+  let macros = HashMap::new();
+  let mut inner_ui = MockUI{ prints_history: Vec::new() };
+  {
+    let mut ed = Ed::new(&mut io, &macros);
+    ed.file = "file".to_owned();
+    ed.history.current_mut("initial load".into()).extend(
+      ["a\n", "b\n", "c\n", "d\n"].iter().map(|x| (*x).try_into().unwrap())
+    );
+    ed.selection = (3, 4);
+    let mut ui = ScriptedUI{
+      print_ui: Some(&mut inner_ui),
+      input: [
+        "!first",
+        "2,3|second",
+        "1r !third",
+        "1,2w !fourth",
+        ",g/^d/p",
+        "!fifth",
+        "/",
+      ].iter().map(|x| format!("{}\n", x)).collect(),
+    };
+    while !ui.input.is_empty() {
+      ed.get_and_run_command(&mut ui).expect("Error running test");
+    }
+  }
+  assert_eq!(io.contexts, vec![
+    ("first".to_owned(), context((3, 4), "", false)),
+    ("second".to_owned(), context((2, 3), "first", false)),
+    ("third".to_owned(), context((1, 1), "second", false)),
+    ("fourth".to_owned(), context((1, 2), "third", false)),
+    // 'g' selects the matching line, "d" (now line 5, after 'r' added a line)
+    ("fifth".to_owned(), context((5, 5), "fourth", true)),
+  ]);
+}
@@ -0,0 +1,237 @@
+// Tests for the stepping API, Ed::step
+
+mod shared;
+use shared::dummy_io::DummyIO;
+use add_ed::{
+  ui::UI,
+  plugins::CommandExtender,
+  Ed,
+  PubLine,
+  Sel,
+  PrintingFlags,
+  error::{EdError, Result},
+  step::{Request, Response},
+};
+
+// Verify behaviour of Ed::step
+//
+// - Requests are returned in the order a UI would be called
+// - Commands taking input are resumed with the given input, without repeating
+//   requests or snapshots
+// - 'g' taking commands as input is verified before the input is requested,
+//   then run once and creates one snapshot
+// - 'G' is driven line by line and creates one snapshot
+// - Command extenders (and plugins) taking input error instead of being run
+//   again when resumed
+// - Responses not answering the last request error without changing state
+
+fn lines(texts: &[&str]) -> Vec<PubLine> {
+  texts.iter().map(|t| (*t).try_into().unwrap()).collect()
+}
+
+// Runs the check on an editor with "a", "b" and "c" in its buffer
+fn run(check: impl FnOnce(&mut Ed)) {
+  let mut io = DummyIO::new();
+  let macros = std::collections::HashMap::new();
+  let mut ed = Ed::new(
+    &mut io,
+    &macros,
+  );
+  ed.history.current_mut("initial load".into()).extend(
+    ["a\n", "b\n", "c\n"].iter().map(|x| (*x).try_into().unwrap())
+  );
+  ed.selection = (1, 3);
+  check(&mut ed);
+}
+
+fn text(ed: &Ed) -> Vec<String> {
+  ed.history.current().iter().map(|l| l.text.trim_end().to_owned()).collect()
+}
+fn labels(ed: &Ed) -> Vec<String> {
+  ed.history.snapshots()[2..].iter().map(|s| s.0.clone()).collect()
+}
+fn command(command: &str) -> Response {
+  Response::Command(command.to_owned())
+}
+fn input(input: &[&str]) -> Response {
+  Response::Input(input.iter().map(|s| (*s).to_owned()).collect())
+}
+const COMMAND: Request = Request::Command{ prefix: Some(':') };
+
+#[test]
+fn step_print_and_quit() {
+  run(|ed| {
+    assert_eq!(ed.step(Response::Continue), Ok(COMMAND));
+    assert_eq!(
+      ed.step(command("2,3n\n")),
+      Ok(Request::PrintSelection{
+        selection: (2, 3),
+        lines: lines(&["b\n", "c\n"]),
+        numbered: true,
+        literal: false,
+      }),
+    );
+    assert_eq!(ed.step(Response::Continue), Ok(COMMAND));
+    assert_eq!(ed.step(command("=\n")), Ok(Request::PrintMessage("(2,3)".to_owned())));
+    assert_eq!(ed.step(Response::Continue), Ok(COMMAND));
+    assert_eq!(ed.step(command("Q\n")), Ok(Request::Quit));
+  });
+}
+
+#[test]
+fn step_input() {
+  run(|ed| {
+    ed.step(Response::Continue).unwrap();
+    assert_eq!(
+      ed.step(command("1ap\n")),
+      Ok(Request::Input{ terminator: '.', initial: None }),
+    );
+    // Nothing is changed until the input is given
+    assert_eq!(text(ed), vec!["a", "b", "c"]);
+    assert_eq!(
+      ed.step(input(&["x\n", "y\n"])),
+      Ok(Request::PrintSelection{
+        selection: (2, 3),
+        lines: lines(&["x\n", "y\n"]),
+        numbered: false,
+        literal: false,
+      }),
+    );
+    assert_eq!(ed.step(Response::Continue), Ok(COMMAND));
+    assert_eq!(text(ed), vec!["a", "x", "y", "b", "c"]);
+    assert_eq!(labels(ed), vec!["1ap"]);
+  });
+}
+
+#[test]
+fn step_interactive_global() {
+  run(|ed| {
+    ed.step(Response::Continue).unwrap();
+    assert_eq!(
+      ed.step(command("G/[ac]/\n")),
+      Ok(Request::PrintSelection{
+        selection: (1, 1),
+        lines: lines(&["a\n"]),
+        numbered: false,
+        literal: false,
+      }),
+    );
+    assert_eq!(
+      ed.step(Response::Continue),
+      Ok(Request::Input{ terminator: '/', initial: None }),
+    );
+    // Like with a UI, commands run on the line take input from the same lines
+    assert_eq!(
+      ed.step(input(&["a\n", "x\n", ".\n"])),
+      Ok(Request::PrintSelection{
+        selection: (4, 4),
+        lines: lines(&["c\n"]),
+        numbered: false,
+        literal: false,
+      }),
+    );
+    ed.step(Response::Continue).unwrap();
+    assert_eq!(ed.step(input(&["d\n"])), Ok(COMMAND));
+    assert_eq!(text(ed), vec!["a", "x", "b"]);
+    assert_eq!(labels(ed), vec!["G/[ac]/"]);
+  });
+}
+
+#[test]
+fn step_global_input() {
+  run(|ed| {
+    ed.step(Response::Continue).unwrap();
+    // Errors found by marking are returned before taking input
+    assert_eq!(
+      ed.step(command("g/x/\n")),
+      Err(EdError::RegexNoMatch("x".to_owned())),
+    );
+    ed.step(Response::Continue).unwrap();
+    assert_eq!(
+      ed.step(command("g/[ac]/s|$|!|\n")),
+      Ok(Request::Input{ terminator: '/', initial: None }),
+    );
+    assert_eq!(
+      ed.step(input(&["p\n"])),
+      Ok(Request::PrintSelection{
+        selection: (1, 1),
+        lines: lines(&["a!\n"]),
+        numbered: false,
+        literal: false,
+      }),
+    );
+    assert_eq!(
+      ed.step(Response::Continue),
+      Ok(Request::PrintSelection{
+        selection: (3, 3),
+        lines: lines(&["c!\n"]),
+        numbered: false,
+        literal: false,
+      }),
+    );
+    assert_eq!(ed.step(Response::Continue), Ok(COMMAND));
+    assert_eq!(text(ed), vec!["a!", "b", "c!"]);
+    assert_eq!(labels(ed), vec!["g/[ac]/s|$|!|"]);
+  });
+}
+
+#[test]
+fn step_errors() {
+  run(|ed| {
+    // Responses must answer the last request
+    assert_eq!(ed.step(input(&["x\n"])), Err(EdError::ResponseUnexpected));
+    assert_eq!(ed.step(Response::Continue), Ok(COMMAND));
+    assert_eq!(ed.step(Response::Continue), Err(EdError::ResponseUnexpected));
+    // Command errors are returned as the request, answered by Continue
+    assert_eq!(
+      ed.step(command("5d\n")),
+      Err(EdError::IndexTooBig{ index: 5, buffer_len: 3 }),
+    );
+    assert_eq!(ed.error, Some(EdError::IndexTooBig{ index: 5, buffer_len: 3 }));
+    assert_eq!(ed.step(Response::Continue), Ok(COMMAND));
+    assert_eq!(text(ed), vec!["a", "b", "c"]);
+  });
+}
+
+// Claims 'Y', which counts its runs and then takes input
+struct TakesInput {
+  runs: usize,
+}
+impl CommandExtender for TakesInput {
+  fn run_command(&mut self,
+    ed: &mut Ed<'_>,
+    ui: &mut dyn UI,
+    _pflags: &mut PrintingFlags,
+    _full_command: &str,
+    _selection: Option<Sel>,
+    command: char,
+    _tail: &str,
+  ) -> Result<bool> {
+    if command != 'Y' { return Ok(false); }
+    self.runs += 1;
+    ui.get_input(
+      ed,
+      '.',
+      #[cfg(feature = "initial_input_data")]
+      None,
+    )?;
+    Ok(true)
+  }
+}
+
+#[test]
+fn step_extender_input() {
+  let mut io = DummyIO::new();
+  let macros = std::collections::HashMap::new();
+  let mut extender = TakesInput{ runs: 0 };
+  {
+    let mut ed = Ed::new(
+      &mut io,
+      &macros,
+    ).with_command_extender(&mut extender);
+    ed.step(Response::Continue).unwrap();
+    assert_eq!(ed.step(command("Y\n")), Err(EdError::StepInputUnsupported));
+    assert_eq!(ed.step(Response::Continue), Ok(COMMAND));
+  }
+  assert_eq!(extender.runs, 1, "The extender was run again.");
+}
@@ -0,0 +1,137 @@
+// Tests for Ed::transaction
+// (Transactional macros are tested with the ':' command)
+
+mod shared;
+use shared::dummy_io::DummyIO;
+use shared::dummy_ui::DummyUI;
+use add_ed::{
+  error::EdError,
+  Ed,
+  UndoStyle,
+};
+
+// Verify behaviour of Ed::transaction
+//
+// - If the closure succeeds its changes and snapshots are kept
+// - If the closure errors buffer, selection and clipboard are restored and any
+//   snapshots it created are removed, also when in the past of history
+// - Returns the result of the closure
+// - If the closure switches buffer, the rollback switches back and restores the
+//   buffer the transaction started in
+
+fn buffer_text(ed: &Ed) -> String {
+  ed.history.current().iter().map(|l| &l.text[..]).collect()
+}
+
+// Errors halfway through roll back everything done before the error
+#[test]
+fn transaction_rollback() {
+  let mut io = DummyIO::new();
+  let macros = std::collections::HashMap::new();
+  let mut ui = DummyUI{};
+  let mut ed = Ed::new(
+    &mut io,
+    &macros,
+  );
+  ed.history.current_mut("initial load".into()).extend(
+    ["a\n", "b\n", "c\n"].iter().map(|x| (*x).try_into().unwrap())
+  );
+  ed.history.set_saved();
+  ed.selection = (1, 3);
+  let res = ed.transaction(|ed| {
+    ed.run_command(&mut ui, "1t1\n")?;
+    ed.run_command(&mut ui, "2d\n")?;
+    ed.run_command(&mut ui, "9d\n")
+  });
+  assert!(res.is_err(), "The failing command didn't fail the transaction.");
+  assert_eq!(buffer_text(&ed), "a\nb\nc\n");
+  assert_eq!(ed.selection, (1, 3));
+  assert!(ed.clipboard.is_empty(), "Clipboard wasn't restored.");
+  assert_eq!(ed.history.len(), 2, "Rollback left snapshots behind.");
+  assert!(ed.history.saved());
+}
+
+// Successful transactions keep the snapshots of each command
+#[test]
+fn transaction_success() {
+  let mut io = DummyIO::new();
+  let macros = std::collections::HashMap::new();
+  let mut ui = DummyUI{};
+  let mut ed = Ed::new(
+    &mut io,
+    &macros,
+  );
+  ed.history.current_mut("initial load".into()).extend(
+    ["a\n", "b\n"].iter().map(|x| (*x).try_into().unwrap())
+  );
+  let res = ed.transaction(|ed| {
+    ed.run_command(&mut ui, "2d\n")?;
+    Ok(ed.history.current().len())
+  });
+  assert_eq!(res, Ok(1));
+  assert_eq!(buffer_text(&ed), "a\n");
+  assert_eq!(ed.history.snapshots()[2].0, "2d");
+}
+
+// Rollback in the past of history leaves no revert snapshots or branches
+#[test]
+fn transaction_rollback_in_past() {
+  for style in [UndoStyle::Revert, UndoStyle::Tree] {
+    let mut io = DummyIO::new();
+    let macros = std::collections::HashMap::new();
+    let mut ui = DummyUI{};
+    let mut ed = Ed::new(
+      &mut io,
+      &macros,
+    );
+    ed.history.undo_style = style;
+    ed.history.current_mut("initial load".into()).extend(
+      ["a\n", "b\n"].iter().map(|x| (*x).try_into().unwrap())
+    );
+    ed.run_command(&mut ui, "1d\n").unwrap();
+    ed.history.undo(1).unwrap();
+    let res = ed.transaction(|ed| {
+      ed.run_command(&mut ui, "2d\n")?;
+      Err::<(), _>(EdError::NoOp)
+    });
+    assert_eq!(res, Err(EdError::NoOp));
+    assert_eq!(buffer_text(&ed), "a\nb\n");
+    assert_eq!(ed.history.len(), 3, "Rollback left snapshots behind.");
+    assert_eq!(ed.history.viewed_i(), 1);
+  }
+}
+
+// Rollback after switching buffer restores the buffer the transaction began in
+#[test]
+fn transaction_rollback_after_switch() {
+  let mut io = DummyIO::new();
+  let macros = std::collections::HashMap::new();
+  let mut ui = DummyUI{};
+  let mut ed = Ed::new(
+    &mut io,
+    &macros,
+  );
+  ed.history.max_snapshots = Some(10);
+  ed.history.current_mut("initial load".into()).extend(
+    ["a\n", "b\n", "c\n"].iter().map(|x| (*x).try_into().unwrap())
+  );
+  ed.run_command(&mut ui, "1d\n").unwrap();
+  ed.run_command(&mut ui, "1d\n").unwrap();
+  ed.selection = (1, 1);
+  let res = ed.transaction(|ed| {
+    ed.run_command(&mut ui, "1d\n")?;
+    ed.run_command(&mut ui, "b other\n")?;
+    Err::<(), _>(EdError::NoOp)
+  });
+  assert_eq!(res, Err(EdError::NoOp));
+  assert_eq!(ed.buffer_name, "main", "Rollback didn't switch back.");
+  assert_eq!(buffer_text(&ed), "c\n");
+  assert_eq!(ed.selection, (1, 1));
+  assert_eq!(ed.history.len(), 4, "Rollback left snapshots behind.");
+  assert_eq!(ed.history.max_snapshots, Some(10));
+  // Successful transactions leave the buffer switched, with limits restored
+  let res = ed.transaction(|ed| ed.run_command(&mut ui, "b other\n"));
+  assert_eq!(res, Ok(false));
+  assert_eq!(ed.buffer_name, "other");
+  assert_eq!(ed.buffers["main"].history.max_snapshots, Some(10));
+}
@@ -5,8 +5,32 @@
 mod shared;
 use shared::fixtures::{
   PrintTest,
+  ErrorTest,
 };
-use shared::mock_ui::Print;
+use shared::mock_ui::{Print, MockUI};
+use shared::dummy_io::DummyIO;
+use add_ed::{
+  ui::ScriptedUI,
+  Ed,
+  UndoStyle,
+  Clock,
+  error::EdError,
+  sync::Shared,
+};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+// Clock which only moves when told to
+#[derive(Debug)]
+struct FakeClock(Mutex<SystemTime>);
+impl FakeClock {
+  fn advance(&self, seconds: u64) {
+    *self.0.lock().unwrap() += Duration::from_secs(seconds);
+  }
+}
+impl Clock for FakeClock {
+  fn now(&self) -> SystemTime { *self.0.lock().unwrap() }
+}
 
 // Verify behaviour of 'u' command
 //
@@ -18,6 +42,15 @@ use shared::mock_ui::Print;
 //   - If it isn't possible to move the given number of steps prints error,
 //     INVALID_UNDO_STEPS.
 // - Sets saved / unsaved after if that undo step is saved / unsaved
+// - With UndoStyle::Tree editing after undo creates a branch, which `u>` and
+//   `u<` switch between (newer resp. older branch)
+// - `u*` goes to an absolute index in history: an integer, `$` (optionally
+//   followed by `-<integer>`) for the last snapshot or `s` for the saved one
+// - `u/<regex>/` and `u?<regex>?` go to the nearest following resp. preceding
+//   snapshot with a label matching the regex
+// - `u:<name>` goes to the snapshot with the given checkpoint name
+// - `u@<duration>` goes to the snapshot that was newest that long ago, with
+//   durations such as `1h30m` (units s, m, h and d)
 // - Currently doesn't modify selection.
 //   (Later it may be good to set selection to the selection _acted upon_ in the
 //   last undone step. But to do that pairing selection with buffer state could
@@ -127,3 +160,625 @@ fn undo_tag_move() {
     expected_history_tags: vec!["2m"],
   }.run()
 }
+
+// Absolute index into history
+#[test]
+fn undo_absolute() {
+  PrintTest{
+    init_buffer: vec!["a","b","c","d"],
+    init_clipboard: vec![],
+    command_input: vec!["1d","3d","u*1"],
+    expected_buffer: vec!["a","b","c","d"],
+    expected_buffer_saved: true,
+    expected_clipboard: vec!["d"],
+    expected_selection: (2,2),
+    expected_prints: vec![
+      Print{
+        text: vec!["Moved to snapshot 1, right after initial load.".to_owned()],
+        n: false,
+        l: false,
+      },
+    ],
+    expected_history_tags: vec!["1d","3d"],
+  }.run()
+}
+
+// Index relative to last snapshot in history
+#[test]
+fn undo_absolute_end() {
+  PrintTest{
+    init_buffer: vec!["a","b","c","d"],
+    init_clipboard: vec![],
+    command_input: vec!["1d","3d","u2","u*$-1"],
+    expected_buffer: vec!["b","c","d"],
+    expected_buffer_saved: false,
+    expected_clipboard: vec!["d"],
+    expected_selection: (2,2),
+    expected_prints: vec![
+      Print{
+        text: vec!["Undid 2 operation(s) to right after initial load.".to_owned()],
+        n: false,
+        l: false,
+      },
+      Print{
+        text: vec!["Moved to snapshot 2, right after 1d.".to_owned()],
+        n: false,
+        l: false,
+      },
+    ],
+    expected_history_tags: vec!["1d","3d"],
+  }.run()
+}
+
+// Go to the saved snapshot
+#[test]
+fn undo_to_saved() {
+  PrintTest{
+    init_buffer: vec!["a","b","c","d"],
+    init_clipboard: vec![],
+    command_input: vec!["1d","3d","u*s"],
+    expected_buffer: vec!["a","b","c","d"],
+    expected_buffer_saved: true,
+    expected_clipboard: vec!["d"],
+    expected_selection: (2,2),
+    expected_prints: vec![
+      Print{
+        text: vec!["Moved to snapshot 1, right after initial load.".to_owned()],
+        n: false,
+        l: false,
+      },
+    ],
+    expected_history_tags: vec!["1d","3d"],
+  }.run()
+}
+
+// Search backwards and forwards over snapshot labels
+#[test]
+fn undo_search() {
+  PrintTest{
+    init_buffer: vec!["a","b","c","d"],
+    init_clipboard: vec![],
+    command_input: vec!["1d","3d","u?^1?","u/^3"],
+    expected_buffer: vec!["b","c"],
+    expected_buffer_saved: false,
+    expected_clipboard: vec!["d"],
+    expected_selection: (2,2),
+    expected_prints: vec![
+      Print{
+        text: vec!["Moved to snapshot 2, right after 1d.".to_owned()],
+        n: false,
+        l: false,
+      },
+      Print{
+        text: vec!["Moved to snapshot 3, right after 3d.".to_owned()],
+        n: false,
+        l: false,
+      },
+    ],
+    expected_history_tags: vec!["1d","3d"],
+  }.run()
+}
+
+// Absolute index beyond end of history
+#[test]
+fn undo_absolute_too_big() {
+  ErrorTest{
+    init_buffer: vec!["a","b"],
+    command_input: vec!["u*5"],
+    expected_error: EdError::UndoIndexTooBig{
+      index: 5,
+      history_len: 2,
+      relative_redo_limit: 0,
+    },
+  }.run()
+}
+
+// Offset from end of history to before start of history
+#[test]
+fn undo_absolute_negative() {
+  ErrorTest{
+    init_buffer: vec!["a","b"],
+    command_input: vec!["u*$-5"],
+    expected_error: EdError::UndoIndexNegative{relative_undo_limit: 1},
+  }.run()
+}
+
+// Search without match
+#[test]
+fn undo_search_no_match() {
+  ErrorTest{
+    init_buffer: vec!["a","b"],
+    command_input: vec!["u?^e?"],
+    expected_error: EdError::RegexNoMatch("^e".to_owned()),
+  }.run()
+}
+
+// Invalid duration
+#[test]
+fn undo_time_invalid() {
+  ErrorTest{
+    init_buffer: vec!["a","b"],
+    command_input: vec!["u@5x"],
+    expected_error: EdError::UndoDurationInvalid("5x".to_owned()),
+  }.run()
+}
+
+// Unknown checkpoint
+#[test]
+fn undo_checkpoint_unknown() {
+  ErrorTest{
+    init_buffer: vec!["a","b"],
+    command_input: vec!["u:missing"],
+    expected_error: EdError::UndoCheckpointUnknown("missing".to_owned()),
+  }.run()
+}
+
+// Duration reaching back before history
+#[test]
+fn undo_time_too_far() {
+  ErrorTest{
+    init_buffer: vec!["a","b"],
+    command_input: vec!["u@1000d"],
+    expected_error: EdError::UndoIndexNegative{relative_undo_limit: 1},
+  }.run()
+}
+
+// Verify switching between branches with tree style undo
+#[test]
+fn undo_tree_branches() {
+  let mut io = DummyIO::new();
+  let macros = std::collections::HashMap::new();
+  let mut inner_ui = MockUI{ prints_history: Vec::new() };
+  let mut ui = ScriptedUI{
+    print_ui: Some(&mut inner_ui),
+    input: vec![
+      "1d", // First branch
+      "u",
+      "2d", // Second branch, from same snapshot
+      "u<", // Switch to first branch
+      "u>",
+      "u>",
+    ].iter().map(|x| format!("{}\n", x)).collect(),
+  };
+  let mut ed = Ed::new(
+    &mut io,
+    &macros,
+  );
+  ed.history.undo_style = UndoStyle::Tree;
+  ed.history.current_mut("initial load".into()).extend(
+    ["a\n", "b\n"].iter().map(|x| (*x).try_into().unwrap())
+  );
+  ed.history.set_saved();
+  for _ in 0 .. 5 {
+    ed.get_and_run_command(&mut ui).expect("Error running test");
+  }
+  assert_eq!(ed.history.len(), 4, "Tree style undo shouldn't add snapshots.");
+  assert_eq!(ed.history.snapshots()[ed.history.viewed_i()].0, "2d");
+  assert!(
+    ed.get_and_run_command(&mut ui).is_err(),
+    "Switching to a nonexistent branch should error."
+  );
+  assert_eq!(
+    inner_ui.prints_history[..3],
+    vec![
+      Print{
+        text: vec!["Undid 1 operation(s) to right after initial load.".to_owned()],
+        n: false,
+        l: false,
+      },
+      Print{
+        text: vec!["Switched 1 branch(es) older to right after 1d.".to_owned()],
+        n: false,
+        l: false,
+      },
+      Print{
+        text: vec!["Switched 1 branch(es) newer to right after 2d.".to_owned()],
+        n: false,
+        l: false,
+      },
+    ],
+  );
+}
+
+// Verify behaviour of 'U' command
+//
+// - Doesn't allow selection or index
+// - Prints the 10 snapshots nearest to the viewed snapshot, marking the viewed
+//   one with '>' and the saved one with "(saved)"
+// - Accepts an index (same as `u*`) to print snapshots nearest to instead
+// - Flag 'a' adds absolute indices, 'A' prints all snapshots
+// - Flag 'd' instead prints the changes made by the snapshot, compared to the
+//   snapshot it was made from
+// - Flag 't' adds how long ago each snapshot was created
+// - The index may also be given as `@<duration>`, same as for `u@`
+// - `U:<name>` names the viewed snapshot as a checkpoint, `U:` lists them
+
+// Absolute indices
+#[test]
+fn list_history_absolute() {
+  PrintTest{
+    init_buffer: vec!["a","b","c","d"],
+    init_clipboard: vec![],
+    command_input: vec!["1d","3d","u","Ua"],
+    expected_buffer: vec!["b","c","d"],
+    expected_buffer_saved: false,
+    expected_clipboard: vec!["d"],
+    expected_selection: (2,2),
+    expected_prints: vec![
+      Print{
+        text: vec!["Undid 1 operation(s) to right after 1d.".to_owned()],
+        n: false,
+        l: false,
+      },
+      Print{
+        text: vec![concat!(
+          "  0 Before reading in a file (empty)\n",
+          "  1 initial load (saved)\n",
+          "> 2 1d\n",
+          "  3 3d",
+        ).to_owned()],
+        n: false,
+        l: false,
+      },
+    ],
+    expected_history_tags: vec!["1d","3d"],
+  }.run()
+}
+
+// Print all of history, with an index far from the viewed
+#[test]
+fn list_history_all() {
+  PrintTest{
+    init_buffer: vec!["a"],
+    init_clipboard: vec![],
+    command_input: vec![
+      "1t0","1t0","1t0","1t0","1t0","1t0","1t0","1t0","1t0","1t0","1t0",
+      "U0aA",
+    ],
+    expected_buffer: vec!["a","a","a","a","a","a","a","a","a","a","a","a"],
+    expected_buffer_saved: false,
+    expected_clipboard: vec![],
+    expected_selection: (1,1),
+    expected_prints: vec![
+      Print{
+        text: vec![concat!(
+          "   0 Before reading in a file (empty)\n",
+          "   1 initial load (saved)\n",
+          "   2 1t0\n",
+          "   3 1t0\n",
+          "   4 1t0\n",
+          "   5 1t0\n",
+          "   6 1t0\n",
+          "   7 1t0\n",
+          "   8 1t0\n",
+          "   9 1t0\n",
+          "  10 1t0\n",
+          "  11 1t0\n",
+          "> 12 1t0",
+        ).to_owned()],
+        n: false,
+        l: false,
+      },
+    ],
+    expected_history_tags: vec![
+      "1t0","1t0","1t0","1t0","1t0","1t0","1t0","1t0","1t0","1t0","1t0",
+    ],
+  }.run()
+}
+
+// Print the 10 snapshots nearest the start of history, though viewing the end
+#[test]
+fn list_history_index() {
+  PrintTest{
+    init_buffer: vec!["a"],
+    init_clipboard: vec![],
+    command_input: vec![
+      "1t0","1t0","1t0","1t0","1t0","1t0","1t0","1t0","1t0","1t0","1t0",
+      "U$-12",
+    ],
+    expected_buffer: vec!["a","a","a","a","a","a","a","a","a","a","a","a"],
+    expected_buffer_saved: false,
+    expected_clipboard: vec![],
+    expected_selection: (1,1),
+    expected_prints: vec![
+      Print{
+        text: vec![concat!(
+          "  Before reading in a file (empty)\n",
+          "  initial load (saved)\n",
+          "  1t0\n",
+          "  1t0\n",
+          "  1t0\n",
+          "  1t0\n",
+          "  1t0\n",
+          "  1t0\n",
+          "  1t0\n",
+          "  1t0",
+        ).to_owned()],
+        n: false,
+        l: false,
+      },
+    ],
+    expected_history_tags: vec![
+      "1t0","1t0","1t0","1t0","1t0","1t0","1t0","1t0","1t0","1t0","1t0",
+    ],
+  }.run()
+}
+
+// Print the 10 snapshots around an index in the middle of history
+#[test]
+fn list_history_middle() {
+  PrintTest{
+    init_buffer: vec!["a"],
+    init_clipboard: vec![],
+    command_input: vec![
+      "1t0","1t0","1t0","1t0","1t0","1t0","1t0","1t0","1t0","1t0",
+      "1t0","1t0","1t0","1t0","1t0","1t0","1t0","1t0","1t0","1t0",
+      "U11a",
+    ],
+    expected_buffer: vec![
+      "a","a","a","a","a","a","a","a","a","a","a",
+      "a","a","a","a","a","a","a","a","a","a",
+    ],
+    expected_buffer_saved: false,
+    expected_clipboard: vec![],
+    expected_selection: (1,1),
+    expected_prints: vec![
+      Print{
+        text: vec![concat!(
+          "   6 1t0\n",
+          "   7 1t0\n",
+          "   8 1t0\n",
+          "   9 1t0\n",
+          "  10 1t0\n",
+          "  11 1t0\n",
+          "  12 1t0\n",
+          "  13 1t0\n",
+          "  14 1t0\n",
+          "  15 1t0",
+        ).to_owned()],
+        n: false,
+        l: false,
+      },
+    ],
+    expected_history_tags: vec![
+      "1t0","1t0","1t0","1t0","1t0","1t0","1t0","1t0","1t0","1t0",
+      "1t0","1t0","1t0","1t0","1t0","1t0","1t0","1t0","1t0","1t0",
+    ],
+  }.run()
+}
+
+// Print the changes made by a snapshot
+#[test]
+fn list_history_diff() {
+  PrintTest{
+    init_buffer: vec!["a","b","c","d"],
+    init_clipboard: vec![],
+    command_input: vec!["2c","x","y",".","3d","U2d"],
+    expected_buffer: vec!["a","x","c","d"],
+    expected_buffer_saved: false,
+    expected_clipboard: vec!["y"],
+    expected_selection: (3,3),
+    expected_prints: vec![
+      Print{
+        text: vec![concat!(
+          "Changes by snapshot 2, 2c:\n",
+          "-2\tb\n",
+          "+2\tx\n",
+          "+3\ty",
+        ).to_owned()],
+        n: false,
+        l: false,
+      },
+    ],
+    expected_history_tags: vec!["2c","3d"],
+  }.run()
+}
+
+// Index beyond end of history
+#[test]
+fn list_history_index_too_big() {
+  ErrorTest{
+    init_buffer: vec!["a"],
+    command_input: vec!["U5"],
+    expected_error: EdError::UndoIndexTooBig{
+      index: 5,
+      history_len: 2,
+      relative_redo_limit: 0,
+    },
+  }.run()
+}
+
+// Verify that 'U' shows branches as indented
+#[test]
+fn list_history_tree() {
+  let mut io = DummyIO::new();
+  let macros = std::collections::HashMap::new();
+  let mut inner_ui = MockUI{ prints_history: Vec::new() };
+  let mut ui = ScriptedUI{
+    print_ui: Some(&mut inner_ui),
+    input: vec!["1d", "u", "2d", "U"].iter().map(|x| format!("{}\n", x)).collect(),
+  };
+  let mut ed = Ed::new(
+    &mut io,
+    &macros,
+  );
+  ed.history.undo_style = UndoStyle::Tree;
+  ed.history.current_mut("initial load".into()).extend(
+    ["a\n", "b\n"].iter().map(|x| (*x).try_into().unwrap())
+  );
+  ed.history.set_saved();
+  for _ in 0 .. 4 {
+    ed.get_and_run_command(&mut ui).expect("Error running test");
+  }
+  assert_eq!(
+    inner_ui.prints_history.last().unwrap().text,
+    vec![concat!(
+      "  Before reading in a file (empty)\n",
+      "  initial load (saved)\n",
+      ">   2d\n",
+      "  1d",
+    ).to_owned()],
+  );
+}
+
+// Verify that history limits drop the oldest snapshots, except the saved one
+#[test]
+fn history_limit() {
+  let mut io = DummyIO::new();
+  let macros = std::collections::HashMap::new();
+  let mut inner_ui = MockUI{ prints_history: Vec::new() };
+  let mut ui = ScriptedUI{
+    print_ui: Some(&mut inner_ui),
+    input: vec!["1d", "1d", "1d", "u*0"].iter().map(|x| format!("{}\n", x)).collect(),
+  };
+  let mut ed = Ed::new(
+    &mut io,
+    &macros,
+  );
+  ed.history.max_snapshots = Some(3);
+  ed.history.current_mut("initial load".into()).extend(
+    ["a\n", "b\n", "c\n", "d\n"].iter().map(|x| (*x).try_into().unwrap())
+  );
+  ed.history.set_saved();
+  for _ in 0 .. 4 {
+    ed.get_and_run_command(&mut ui).expect("Error running test");
+  }
+  assert_eq!(ed.history.dropped(), 2);
+  assert_eq!(
+    ed.history.snapshots().iter().map(|x| &x.0[..]).collect::<Vec<_>>(),
+    vec!["initial load", "1d", "1d"],
+  );
+  assert_eq!(
+    ed.history.current().get_lines((1, 4)).unwrap().collect::<String>(),
+    "a\nb\nc\nd\n",
+    "Oldest kept snapshot should be the saved state."
+  );
+  assert!(ed.history.saved());
+  assert_eq!(
+    ed.history.snapshots()[1].1.get_lines((1, 2)).unwrap().collect::<String>(),
+    "c\nd\n",
+    "The first deletion should be merged into the second."
+  );
+}
+
+// Verify moving through history by time, and listing snapshot ages
+#[test]
+fn undo_time() {
+  let mut io = DummyIO::new();
+  let macros = std::collections::HashMap::new();
+  let mut inner_ui = MockUI{ prints_history: Vec::new() };
+  let mut ui = ScriptedUI{
+    print_ui: Some(&mut inner_ui),
+    input: vec!["u@15m", "Ut", "U@15m1sd"].iter().map(|x| format!("{}\n", x)).collect(),
+  };
+  let mut ed = Ed::new(
+    &mut io,
+    &macros,
+  );
+  let clock = Shared::new(FakeClock(Mutex::new(SystemTime::now())));
+  ed.history.clock = clock.clone();
+  ed.history.current_mut("initial load".into()).extend(
+    ["a\n", "b\n", "c\n"].iter().map(|x| (*x).try_into().unwrap())
+  );
+  ed.history.set_saved();
+  for _ in 0 .. 2 {
+    clock.advance(10 * 60);
+    ed.run_command(&mut ui, "1d\n").expect("Error running test");
+  }
+  for _ in 0 .. 3 {
+    ed.get_and_run_command(&mut ui).expect("Error running test");
+  }
+  assert_eq!(ed.history.viewed_i(), 1);
+  assert_eq!(
+    inner_ui.prints_history,
+    vec![
+      Print{
+        text: vec!["Moved to snapshot 1, right after initial load.".to_owned()],
+        n: false,
+        l: false,
+      },
+      Print{
+        text: vec![concat!(
+          "  20m ago Before reading in a file (empty)\n",
+          "> 20m ago initial load (saved)\n",
+          "  10m ago 1d\n",
+          "   0s ago 1d",
+        ).to_owned()],
+        n: false,
+        l: false,
+      },
+      Print{
+        text: vec![concat!(
+          "Changes by snapshot 1, initial load:\n",
+          "+1\ta\n",
+          "+2\tb\n",
+          "+3\tc",
+        ).to_owned()],
+        n: false,
+        l: false,
+      },
+    ],
+  );
+}
+
+// Verify naming, listing and returning to checkpoints
+#[test]
+fn checkpoints() {
+  let mut io = DummyIO::new();
+  let macros = std::collections::HashMap::new();
+  let mut inner_ui = MockUI{ prints_history: Vec::new() };
+  let mut ui = ScriptedUI{
+    print_ui: Some(&mut inner_ui),
+    input: vec![
+      "U:",
+      "U:good",
+      "1d",
+      "1d",
+      "U:",
+      "u:good",
+      "U",
+    ].iter().map(|x| format!("{}\n", x)).collect(),
+  };
+  let mut ed = Ed::new(
+    &mut io,
+    &macros,
+  );
+  ed.history.current_mut("initial load".into()).extend(
+    ["a\n", "b\n"].iter().map(|x| (*x).try_into().unwrap())
+  );
+  ed.history.set_saved();
+  for _ in 0 .. 7 {
+    ed.get_and_run_command(&mut ui).expect("Error running test");
+  }
+  assert_eq!(ed.history.viewed_i(), 1);
+  assert_eq!(
+    inner_ui.prints_history,
+    vec![
+      Print{
+        text: vec!["No checkpoints in history.".to_owned()],
+        n: false,
+        l: false,
+      },
+      Print{
+        text: vec!["good: snapshot 1, initial load".to_owned()],
+        n: false,
+        l: false,
+      },
+      Print{
+        text: vec!["Moved to snapshot 1, right after initial load.".to_owned()],
+        n: false,
+        l: false,
+      },
+      Print{
+        text: vec![concat!(
+          "  Before reading in a file (empty)\n",
+          "> initial load [good] (saved)\n",
+          "  1d\n",
+          "  1d",
+        ).to_owned()],
+        n: false,
+        l: false,
+      },
+    ],
+  );
+}
@@ -9,6 +9,8 @@ use shared::fake_io::{
   FakeIO,
   ShellCommand,
 };
+use shared::mock_ui::{Print, MockUI};
+use add_ed::Ed;
 
 // Verify behaviour of 'w' command
 //
@@ -23,6 +25,7 @@ use shared::fake_io::{
 //   the given selection
 // - If whole buffer is written and path isn't a shell command, saves it to
 //   state.path
+// - Prints the number of bytes written, unless the quiet option is set
 
 // Function to set up the "filesystem" for these tests
 fn test_io() -> FakeIO {
@@ -73,6 +76,29 @@ fn write_defaults() {
   }.run();
 }
 
+// Returns what is printed when writing with and without quiet
+fn write_prints(quiet: bool) -> Vec<Print> {
+  let mut io = test_io();
+  let macros = HashMap::new();
+  let mut ui = MockUI{ prints_history: Vec::new() };
+  let mut ed = Ed::new(&mut io, &macros);
+  ed.quiet = quiet;
+  ed.history.current_mut("initial load".into()).push("3\n".try_into().unwrap());
+  ed.run_command(&mut ui, "w numbers2\n").unwrap();
+  ui.prints_history
+}
+
+// The byte count is only printed without quiet
+#[test]
+fn write_quiet() {
+  assert_eq!(write_prints(false), vec![Print{
+    text: vec!["Wrote 2 bytes to path `numbers2`".to_owned()],
+    n: false,
+    l: false,
+  }]);
+  assert_eq!(write_prints(true), vec![]);
+}
+
 // Fully specified to other path
 #[test]
 fn write_fully_specified() {